@@ -1,8 +1,16 @@
-use std::{collections::VecDeque, fs, path::PathBuf};
+use std::{
+    collections::{HashSet, VecDeque},
+    fs,
+    path::PathBuf,
+    time::Duration,
+};
 
-use tokio::io::AsyncWriteExt;
+use sha2::{Digest, Sha256, Sha512};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-use crate::handlers::error::Error;
+use crate::handlers::{config::AppConfig, error::Error};
+
+use super::process::invoke_ffprobe_duration;
 
 #[derive(serde::Serialize)]
 #[serde(tag = "type")]
@@ -14,6 +22,8 @@ pub enum SearchEntry {
         relative: String,
         name: Option<String>,
         children: Vec<SearchEntry>,
+        /// Whether directory has any entry, even if children are not expanded.
+        has_children: bool,
         #[serde(skip_serializing)]
         path: PathBuf,
     },
@@ -29,26 +39,43 @@ pub enum SearchEntry {
     },
 }
 
+/// Returns path of `absolute` relative to `search_dir`,
+/// or `None` if `absolute` is not inside `search_dir`, such as a symlink target elsewhere.
+///
+/// Relative path of `search_dir` itself is the whole path.
+/// `search_dir` is matched by whole components, `/videos2` is not inside `/videos`.
+fn relative_path<'a>(absolute: &'a str, search_dir: &str) -> Option<&'a str> {
+    if absolute == search_dir {
+        return Some(absolute);
+    }
+
+    let relative = absolute.strip_prefix(search_dir)?;
+    if relative.starts_with(std::path::is_separator)
+        || search_dir.ends_with(std::path::is_separator)
+    {
+        Some(relative)
+    } else {
+        None
+    }
+}
+
 impl SearchEntry {
+    /// Creates an entry from path, skipped if path is not inside `search_dir` once canonicalized.
     fn from_path(path: PathBuf, search_dir: &str) -> Option<Self> {
         let Ok(absolute) = path.canonicalize().map(|s| s.to_string_lossy().to_string()) else {
             return None;
         };
-
-        let relative_slice = if search_dir == absolute {
-            0..absolute.as_bytes().len()
-        } else {
-            search_dir.as_bytes().len()..absolute.as_bytes().len()
-        };
+        let relative = relative_path(&absolute, search_dir)?.to_string();
 
         if path.is_dir() {
             let name = path.file_name().map(|s| s.to_string_lossy().to_string());
 
             Some(SearchEntry::Directory {
-                relative: absolute[relative_slice].to_string(),
+                relative,
                 absolute,
                 name,
                 children: Vec::with_capacity(12),
+                has_children: false,
                 path,
             })
         } else if path.is_file() {
@@ -57,7 +84,7 @@ impl SearchEntry {
             };
 
             Some(SearchEntry::File {
-                relative: absolute[relative_slice].to_string(),
+                relative,
                 absolute,
                 name,
                 stem: path.file_stem().map(|s| s.to_string_lossy().to_string()),
@@ -78,67 +105,171 @@ impl SearchEntry {
     }
 }
 
-/// A command finds all files(in relative path) from a directory recursively
-/// and returns a flatten files list will be returned.
-///
-/// `mex_depth` tells how depth should recursively search in, default for `5`.
-/// For performance considering, always provides a small value.
-#[tauri::command]
-pub async fn search_directory(dir: String, max_depth: Option<usize>) -> Result<SearchEntry, Error> {
-    let max_depth = max_depth.unwrap_or(5);
+/// Creates an entry of a directory entry found by [`search`],
+/// skipped if it is a symlinked directory and `follow_symlinks` is `false`,
+/// or if it is not inside `relative_to`.
+fn child_entry(
+    entry: &fs::DirEntry,
+    relative_to: &str,
+    follow_symlinks: bool,
+) -> Option<SearchEntry> {
+    let is_symlink = entry.file_type().map(|t| t.is_symlink()).unwrap_or(false);
+    if is_symlink && !follow_symlinks && entry.path().is_dir() {
+        return None;
+    }
+
+    SearchEntry::from_path(entry.path(), relative_to)
+}
 
+/// Searches a directory recursively, relative paths of entries are computed against `relative_to`.
+///
+/// Directories deeper than `max_depth` are not expanded,
+/// but `has_children` of them are still checked by reading the first entry only.
+///
+/// Each directory is expanded at most once by its canonicalized path,
+/// preventing symlink loops (`a -> b -> a`) from being walked over and over.
+/// Symlinked directories are skipped entirely unless `follow_symlinks` is `true`,
+/// and are skipped still if pointing outside `relative_to`.
+fn search(
+    dir: String,
+    relative_to: &str,
+    max_depth: usize,
+    follow_symlinks: bool,
+) -> Result<SearchEntry, Error> {
     let search_dir = PathBuf::from(&dir);
     if !search_dir.is_dir() {
         return Err(Error::directory_not_found(dir));
     }
 
-    let Ok(search_dir_absolute) = search_dir
-        .canonicalize()
-        .map(|p| p.to_string_lossy().to_string())
-    else {
-        return Err(Error::directory_not_found(dir));
-    };
-
-    let Some(mut root) = SearchEntry::from_path(search_dir, &search_dir_absolute).map(|e| (e))
-    else {
+    let Some(mut root) = SearchEntry::from_path(search_dir, relative_to) else {
         return Err(Error::directory_not_found(dir));
     };
 
     let root_ptr: *mut SearchEntry = &mut root;
     let mut directories = VecDeque::from([(root_ptr, 0)]);
+    let mut visited = HashSet::new();
     while let Some((current_dir_ptr, depth)) = directories.pop_front() {
         let current_dir = unsafe { &mut *current_dir_ptr };
 
-        let SearchEntry::Directory { children, path, .. } = current_dir else {
+        let SearchEntry::Directory {
+            absolute,
+            children,
+            has_children,
+            path,
+            ..
+        } = current_dir
+        else {
             continue;
         };
 
-        let Ok(mut entries) = fs::read_dir(path) else {
+        // already expanded somewhere else, reached again from a symlink
+        if !visited.insert(absolute.clone()) {
+            continue;
+        }
+
+        let Ok(entries) = fs::read_dir(path) else {
             continue;
         };
 
-        while let Some(next_entry) = entries
-            .next()
-            .and_then(|e| e.ok())
-            .and_then(|e| SearchEntry::from_path(e.path(), &search_dir_absolute))
-        {
-            children.push(next_entry);
+        let mut entries = entries
+            .filter_map(|e| e.ok())
+            .filter_map(|entry| child_entry(&entry, relative_to, follow_symlinks));
+
+        // directories beyond max depth are not expanded
+        if depth > max_depth {
+            *has_children = entries.next().is_some();
+            continue;
         }
 
+        children.extend(entries);
+        *has_children = !children.is_empty();
+
         let next_depth = depth + 1;
-        if next_depth <= max_depth {
-            children.iter_mut().for_each(|child| {
-                if child.is_dir() {
-                    let child_ptr: *mut SearchEntry = child;
-                    directories.push_back((child_ptr, next_depth));
-                }
-            })
-        }
+        children.iter_mut().for_each(|child| {
+            if child.is_dir() {
+                let child_ptr: *mut SearchEntry = child;
+                directories.push_back((child_ptr, next_depth));
+            }
+        })
     }
 
     Ok(root)
 }
 
+/// Returns depth searched by [`search_directory`], at most `1` if `lazy`.
+fn search_max_depth(max_depth: Option<usize>, lazy: Option<bool>) -> usize {
+    let max_depth = max_depth.unwrap_or(5);
+    if lazy.unwrap_or(false) {
+        max_depth.min(1)
+    } else {
+        max_depth
+    }
+}
+
+/// A command finds all files(in relative path) from a directory recursively
+/// and returns a flatten files list will be returned.
+///
+/// `mex_depth` tells how depth should recursively search in, default for `5`.
+/// For performance considering, always provides a small value.
+///
+/// If `lazy` is `true`, only the top two levels are expanded no matter what `max_depth` is,
+/// deeper directories are expanded on demand by [`expand_directory`].
+///
+/// Symlinked directories are skipped unless `follow_symlinks` is `true`, default for `false`.
+#[tauri::command]
+pub async fn search_directory(
+    dir: String,
+    max_depth: Option<usize>,
+    lazy: Option<bool>,
+    follow_symlinks: Option<bool>,
+) -> Result<SearchEntry, Error> {
+    let max_depth = search_max_depth(max_depth, lazy);
+
+    let Ok(search_dir_absolute) = PathBuf::from(&dir)
+        .canonicalize()
+        .map(|p| p.to_string_lossy().to_string())
+    else {
+        return Err(Error::directory_not_found(dir));
+    };
+
+    search(
+        dir,
+        &search_dir_absolute,
+        max_depth,
+        follow_symlinks.unwrap_or(false),
+    )
+}
+
+/// A command expands a directory not expanded by a lazy [`search_directory`],
+/// `max_depth` is default for `1`.
+///
+/// Relative paths are computed against `root` if specified,
+/// which should be the directory searched by [`search_directory`], keeping relative paths consistent.
+/// Otherwise, relative paths are computed against the directory itself.
+#[tauri::command]
+pub async fn expand_directory(
+    dir: String,
+    max_depth: Option<usize>,
+    root: Option<String>,
+    follow_symlinks: Option<bool>,
+) -> Result<SearchEntry, Error> {
+    let max_depth = max_depth.unwrap_or(1);
+
+    let Ok(relative_to) = PathBuf::from(root.as_ref().unwrap_or(&dir))
+        .canonicalize()
+        .map(|p| p.to_string_lossy().to_string())
+    else {
+        return Err(Error::directory_not_found(root.unwrap_or(dir)));
+    };
+
+    search(
+        dir,
+        &relative_to,
+        max_depth,
+        follow_symlinks.unwrap_or(false),
+    )
+}
+
 /// Writes text content to specified path.
 #[tauri::command]
 pub async fn write_text_file(path: String, content: String) -> Result<(), Error> {
@@ -155,3 +286,244 @@ pub async fn write_text_file(path: String, content: String) -> Result<(), Error>
 
     Ok(())
 }
+
+/// Hash algorithms accepted by [`file_hash`].
+static HASH_ALGORITHMS: [&'static str; 2] = ["sha256", "sha512"];
+
+/// Size of buffer reading file when hashing.
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Hashes a file by streaming, the file is never loaded into memory fully.
+async fn hash_file<D: Digest>(path: &str) -> Result<String, Error> {
+    let mut file = tokio::fs::File::open(path).await.map_err(Error::io)?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0; HASH_BUFFER_SIZE];
+    loop {
+        let len = file.read(&mut buffer).await.map_err(Error::io)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buffer[..len]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// A command returns hex encoded content hash of a file by an algorithm in [`HASH_ALGORITHMS`],
+/// for skipping re-encoding identical outputs.
+#[tauri::command]
+pub async fn file_hash(path: String, algo: String) -> Result<String, Error> {
+    let algo = algo.to_lowercase();
+    if !HASH_ALGORITHMS.iter().any(|a| *a == algo.as_str()) {
+        return Err(Error::unsupported_hash_algorithm(algo));
+    }
+    if !PathBuf::from(&path).is_file() {
+        return Err(Error::file_not_found(path));
+    }
+
+    match algo.as_str() {
+        "sha512" => hash_file::<Sha512>(&path).await,
+        _ => hash_file::<Sha256>(&path).await,
+    }
+}
+
+/// A command checks whether a file is no longer growing,
+/// by comparing file size and modified time between two reads separated by `settle_ms`,
+/// default for `1000` milliseconds.
+///
+/// If `probe_duration` is `true`, media durations probed by ffprobe are compared as well.
+#[tauri::command]
+pub async fn is_stable(
+    config: tauri::State<'_, AppConfig>,
+    path: String,
+    settle_ms: Option<u64>,
+    probe_duration: Option<bool>,
+) -> Result<bool, Error> {
+    let settle = Duration::from_millis(settle_ms.unwrap_or(1000));
+    let ffprobe = if probe_duration.unwrap_or(false) {
+        match config.lock().await.as_ref() {
+            Some(config) => Some(config.ffprobe().to_string()),
+            None => return Err(Error::configuration_not_loaded()),
+        }
+    } else {
+        None
+    };
+
+    let before = tokio::fs::metadata(&path).await.map_err(Error::io)?;
+    let duration_before = match &ffprobe {
+        Some(ffprobe) => invoke_ffprobe_duration(ffprobe, &path).await?,
+        None => None,
+    };
+
+    tokio::time::sleep(settle).await;
+
+    let after = tokio::fs::metadata(&path).await.map_err(Error::io)?;
+    let duration_after = match &ffprobe {
+        Some(ffprobe) => invoke_ffprobe_duration(ffprobe, &path).await?,
+        None => None,
+    };
+
+    let stable = before.len() == after.len()
+        && before.modified().ok() == after.modified().ok()
+        && duration_before == duration_after;
+    Ok(stable)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{relative_path, search, search_max_depth, SearchEntry};
+
+    /// Creates an empty temporary directory, canonicalized.
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("search-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    fn children(entry: &SearchEntry) -> (&Vec<SearchEntry>, bool) {
+        match entry {
+            SearchEntry::Directory {
+                children,
+                has_children,
+                ..
+            } => (children, *has_children),
+            SearchEntry::File { .. } => panic!("not a directory"),
+        }
+    }
+
+    #[test]
+    fn strips_search_dir() {
+        assert_eq!(
+            relative_path("/videos/a/b.mp4", "/videos"),
+            Some("/a/b.mp4")
+        );
+        assert_eq!(relative_path("/videos", "/videos"), Some("/videos"));
+    }
+
+    #[test]
+    fn rejects_paths_outside_search_dir() {
+        assert_eq!(relative_path("/music/b.mp3", "/videos"), None);
+        assert_eq!(relative_path("/vid", "/videos"), None);
+        assert_eq!(relative_path("/videos2/a.mp4", "/videos"), None);
+        assert_eq!(relative_path("/videos/a.mp4", "/videos/"), Some("a.mp4"));
+    }
+
+    #[test]
+    fn limits_depth_if_lazy() {
+        assert_eq!(search_max_depth(None, None), 5);
+        assert_eq!(search_max_depth(Some(3), Some(false)), 3);
+        assert_eq!(search_max_depth(Some(3), Some(true)), 1);
+        assert_eq!(search_max_depth(None, Some(true)), 1);
+        assert_eq!(search_max_depth(Some(0), Some(true)), 0);
+    }
+
+    #[test]
+    fn expands_top_two_levels_if_lazy() {
+        let root = temp_dir();
+        fs::create_dir_all(root.join("a").join("b").join("c")).unwrap();
+        fs::write(root.join("a").join("b").join("c").join("d.mp4"), b"").unwrap();
+
+        let root_str = root.to_string_lossy().to_string();
+        let entry = search(
+            root_str.clone(),
+            &root_str,
+            search_max_depth(Some(5), Some(true)),
+            false,
+        )
+        .unwrap();
+
+        let (level0, _) = children(&entry);
+        assert_eq!(level0.len(), 1);
+        let (level1, _) = children(&level0[0]);
+        assert_eq!(level1.len(), 1);
+        let (level2, has_children) = children(&level1[0]);
+        assert!(level2.is_empty());
+        assert!(has_children);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_symlinks_outside_search_dir() {
+        let root = temp_dir();
+        let outside = temp_dir();
+        fs::write(outside.join("a.mp4"), b"").unwrap();
+        std::os::unix::fs::symlink(outside.join("a.mp4"), root.join("a.mp4")).unwrap();
+
+        let root_str = root.to_string_lossy().to_string();
+        let entry = search(root_str.clone(), &root_str, 5, true).unwrap();
+        let (children, has_children) = children(&entry);
+        assert!(children.is_empty());
+        assert!(!has_children);
+
+        fs::remove_dir_all(root).unwrap();
+        fs::remove_dir_all(outside).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn expands_symlink_loops_once() {
+        let root = temp_dir();
+        fs::create_dir(root.join("a")).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("a").join("loop")).unwrap();
+
+        let root_str = root.to_string_lossy().to_string();
+        let entry = search(root_str.clone(), &root_str, 5, false).unwrap();
+        let (level0, _) = children(&entry);
+        let (level1, has_children) = children(&level0[0]);
+        assert!(level1.is_empty());
+        assert!(!has_children);
+
+        let entry = search(root_str.clone(), &root_str, 5, true).unwrap();
+        let (level0, _) = children(&entry);
+        let (level1, _) = children(&level0[0]);
+        assert_eq!(level1.len(), 1);
+        // root is expanded already, never again from the symlink
+        let (level2, _) = children(&level1[0]);
+        assert!(level2.is_empty());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_symlinks_into_sibling_with_same_prefix() {
+        let root = temp_dir();
+        let sibling = PathBuf::from(format!("{}2", root.display()));
+        fs::create_dir(&sibling).unwrap();
+        fs::write(sibling.join("a.mp4"), b"").unwrap();
+        std::os::unix::fs::symlink(sibling.join("a.mp4"), root.join("a.mp4")).unwrap();
+
+        let root_str = root.to_string_lossy().to_string();
+        let entry = search(root_str.clone(), &root_str, 5, true).unwrap();
+        let (children, has_children) = children(&entry);
+        assert!(children.is_empty());
+        assert!(!has_children);
+
+        fs::remove_dir_all(root).unwrap();
+        fs::remove_dir_all(sibling).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_filtered_entries_beyond_max_depth() {
+        let root = temp_dir();
+        let outside = temp_dir();
+        fs::create_dir_all(root.join("a").join("b")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("a").join("b").join("link")).unwrap();
+
+        let root_str = root.to_string_lossy().to_string();
+        let entry = search(root_str.clone(), &root_str, 1, false).unwrap();
+        let (level0, _) = children(&entry);
+        let (level1, _) = children(&level0[0]);
+        let (level2, has_children) = children(&level1[0]);
+        assert!(level2.is_empty());
+        assert!(!has_children, "only a skipped symlinked directory inside");
+
+        fs::remove_dir_all(root).unwrap();
+        fs::remove_dir_all(outside).unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 pub mod fs;
-pub mod system;
+pub mod probe;
 pub mod process;
+pub mod system;
 pub mod task;
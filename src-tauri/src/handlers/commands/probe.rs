@@ -0,0 +1,1005 @@
+use std::{
+    collections::HashMap,
+    io::{BufReader, Read},
+    process::Stdio,
+    time::Duration,
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    handlers::{config::AppConfig, error::Error},
+    with_default_args,
+};
+
+use super::process::{
+    create_process, create_std_process, invoke_ffmpeg, invoke_ffprobe, invoke_ffprobe_duration,
+};
+
+/// Output formats accepted by [`ffprobe_query`].
+static QUERY_FORMATS: [&'static str; 4] = ["json", "csv", "flat", "default"];
+
+/// Maximum amount of frames extracted by [`filmstrip`].
+pub const MAX_FILMSTRIP_FRAMES: usize = 60;
+/// Minimum and maximum width of frames extracted by [`filmstrip`], in pixels.
+pub const FILMSTRIP_WIDTH_RANGE: (u32, u32) = (16, 480);
+
+/// Maximum duration ffmpeg runs for validating a filtergraph by [`validate_filtergraph`].
+const FILTERGRAPH_VALIDATION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Duration in seconds of each input read and of output written when validating a filtergraph.
+static FILTERGRAPH_VALIDATION_DURATION: &'static str = "1";
+
+/// Signature of PNG file.
+static PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Stream of a media probed by ffprobe.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ProbeStream {
+    pub index: usize,
+    pub codec_type: Option<String>,
+    pub codec_name: Option<String>,
+    pub codec_long_name: Option<String>,
+    pub profile: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub pix_fmt: Option<String>,
+    pub sample_rate: Option<String>,
+    pub channels: Option<u32>,
+    pub channel_layout: Option<String>,
+    pub bit_rate: Option<String>,
+    pub duration: Option<String>,
+    #[serde(default)]
+    pub disposition: HashMap<String, u8>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+/// Chapter of a media probed by ffprobe.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ProbeChapter {
+    pub id: i64,
+    pub time_base: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeStreams {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeChapters {
+    #[serde(default)]
+    chapters: Vec<ProbeChapter>,
+}
+
+/// Invokes ffprobe in a blocking child process and deserializes JSON output
+/// from stdout pipe directly, without collecting the whole output into memory first.
+///
+/// Only fields declared in `T` are kept,
+/// which reduces peak memory for medias having thousands of streams or chapters.
+fn invoke_ffprobe_json_streaming<T>(ffprobe: &str, args: &[&str]) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    let (result, stderr) = invoke_ffprobe_json_streaming_with_stderr::<T>(ffprobe, args)?;
+    if !stderr.trim().is_empty() {
+        Err(Error::ffprobe_runtime_error(stderr.trim()))
+    } else {
+        result.map_err(Error::internal)
+    }
+}
+
+/// Same as [`invoke_ffprobe_json_streaming`], but returns deserialized output and stderr separately,
+/// leaving callers to decide whether errors printed by ffprobe fail.
+fn invoke_ffprobe_json_streaming_with_stderr<T>(
+    ffprobe: &str,
+    args: &[&str],
+) -> Result<(Result<T, serde_json::Error>, String), Error>
+where
+    T: DeserializeOwned,
+{
+    let mut child = create_std_process(ffprobe, args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => Error::ffprobe_not_found(ffprobe),
+            std::io::ErrorKind::PermissionDenied => Error::binary_not_executable(ffprobe),
+            _ => Error::ffprobe_unavailable_with_raw_error(ffprobe, err),
+        })?;
+
+    // stderr is read in another thread while deserializing stdout,
+    // otherwise ffprobe blocks on writing a full stderr pipe and never closes stdout
+    let stderr_pipe = child.stderr.take();
+    let stderr_reader = std::thread::spawn(move || {
+        let mut stderr = String::new();
+        if let Some(mut pipe) = stderr_pipe {
+            let _ = pipe.read_to_string(&mut stderr);
+        }
+        stderr
+    });
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap()); // safely unwrap
+    let result = serde_json::from_reader::<_, T>(&mut stdout);
+    // drains stdout left by a failed deserialization, so that ffprobe never blocks on writing
+    let _ = std::io::copy(&mut stdout, &mut std::io::sink());
+
+    let stderr = stderr_reader.join().unwrap_or_default();
+    child.wait().map_err(Error::internal)?;
+
+    Ok((result, stderr))
+}
+
+/// Invokes ffprobe in a blocking child process and returns streams of a media.
+pub async fn invoke_ffprobe_streams(ffprobe: &str, path: &str) -> Result<Vec<ProbeStream>, Error> {
+    let (ffprobe, path) = (ffprobe.to_string(), path.to_string());
+    let probed = tokio::task::spawn_blocking(move || {
+        invoke_ffprobe_json_streaming::<ProbeStreams>(
+            &ffprobe,
+            with_default_args!("-print_format", "json", "-show_streams", &path),
+        )
+    })
+    .await
+    .map_err(Error::internal)??;
+
+    Ok(probed.streams)
+}
+
+/// Streams of a media probed by ffprobe, along with errors printed by ffprobe.
+pub struct ProbedStreams {
+    /// `None` if ffprobe prints nothing recognizable, such as for an unrecognized media.
+    pub streams: Option<Vec<ProbeStream>>,
+    /// Errors printed by ffprobe, empty if none.
+    pub stderr: String,
+}
+
+/// Invokes ffprobe in a blocking child process and returns streams of a media,
+/// without failing if ffprobe prints errors, such as a corrupted frame of a playable media.
+pub async fn invoke_ffprobe_probed_streams(
+    ffprobe: &str,
+    path: &str,
+) -> Result<ProbedStreams, Error> {
+    let (ffprobe, path) = (ffprobe.to_string(), path.to_string());
+    let (result, stderr) = tokio::task::spawn_blocking(move || {
+        invoke_ffprobe_json_streaming_with_stderr::<ProbeStreams>(
+            &ffprobe,
+            with_default_args!("-print_format", "json", "-show_streams", &path),
+        )
+    })
+    .await
+    .map_err(Error::internal)??;
+
+    Ok(ProbedStreams {
+        streams: result.ok().map(|probed| probed.streams),
+        stderr: stderr.trim().to_string(),
+    })
+}
+
+/// A command returns streams of a media using ffprobe.
+#[tauri::command]
+pub async fn probe_streams(
+    config: tauri::State<'_, AppConfig>,
+    path: String,
+) -> Result<Vec<ProbeStream>, Error> {
+    let ffprobe = match config.lock().await.as_ref() {
+        Some(config) => config.ffprobe().to_string(),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    invoke_ffprobe_streams(&ffprobe, &path).await
+}
+
+/// A command returns chapters of a media using ffprobe.
+#[tauri::command]
+pub async fn probe_chapters(
+    config: tauri::State<'_, AppConfig>,
+    path: String,
+) -> Result<Vec<ProbeChapter>, Error> {
+    let ffprobe = match config.lock().await.as_ref() {
+        Some(config) => config.ffprobe().to_string(),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    let probed = tokio::task::spawn_blocking(move || {
+        invoke_ffprobe_json_streaming::<ProbeChapters>(
+            &ffprobe,
+            with_default_args!("-print_format", "json", "-show_chapters", &path),
+        )
+    })
+    .await
+    .map_err(Error::internal)??;
+
+    Ok(probed.chapters)
+}
+
+/// Compact summary of a video stream.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VideoStreamSummary {
+    pub index: usize,
+    pub codec: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Average frame rate in frames per second.
+    pub frame_rate: Option<f64>,
+    pub pix_fmt: Option<String>,
+}
+
+/// Compact summary of an audio stream.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AudioStreamSummary {
+    pub index: usize,
+    pub codec: Option<String>,
+    pub channels: Option<u32>,
+    pub sample_rate: Option<u32>,
+    pub language: Option<String>,
+}
+
+/// Compact summary of a subtitle stream.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SubtitleSummary {
+    pub index: usize,
+    pub codec: Option<String>,
+    pub language: Option<String>,
+}
+
+/// Compact summary of a media, for list views.
+/// Uses [`media_metadata`](super::task::media_metadata) for full details.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MediaSummary {
+    /// Container format names, such as `mov,mp4,m4a,3gp,3g2,mj2`.
+    pub container: String,
+    /// Duration in seconds.
+    pub duration: Option<f64>,
+    /// Overall bitrate in bits per second.
+    pub bitrate: Option<u64>,
+    /// File size in bytes.
+    pub size: Option<u64>,
+    pub video: Vec<VideoStreamSummary>,
+    pub audio: Vec<AudioStreamSummary>,
+    pub subtitle: Vec<SubtitleSummary>,
+}
+
+#[derive(Default, serde::Deserialize)]
+struct ProbeSummaryFormat {
+    #[serde(default)]
+    format_name: String,
+    duration: Option<String>,
+    bit_rate: Option<String>,
+    size: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeSummaryStream {
+    index: usize,
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    avg_frame_rate: Option<String>,
+    pix_fmt: Option<String>,
+    channels: Option<u32>,
+    sample_rate: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeSummary {
+    #[serde(default)]
+    format: ProbeSummaryFormat,
+    #[serde(default)]
+    streams: Vec<ProbeSummaryStream>,
+}
+
+/// Parses a frame rate printed by ffprobe, such as `30000/1001`.
+/// Returns `None` for `0/0`, which ffprobe prints if frame rate is unknown.
+fn parse_frame_rate(rate: &str) -> Option<f64> {
+    let (num, den) = rate.split_once('/')?;
+    let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+    (den != 0.0 && num != 0.0).then(|| num / den)
+}
+
+impl ProbeSummary {
+    fn into_media_summary(self) -> MediaSummary {
+        let mut summary = MediaSummary {
+            container: self.format.format_name,
+            duration: self
+                .format
+                .duration
+                .and_then(|d| d.parse::<f64>().ok())
+                .filter(|d| d.is_finite()),
+            bitrate: self.format.bit_rate.and_then(|b| b.parse().ok()),
+            size: self.format.size.and_then(|s| s.parse().ok()),
+            video: Vec::new(),
+            audio: Vec::new(),
+            subtitle: Vec::new(),
+        };
+
+        for mut stream in self.streams {
+            let language = stream.tags.remove("language");
+            match stream.codec_type.as_deref() {
+                Some("video") => summary.video.push(VideoStreamSummary {
+                    index: stream.index,
+                    codec: stream.codec_name,
+                    width: stream.width,
+                    height: stream.height,
+                    frame_rate: stream.avg_frame_rate.as_deref().and_then(parse_frame_rate),
+                    pix_fmt: stream.pix_fmt,
+                }),
+                Some("audio") => summary.audio.push(AudioStreamSummary {
+                    index: stream.index,
+                    codec: stream.codec_name,
+                    channels: stream.channels,
+                    sample_rate: stream.sample_rate.and_then(|r| r.parse().ok()),
+                    language,
+                }),
+                Some("subtitle") => summary.subtitle.push(SubtitleSummary {
+                    index: stream.index,
+                    codec: stream.codec_name,
+                    language,
+                }),
+                _ => {}
+            }
+        }
+
+        summary
+    }
+}
+
+/// A command returns a compact summary of format and streams of a media using ffprobe.
+#[tauri::command]
+pub async fn media_summary(
+    config: tauri::State<'_, AppConfig>,
+    path: String,
+) -> Result<MediaSummary, Error> {
+    let ffprobe = match config.lock().await.as_ref() {
+        Some(config) => config.ffprobe().to_string(),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    let probed = tokio::task::spawn_blocking(move || {
+        invoke_ffprobe_json_streaming::<ProbeSummary>(
+            &ffprobe,
+            with_default_args!(
+                "-print_format",
+                "json",
+                "-show_format",
+                "-show_streams",
+                &path
+            ),
+        )
+    })
+    .await
+    .map_err(Error::internal)??;
+
+    Ok(probed.into_media_summary())
+}
+
+/// Transfer characteristics of HDR videos, PQ and HLG.
+static HDR_TRANSFERS: [&'static str; 2] = ["smpte2084", "arib-std-b67"];
+
+/// Mastering display color volume of a HDR video, values are rationals printed by ffprobe, such as `34000/50000`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MasteringDisplay {
+    pub red_x: Option<String>,
+    pub red_y: Option<String>,
+    pub green_x: Option<String>,
+    pub green_y: Option<String>,
+    pub blue_x: Option<String>,
+    pub blue_y: Option<String>,
+    pub white_point_x: Option<String>,
+    pub white_point_y: Option<String>,
+    pub min_luminance: Option<String>,
+    pub max_luminance: Option<String>,
+}
+
+/// Content light level of a HDR video, in nits.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct ContentLightLevel {
+    /// MaxCLL
+    pub max_content: Option<u32>,
+    /// MaxFALL
+    pub max_average: Option<u32>,
+}
+
+/// Color metadata of the first video stream of a media.
+/// All fields are `None` for medias without video stream,
+/// and HDR fields are `None` for SDR videos.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ColorInfo {
+    pub color_range: Option<String>,
+    pub color_primaries: Option<String>,
+    pub color_transfer: Option<String>,
+    pub color_space: Option<String>,
+    pub mastering_display: Option<MasteringDisplay>,
+    pub content_light_level: Option<ContentLightLevel>,
+    /// Whether transfer characteristic is PQ or HLG.
+    pub is_hdr: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeSideData {
+    side_data_type: Option<String>,
+    #[serde(flatten)]
+    mastering_display: Option<MasteringDisplay>,
+    max_content: Option<u32>,
+    max_average: Option<u32>,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeColorStream {
+    color_range: Option<String>,
+    color_primaries: Option<String>,
+    color_transfer: Option<String>,
+    color_space: Option<String>,
+    #[serde(default)]
+    side_data_list: Vec<ProbeSideData>,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeColorFrame {
+    #[serde(default)]
+    side_data_list: Vec<ProbeSideData>,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeColor {
+    #[serde(default)]
+    streams: Vec<ProbeColorStream>,
+    #[serde(default)]
+    frames: Vec<ProbeColorFrame>,
+}
+
+impl ProbeColor {
+    /// Collects color info from the first video stream.
+    /// Side data are looked up in stream first, then in the first frame,
+    /// since some containers only carry them in frames.
+    fn into_color_info(self) -> ColorInfo {
+        let mut frames = self.frames.into_iter();
+        let Some(stream) = self.streams.into_iter().next() else {
+            return ColorInfo::default();
+        };
+
+        let mut side_data_list = stream.side_data_list;
+        if let Some(frame) = frames.next() {
+            side_data_list.extend(frame.side_data_list);
+        }
+
+        let mut mastering_display = None;
+        let mut content_light_level = None;
+        for side_data in side_data_list {
+            match side_data.side_data_type.as_deref() {
+                Some("Mastering display metadata") if mastering_display.is_none() => {
+                    mastering_display = side_data.mastering_display;
+                }
+                Some("Content light level metadata") if content_light_level.is_none() => {
+                    content_light_level = Some(ContentLightLevel {
+                        max_content: side_data.max_content,
+                        max_average: side_data.max_average,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let is_hdr = stream
+            .color_transfer
+            .as_deref()
+            .map(|transfer| HDR_TRANSFERS.contains(&transfer))
+            .unwrap_or(false);
+
+        ColorInfo {
+            color_range: stream.color_range,
+            color_primaries: stream.color_primaries,
+            color_transfer: stream.color_transfer,
+            color_space: stream.color_space,
+            mastering_display,
+            content_light_level,
+            is_hdr,
+        }
+    }
+}
+
+/// A command returns color metadata of the first video stream of a media using ffprobe,
+/// including HDR mastering display and content light level side data read from the first frame.
+#[tauri::command]
+pub async fn probe_color(
+    config: tauri::State<'_, AppConfig>,
+    path: String,
+) -> Result<ColorInfo, Error> {
+    let ffprobe = match config.lock().await.as_ref() {
+        Some(config) => config.ffprobe().to_string(),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    let probed = tokio::task::spawn_blocking(move || {
+        invoke_ffprobe_json_streaming::<ProbeColor>(
+            &ffprobe,
+            with_default_args!(
+                "-print_format",
+                "json",
+                "-select_streams",
+                "v:0",
+                "-show_streams",
+                "-show_frames",
+                "-read_intervals",
+                "%+#1",
+                &path
+            ),
+        )
+    })
+    .await
+    .map_err(Error::internal)??;
+
+    Ok(probed.into_color_info())
+}
+
+/// Validates entries of [`ffprobe_query`], such as `stream=index,codec_name:format=duration`.
+///
+/// Entries are passed as a single argument so there is no injection risk,
+/// but obviously malformed input is rejected early rather than confusing ffprobe.
+fn validate_query_entries(entries: &str) -> Result<(), Error> {
+    if entries.is_empty() {
+        return Err(Error::invalid_ffprobe_query("entries is empty"));
+    }
+    if entries.starts_with('-') {
+        return Err(Error::invalid_ffprobe_query("entries starts with '-'"));
+    }
+    if let Some(c) = entries
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || "_=:,.-".contains(*c)))
+    {
+        return Err(Error::invalid_ffprobe_query(format!(
+            "unexpected character '{}' in entries",
+            c.escape_default()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Prefixes a relative path starting with `-` with `./`,
+/// so that ffprobe never parses it as an option, such as a file named `-show_data`.
+fn positional_path(path: &str) -> String {
+    if path.starts_with('-') {
+        format!("./{path}")
+    } else {
+        path.to_string()
+    }
+}
+
+/// A command runs ffprobe with custom `-show_entries` against a media and returns stdout as is.
+#[tauri::command]
+pub async fn ffprobe_query(
+    config: tauri::State<'_, AppConfig>,
+    path: String,
+    entries: String,
+    format: String,
+) -> Result<String, Error> {
+    if !QUERY_FORMATS.iter().any(|f| *f == format.as_str()) {
+        return Err(Error::invalid_ffprobe_query(format!(
+            "unsupported output format {}",
+            format
+        )));
+    }
+    validate_query_entries(&entries)?;
+    let path = positional_path(&path);
+
+    let ffprobe = match config.lock().await.as_ref() {
+        Some(config) => config.ffprobe().to_string(),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    let output = invoke_ffprobe(
+        &ffprobe,
+        with_default_args!("-show_entries", &entries, "-of", &format, &path),
+    )
+    .await?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.trim().is_empty() {
+        Err(Error::ffprobe_runtime_error(stderr.trim()))
+    } else {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+/// Splits concatenated PNG images piped out by ffmpeg `image2pipe` muxer.
+/// Each image ends at its `IEND` chunk, incomplete trailing image is dropped.
+fn split_png_stream(mut bytes: &[u8]) -> Vec<Vec<u8>> {
+    let mut images = Vec::new();
+    'images: while bytes.starts_with(&PNG_SIGNATURE) {
+        let mut offset = PNG_SIGNATURE.len();
+        loop {
+            // length(4) + type(4) + data(length) + crc(4)
+            let Some(header) = bytes.get(offset..offset + 8) else {
+                break 'images;
+            };
+            let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+            let chunk_type = &header[4..8];
+            offset += 8 + length + 4;
+            if offset > bytes.len() {
+                break 'images;
+            }
+
+            if chunk_type == b"IEND" {
+                break;
+            }
+        }
+
+        images.push(bytes[..offset].to_vec());
+        bytes = &bytes[offset..];
+    }
+
+    images
+}
+
+/// A command extracts `count` evenly spaced frames of a media as small PNG images for scrubbable preview,
+/// by a single ffmpeg call.
+///
+/// `count` is bounded by [`MAX_FILMSTRIP_FRAMES`] and `width` is clamped into [`FILMSTRIP_WIDTH_RANGE`],
+/// height is scaled by keeping aspect ratio.
+/// Fewer frames than requested are returned for very short medias.
+/// If duration of media is unknown, leading frames are returned instead.
+#[tauri::command]
+pub async fn filmstrip(
+    config: tauri::State<'_, AppConfig>,
+    path: String,
+    count: usize,
+    width: u32,
+) -> Result<Vec<Vec<u8>>, Error> {
+    let count = count.min(MAX_FILMSTRIP_FRAMES);
+    if count == 0 {
+        return Ok(vec![]);
+    }
+    let width = width.clamp(FILMSTRIP_WIDTH_RANGE.0, FILMSTRIP_WIDTH_RANGE.1);
+
+    let (ffmpeg, ffprobe) = match config.lock().await.as_ref() {
+        Some(config) => (config.ffmpeg().to_string(), config.ffprobe().to_string()),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    if !std::path::Path::new(&path).is_file() {
+        return Err(Error::file_not_found(path));
+    }
+
+    let scale = format!("scale={}:-2", width);
+    let filter = match invoke_ffprobe_duration(&ffprobe, &path).await? {
+        Some(duration) if duration > 0.0 => format!("fps={}/{},{}", count, duration, scale),
+        _ => scale,
+    };
+    let frames = count.to_string();
+    let output = invoke_ffmpeg(
+        &ffmpeg,
+        with_default_args!(
+            "-i",
+            &path,
+            "-an",
+            "-sn",
+            "-vf",
+            &filter,
+            "-frames:v",
+            &frames,
+            "-f",
+            "image2pipe",
+            "-c:v",
+            "png",
+            "-"
+        ),
+    )
+    .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::ffmpeg_runtime_error(
+            stderr.lines().last().unwrap_or_default().trim(),
+        ));
+    }
+
+    Ok(split_png_stream(&output.stdout))
+}
+
+/// Result of validating a filtergraph.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FilterValidation {
+    pub valid: bool,
+    /// The first error line printed by ffmpeg, such as `No such filter: 'scalee'`.
+    pub error: Option<String>,
+}
+
+/// A command validates a `-filter_complex` graph by running it against inputs briefly,
+/// for giving immediate feedback in a filtergraph editor.
+///
+/// Only the first second of each input is read, and outputs of graph are written to `null` muxer
+/// limited to a single video frame and a second, since generated sources never end by themselves.
+/// Graphs without inputs should generate their own sources, such as `testsrc` or `anullsrc`.
+/// Labeled outputs of graph are never mapped, hence graphs should leave them unlabeled.
+///
+/// ffmpeg is killed after [`FILTERGRAPH_VALIDATION_TIMEOUT`],
+/// graph is regarded as invalid then since it is never proven to run through.
+#[tauri::command]
+pub async fn validate_filtergraph(
+    config: tauri::State<'_, AppConfig>,
+    graph: String,
+    inputs: Vec<String>,
+) -> Result<FilterValidation, Error> {
+    let ffmpeg = match config.lock().await.as_ref() {
+        Some(config) => config.ffmpeg().to_string(),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    let process = create_process(&ffmpeg, filtergraph_args(&graph, &inputs))
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(FILTERGRAPH_VALIDATION_TIMEOUT, process).await {
+        Ok(output) => Some(output.map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => Error::ffmpeg_not_found(&ffmpeg),
+            std::io::ErrorKind::PermissionDenied => Error::binary_not_executable(&ffmpeg),
+            _ => Error::ffmpeg_unavailable_with_raw_error(&ffmpeg, err),
+        })?),
+        Err(_) => None,
+    };
+
+    Ok(filter_validation(output.as_ref()))
+}
+
+/// Returns args of ffmpeg validating a filtergraph against inputs.
+fn filtergraph_args<'a>(graph: &'a str, inputs: &'a [String]) -> Vec<&'a str> {
+    let input_args = inputs
+        .iter()
+        .flat_map(|input| ["-t", FILTERGRAPH_VALIDATION_DURATION, "-i", input.as_str()]);
+    ["-hide_banner", "-loglevel", "error", "-nostdin"]
+        .into_iter()
+        .chain(input_args)
+        .chain(["-filter_complex", graph])
+        .chain(["-frames:v", "1", "-t", FILTERGRAPH_VALIDATION_DURATION])
+        .chain(["-f", "null", "-"])
+        .collect()
+}
+
+/// Interprets output of ffmpeg validating a filtergraph, `None` if ffmpeg timed out.
+fn filter_validation(output: Option<&std::process::Output>) -> FilterValidation {
+    let Some(output) = output else {
+        return FilterValidation {
+            valid: false,
+            error: Some(format!(
+                "validation timed out after {} seconds",
+                FILTERGRAPH_VALIDATION_TIMEOUT.as_secs()
+            )),
+        };
+    };
+
+    if output.status.success() {
+        return FilterValidation {
+            valid: true,
+            error: None,
+        };
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let error = stderr
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .map(|line| line.to_string());
+    FilterValidation {
+        valid: false,
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        filter_validation, filtergraph_args, parse_frame_rate, positional_path, split_png_stream,
+        validate_query_entries, ProbeSummary, PNG_SIGNATURE,
+    };
+
+    #[test]
+    fn validates_query_entries() {
+        assert!(validate_query_entries("stream=index,codec_name:format=duration").is_ok());
+        assert!(validate_query_entries("").is_err());
+        assert!(validate_query_entries("-show_data").is_err());
+        assert!(validate_query_entries("stream=index;rm").is_err());
+    }
+
+    #[test]
+    fn guards_paths_starting_with_dash() {
+        assert_eq!(positional_path("-show_data"), "./-show_data");
+        assert_eq!(positional_path("-"), "./-");
+        assert_eq!(positional_path("a-b.mp4"), "a-b.mp4");
+        assert_eq!(positional_path("/media/-a.mp4"), "/media/-a.mp4");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_large_stderr_while_deserializing() {
+        use super::invoke_ffprobe_json_streaming_with_stderr;
+
+        // 1 MiB of stderr fills the pipe long before stdout is written
+        let script = "head -c 1048576 /dev/zero | tr '\\0' x >&2; echo '{\"streams\":[]}'";
+        let (result, stderr) =
+            invoke_ffprobe_json_streaming_with_stderr::<serde_json::Value>("sh", &["-c", script])
+                .unwrap();
+        assert_eq!(result.unwrap(), serde_json::json!({ "streams": [] }));
+        assert_eq!(stderr.len(), 1048576);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn drains_stdout_failing_deserialization() {
+        use super::invoke_ffprobe_json_streaming_with_stderr;
+
+        let script = "echo '{'; head -c 1048576 /dev/zero; echo failed >&2";
+        let (result, stderr) =
+            invoke_ffprobe_json_streaming_with_stderr::<serde_json::Value>("sh", &["-c", script])
+                .unwrap();
+        assert!(result.is_err());
+        assert_eq!(stderr, "failed\n");
+    }
+
+    #[test]
+    fn limits_output_of_validation() {
+        let inputs = vec!["a.mp4".to_string()];
+        let args = filtergraph_args("[0:v]scale=640:-2", &inputs);
+        assert_eq!(
+            args,
+            vec![
+                "-hide_banner",
+                "-loglevel",
+                "error",
+                "-nostdin",
+                "-t",
+                "1",
+                "-i",
+                "a.mp4",
+                "-filter_complex",
+                "[0:v]scale=640:-2",
+                "-frames:v",
+                "1",
+                "-t",
+                "1",
+                "-f",
+                "null",
+                "-",
+            ]
+        );
+    }
+
+    #[test]
+    fn regards_timeout_as_invalid() {
+        let validation = filter_validation(None);
+        assert!(!validation.valid);
+        assert!(validation.error.is_some());
+    }
+
+    #[cfg(unix)]
+    fn output(code: i32, stderr: &str) -> std::process::Output {
+        use std::os::unix::process::ExitStatusExt;
+
+        std::process::Output {
+            status: std::process::ExitStatus::from_raw(code << 8),
+            stdout: Vec::new(),
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn accepts_valid_graph() {
+        let validation = filter_validation(Some(&output(0, "")));
+        assert!(validation.valid);
+        assert_eq!(validation.error, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_first_error_of_invalid_graph() {
+        let stderr = "\n[AVFilterGraph @ 0x55d1] No such filter: 'scalee'\n\
+            Error initializing complex filters.\n";
+        let validation = filter_validation(Some(&output(8, stderr)));
+        assert!(!validation.valid);
+        assert_eq!(
+            validation.error.as_deref(),
+            Some("[AVFilterGraph @ 0x55d1] No such filter: 'scalee'")
+        );
+    }
+
+    /// A minimal PNG with a header chunk of `data` and an end chunk, crc are not checked.
+    fn png(data: &[u8]) -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        for (chunk_type, data) in [(b"IHDR", data), (b"IEND", &[][..])] {
+            png.extend((data.len() as u32).to_be_bytes());
+            png.extend(chunk_type);
+            png.extend(data);
+            png.extend([0; 4]);
+        }
+        png
+    }
+
+    #[test]
+    fn splits_concatenated_pngs() {
+        let (first, second) = (png(b"first"), png(b"second image"));
+        let stream = [first.clone(), second.clone()].concat();
+        assert_eq!(split_png_stream(&stream), vec![first, second]);
+    }
+
+    #[test]
+    fn drops_incomplete_trailing_png() {
+        let first = png(b"first");
+        let second = png(b"second");
+        let stream = [&first[..], &second[..second.len() - 1]].concat();
+        assert_eq!(split_png_stream(&stream), vec![first]);
+        assert!(split_png_stream(b"not a png").is_empty());
+    }
+
+    #[test]
+    fn summarizes_probed_media() {
+        let probed: ProbeSummary = serde_json::from_value(serde_json::json!({
+            "format": {
+                "format_name": "matroska,webm",
+                "duration": "60.500000",
+                "bit_rate": "4000000",
+                "size": "30250000",
+            },
+            "streams": [
+                {
+                    "index": 0,
+                    "codec_type": "video",
+                    "codec_name": "h264",
+                    "width": 1920,
+                    "height": 1080,
+                    "avg_frame_rate": "30000/1001",
+                    "pix_fmt": "yuv420p",
+                },
+                {
+                    "index": 1,
+                    "codec_type": "audio",
+                    "codec_name": "aac",
+                    "channels": 2,
+                    "sample_rate": "48000",
+                    "tags": { "language": "eng" },
+                },
+                { "index": 2, "codec_type": "subtitle", "codec_name": "subrip" },
+                { "index": 3, "codec_type": "attachment" },
+            ],
+        }))
+        .unwrap();
+
+        let summary = probed.into_media_summary();
+        assert_eq!(summary.container, "matroska,webm");
+        assert_eq!(summary.duration, Some(60.5));
+        assert_eq!(summary.bitrate, Some(4000000));
+        assert_eq!(summary.size, Some(30250000));
+        assert_eq!(summary.video.len(), 1);
+        assert_eq!(summary.video[0].width, Some(1920));
+        assert!((summary.video[0].frame_rate.unwrap() - 29.97).abs() < 0.01);
+        assert_eq!(summary.audio.len(), 1);
+        assert_eq!(summary.audio[0].sample_rate, Some(48000));
+        assert_eq!(summary.audio[0].language.as_deref(), Some("eng"));
+        assert_eq!(summary.subtitle.len(), 1);
+        assert_eq!(summary.subtitle[0].index, 2);
+    }
+
+    #[test]
+    fn summarizes_missing_format_fields() {
+        let probed: ProbeSummary = serde_json::from_value(serde_json::json!({
+            "format": { "duration": "N/A" },
+        }))
+        .unwrap();
+
+        let summary = probed.into_media_summary();
+        assert_eq!(summary.container, "");
+        assert_eq!(summary.duration, None);
+        assert_eq!(summary.bitrate, None);
+        assert!(summary.video.is_empty());
+        assert_eq!(parse_frame_rate("0/0"), None);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{ffi::OsStr, process::Output};
+use std::{collections::HashMap, ffi::OsStr, path::Path, process::Output};
 
 use tokio::process::Command;
 
@@ -15,19 +15,232 @@ macro_rules! with_default_args {
     };
 }
 
+/// Shells a command line is quoted for by [`quote_command`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum Shell {
+    /// POSIX shells, such as `sh`, `bash` and `zsh`.
+    Posix,
+    /// `cmd.exe` on Windows.
+    Cmd,
+    /// PowerShell on Windows.
+    PowerShell,
+}
+
+impl Shell {
+    /// Returns the default shell of current platform, `cmd.exe` on Windows and POSIX shells otherwise.
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            Self::Cmd
+        } else {
+            Self::Posix
+        }
+    }
+}
+
+/// Quotes an argument, making it safe to be copied and pasted into a shell.
+///
+/// - For POSIX shells, arguments are wrapped in single quotes
+/// and embedded single quotes are escaped as `'\''`.
+/// - For `cmd.exe`, arguments are quoted by rules of `CommandLineToArgvW` first, see [`quote_argv`].
+/// If metacharacters of `cmd.exe` present, such as `%`, `^` and `&`,
+/// all of them including double quotes are escaped by `^` then.
+/// - For PowerShell, arguments are wrapped in single quotes and embedded single quotes are doubled.
+/// PowerShell before 7.3 drops embedded double quotes when passing arguments to native programs,
+/// arguments containing double quotes are not preserved there.
+pub fn quote_arg(arg: &str, shell: Shell) -> String {
+    match shell {
+        Shell::Posix => {
+            let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./:=,+@%^".contains(c);
+            if !arg.is_empty() && arg.chars().all(is_safe) {
+                return arg.to_string();
+            }
+
+            format!("'{}'", arg.replace('\'', "'\\''"))
+        }
+        Shell::Cmd => {
+            static METACHARACTERS: &'static str = "()%!^\"<>&|";
+
+            // double quotes are left alone if nothing else needs escaping
+            let quoted = quote_argv(arg);
+            if !quoted.contains(|c| c != '"' && METACHARACTERS.contains(c)) {
+                return quoted;
+            }
+
+            let mut escaped = String::with_capacity(quoted.len() * 2);
+            for c in quoted.chars() {
+                if METACHARACTERS.contains(c) {
+                    escaped.push('^');
+                }
+                escaped.push(c);
+            }
+            escaped
+        }
+        Shell::PowerShell => {
+            let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./:=+\\".contains(c);
+            if !arg.is_empty() && arg.chars().all(is_safe) {
+                return arg.to_string();
+            }
+
+            format!("'{}'", arg.replace('\'', "''"))
+        }
+    }
+}
+
+/// Quotes an argument by rules of `CommandLineToArgvW`, which programs on Windows parse arguments by.
+///
+/// Arguments are wrapped in double quotes, embedded double quotes are escaped by backslashes
+/// and backslashes preceding double quotes are doubled.
+fn quote_argv(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '"']) {
+        return arg.to_string();
+    }
+
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(std::iter::repeat('\\').take(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat('\\').take(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    // doubles trailing backslashes, so that they do not escape the closing quote
+    quoted.extend(std::iter::repeat('\\').take(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+/// Joins program and arguments into a command line for a shell, with arguments quoted by [`quote_arg`].
+///
+/// For PowerShell, a quoted program is invoked by call operator `&`,
+/// since a quoted string is an expression rather than a command there.
+pub fn quote_command<I, S>(program: &str, args: I, shell: Shell) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let quoted_program = quote_arg(program, shell);
+    let program = if shell == Shell::PowerShell && quoted_program != program {
+        format!("& {quoted_program}")
+    } else {
+        quoted_program
+    };
+    std::iter::once(program)
+        .chain(args.into_iter().map(|arg| quote_arg(arg.as_ref(), shell)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Splits a program into an executable and leading args,
+/// such as `nice -n 10 ffmpeg` for running ffmpeg by a wrapper command.
+///
+/// Program is split by whitespaces, whitespaces in single or double quotes are kept.
+/// Backslashes are never regarded as escapes, since they are path separators on Windows.
+/// A program which is an existing file is never split, such as paths containing spaces.
+/// On Windows, extensions in `PATHEXT` are tried as well, since `.exe` is usually omitted.
+///
+/// Returns `None` if program is empty or has an unterminated quote.
+pub fn split_program(program: &str) -> Option<(String, Vec<String>)> {
+    if is_existing_program(program) {
+        return Some((program.to_string(), Vec::new()));
+    }
+
+    let mut parts = Vec::new();
+    let mut part: Option<String> = None;
+    let mut quote: Option<char> = None;
+    for c in program.chars() {
+        match (quote, c) {
+            (Some(q), c) if q == c => quote = None,
+            (Some(_), c) => part.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                part.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => parts.extend(part.take()),
+            (None, c) => part.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    parts.extend(part);
+
+    let mut parts = parts.into_iter();
+    let executable = parts.next()?;
+    Some((executable, parts.collect()))
+}
+
+/// Default executable extensions on Windows, used if `PATHEXT` is not set.
+#[cfg(windows)]
+const DEFAULT_PATHEXT: &'static str = ".COM;.EXE;.BAT;.CMD";
+
+/// Returns `true` if program is an existing file,
+/// or on Windows, an existing file once appended by any extension in `PATHEXT`.
+fn is_existing_program(program: &str) -> bool {
+    if Path::new(program).is_file() {
+        return true;
+    }
+
+    #[cfg(windows)]
+    {
+        let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| DEFAULT_PATHEXT.to_string());
+        if Path::new(program).extension().is_none()
+            && extensions
+                .split(';')
+                .filter(|extension| !extension.is_empty())
+                .any(|extension| Path::new(&format!("{program}{extension}")).is_file())
+        {
+            return true;
+        }
+    }
+
+    false
+}
+
 pub fn create_process<I, S>(program: &str, args: I) -> Command
 where
     I: IntoIterator<Item = S>,
     S: AsRef<OsStr>,
 {
-    let mut command = Command::new(program);
+    Command::from(create_std_process(program, args))
+}
+
+/// Creates a blocking process, for consumers requiring [`std::io::Read`] on outputs.
+///
+/// Program may be a wrapper command, see [`split_program`].
+/// Program is used as is if it fails to split, leaving spawning to report the error.
+///
+/// `LC_ALL=C` is always set to the child environment.
+pub fn create_std_process<I, S>(program: &str, args: I) -> std::process::Command
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let (executable, leading_args) =
+        split_program(program).unwrap_or_else(|| (program.to_string(), Vec::new()));
+    let mut command = std::process::Command::new(executable);
 
     #[cfg(windows)]
     {
+        use std::os::windows::process::CommandExt;
+
         const CREATE_NO_WINDOW: u32 = 0x08000000;
         command.creation_flags(CREATE_NO_WINDOW);
     };
 
+    // forces C locale, ensuring decimals are separated by `.` and outputs are in English,
+    // which progress parsing and banner extracting rely on
+    command.env("LC_ALL", "C");
+    command.args(leading_args);
     command.args(args);
     command
 }
@@ -44,6 +257,7 @@ where
         Ok(output) => Ok(output),
         Err(err) => match err.kind() {
             std::io::ErrorKind::NotFound => Err(Error::ffmpeg_not_found(ffmpeg)),
+            std::io::ErrorKind::PermissionDenied => Err(Error::binary_not_executable(ffmpeg)),
             _ => Err(Error::ffmpeg_unavailable_with_raw_error(ffmpeg, err)),
         },
     }
@@ -61,6 +275,7 @@ where
         Ok(output) => Ok(output),
         Err(err) => match err.kind() {
             std::io::ErrorKind::NotFound => Err(Error::ffprobe_not_found(ffprobe)),
+            std::io::ErrorKind::PermissionDenied => Err(Error::binary_not_executable(ffprobe)),
             _ => Err(Error::ffprobe_unavailable_with_raw_error(ffprobe, err)),
         },
     }
@@ -90,3 +305,293 @@ pub async fn invoke_ffprobe_json_metadata(ffprobe: &str, path: &str) -> Result<S
         Ok(stdout.to_string())
     }
 }
+
+/// Durations of media probed by ffprobe.
+#[derive(serde::Deserialize)]
+struct DurationProbe {
+    #[serde(default)]
+    streams: Vec<DurationProbeStream>,
+    format: Option<DurationProbeFormat>,
+}
+
+#[derive(serde::Deserialize)]
+struct DurationProbeStream {
+    codec_type: Option<String>,
+    duration: Option<String>,
+    #[serde(default)]
+    disposition: HashMap<String, u8>,
+}
+
+#[derive(serde::Deserialize)]
+struct DurationProbeFormat {
+    duration: Option<String>,
+}
+
+/// Parses a duration printed by ffprobe, `N/A` and non-finite durations are regarded as no duration.
+fn parse_probed_duration(duration: Option<&str>) -> Option<f64> {
+    duration
+        .map(|duration| duration.trim())
+        .filter(|duration| !duration.eq_ignore_ascii_case("N/A"))
+        .and_then(|duration| duration.parse::<f64>().ok())
+        .filter(|duration| duration.is_finite())
+}
+
+/// Invokes ffprobe in child process and gets duration of media in seconds.
+/// Returns `None` if duration is not available.
+///
+/// Duration of primary media streams is preferred, which is the longest audio or video stream.
+/// Attached pictures, such as cover arts, are ignored since they may report odd durations.
+/// Duration of container is used if no primary media stream reports a duration.
+///
+/// ffprobe prints `N/A` for medias without a known duration, such as live streams and raw streams,
+/// which is regarded as no duration rather than an error.
+/// Non-finite durations are regarded as no duration as well.
+pub async fn invoke_ffprobe_duration(ffprobe: &str, path: &str) -> Result<Option<f64>, Error> {
+    let output = invoke_ffprobe(
+        ffprobe,
+        with_default_args!(
+            "-show_entries",
+            "stream=codec_type,duration:stream_disposition=attached_pic:format=duration",
+            "-of",
+            "json",
+            &path
+        ),
+    )
+    .await?;
+
+    let probe = serde_json::from_slice::<DurationProbe>(&output.stdout).map_err(Error::internal)?;
+
+    let stream_duration = probe
+        .streams
+        .iter()
+        .filter(|stream| matches!(stream.codec_type.as_deref(), Some("audio") | Some("video")))
+        .filter(|stream| stream.disposition.get("attached_pic").copied().unwrap_or(0) == 0)
+        .filter_map(|stream| parse_probed_duration(stream.duration.as_deref()))
+        .max_by(|a, b| a.total_cmp(b));
+    if stream_duration.is_some() {
+        return Ok(stream_duration);
+    }
+
+    Ok(probe
+        .format
+        .and_then(|format| parse_probed_duration(format.duration.as_deref())))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use crate::handlers::test_support::fake_ffprobe;
+
+    #[cfg(unix)]
+    use super::invoke_ffprobe_duration;
+    use super::{
+        create_std_process, parse_probed_duration, quote_arg, quote_command, split_program, Shell,
+    };
+
+    fn owned(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn quotes_for_posix_shells() {
+        let quote = |arg| quote_arg(arg, Shell::Posix);
+        assert_eq!(quote("-c:v"), "-c:v");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("my file.mp4"), "'my file.mp4'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+    }
+
+    #[test]
+    fn quotes_for_cmd() {
+        let quote = |arg| quote_arg(arg, Shell::Cmd);
+        assert_eq!(quote("-c:v"), "-c:v");
+        assert_eq!(quote(""), r#""""#);
+        assert_eq!(quote("my file.mp4"), r#""my file.mp4""#);
+        assert_eq!(quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote(r"C:\out dir\"), r#""C:\out dir\\""#);
+        assert_eq!(quote("%03d.png"), "^%03d.png");
+        assert_eq!(quote("a&b"), "a^&b");
+        assert_eq!(
+            quote("scale=iw/2, crop=(iw)"),
+            r#"^"scale=iw/2, crop=^(iw^)^""#
+        );
+    }
+
+    #[test]
+    fn quotes_for_powershell() {
+        let quote = |arg| quote_arg(arg, Shell::PowerShell);
+        assert_eq!(quote("-c:v"), "-c:v");
+        assert_eq!(quote(r"C:\out\a.mp4"), r"C:\out\a.mp4");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("$env:TEMP"), "'$env:TEMP'");
+        assert_eq!(quote("it's"), "'it''s'");
+        assert_eq!(quote("a,b"), "'a,b'");
+    }
+
+    #[test]
+    fn invokes_quoted_programs_in_powershell() {
+        assert_eq!(
+            quote_command(
+                r"C:\Program Files\ffmpeg.exe",
+                ["-i", "in put.mp4"],
+                Shell::PowerShell
+            ),
+            r"& 'C:\Program Files\ffmpeg.exe' -i 'in put.mp4'"
+        );
+        assert_eq!(
+            quote_command("ffmpeg", ["-i", "in put.mp4"], Shell::PowerShell),
+            "ffmpeg -i 'in put.mp4'"
+        );
+    }
+
+    #[test]
+    fn splits_wrapper_commands() {
+        assert_eq!(
+            split_program("nice -n 10 ffmpeg"),
+            Some(("nice".to_string(), owned(&["-n", "10", "ffmpeg"])))
+        );
+        assert_eq!(
+            split_program("ffmpeg"),
+            Some(("ffmpeg".to_string(), vec![]))
+        );
+    }
+
+    #[test]
+    fn keeps_quoted_parts() {
+        assert_eq!(
+            split_program(r#""C:\Program Files\ffmpeg\bin\ffmpeg.exe" -hide_banner"#),
+            Some((
+                r"C:\Program Files\ffmpeg\bin\ffmpeg.exe".to_string(),
+                owned(&["-hide_banner"])
+            ))
+        );
+        assert_eq!(
+            split_program("nice -n 10 '/opt/my ffmpeg/ffmpeg'"),
+            Some((
+                "nice".to_string(),
+                owned(&["-n", "10", "/opt/my ffmpeg/ffmpeg"])
+            ))
+        );
+        assert_eq!(
+            split_program(r#"wrapper """#),
+            Some(("wrapper".to_string(), owned(&[""])))
+        );
+    }
+
+    #[test]
+    fn rejects_empty_and_unterminated_quotes() {
+        assert_eq!(split_program(""), None);
+        assert_eq!(split_program("   "), None);
+        assert_eq!(split_program(r#""C:\Program Files\ffmpeg.exe"#), None);
+    }
+
+    #[test]
+    fn keeps_existing_paths_with_spaces() {
+        let dir = std::env::temp_dir().join(format!("program dir {}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("my ffmpeg");
+        std::fs::write(&path, b"").unwrap();
+
+        let program = path.to_string_lossy().to_string();
+        assert_eq!(split_program(&program), Some((program.clone(), vec![])));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn keeps_existing_paths_without_extension() {
+        let dir = std::env::temp_dir().join(format!("program dir {}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ffmpeg.exe"), b"").unwrap();
+
+        let program = dir.join("ffmpeg").to_string_lossy().to_string();
+        assert_eq!(split_program(&program), Some((program.clone(), vec![])));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn forces_c_locale() {
+        let command = create_std_process("ffmpeg", ["-version"]);
+        let lc_all = command
+            .get_envs()
+            .find(|(key, _)| *key == "LC_ALL")
+            .and_then(|(_, value)| value);
+        assert_eq!(lc_all, Some(std::ffi::OsStr::new("C")));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reports_non_executable_binaries() {
+        use crate::handlers::error::Error;
+
+        use super::{invoke_ffmpeg, invoke_ffprobe};
+
+        let path = std::env::temp_dir().join(format!("ffmpeg-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "#!/bin/sh\n").unwrap();
+        let program = path.to_string_lossy();
+
+        assert!(matches!(
+            invoke_ffmpeg(&program, ["-version"]).await,
+            Err(Error::BinaryNotExecutable { .. })
+        ));
+        assert!(matches!(
+            invoke_ffprobe(&program, ["-version"]).await,
+            Err(Error::BinaryNotExecutable { .. })
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn treats_unavailable_durations_as_none() {
+        assert_eq!(parse_probed_duration(Some("12.345000")), Some(12.345));
+        assert_eq!(parse_probed_duration(Some(" 1.5\n")), Some(1.5));
+        for duration in [
+            Some("N/A"),
+            Some("n/a"),
+            Some("inf"),
+            Some("nan"),
+            Some(""),
+            None,
+        ] {
+            assert_eq!(parse_probed_duration(duration), None, "{duration:?}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn ignores_cover_art_durations() {
+        let cover = serde_json::json!({
+            "codec_type": "video",
+            "duration": "0.040000",
+            "disposition": { "attached_pic": 1 },
+        });
+        let audio = serde_json::json!({ "codec_type": "audio", "duration": "180.500000" });
+
+        let (dir, ffprobe) = fake_ffprobe(&[(
+            "song.mp3",
+            serde_json::json!({
+                "streams": [cover.clone(), audio],
+                "format": { "duration": "181.000000" },
+            }),
+        )]);
+        let duration = invoke_ffprobe_duration(&ffprobe, "song.mp3").await.unwrap();
+        assert_eq!(duration, Some(180.5));
+        std::fs::remove_dir_all(dir).unwrap();
+
+        // falls back to format duration if only cover art has a duration
+        let (dir, ffprobe) = fake_ffprobe(&[(
+            "song.mp3",
+            serde_json::json!({
+                "streams": [cover, { "codec_type": "audio", "duration": "N/A" }],
+                "format": { "duration": "181.000000" },
+            }),
+        )]);
+        let duration = invoke_ffprobe_duration(&ffprobe, "song.mp3").await.unwrap();
+        assert_eq!(duration, Some(181.0));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
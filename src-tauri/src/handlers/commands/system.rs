@@ -1,25 +1,48 @@
-use std::{collections::HashMap, path::PathBuf, sync::OnceLock};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    process::Stdio,
+    sync::{Mutex, OnceLock},
+    time::SystemTime,
+};
 
+use log::warn;
 use regex::Regex;
 
 use crate::{
     handlers::{
         config::{AppConfig, Config},
         error::Error,
+        tasks::{
+            dry_run::set_dry_run,
+            lifetime::set_detach_children,
+            priority::{is_background_mode, set_background_mode},
+            store::TaskStore,
+            validation::codec_args,
+        },
     },
+    system_tray::select_background_mode,
     with_default_args,
 };
 
-use super::process::{invoke_ffmpeg, invoke_ffprobe};
+use super::process::{create_process, invoke_ffmpeg, invoke_ffprobe, split_program};
+
+/// Finds which program a `-version` output belongs to, `ffmpeg` or `ffprobe`.
+fn version_banner_program(stdout: &str) -> Option<&'static str> {
+    let stdout = stdout.trim_start();
+    ["ffmpeg", "ffprobe"]
+        .into_iter()
+        .find(|program| stdout.starts_with(&format!("{program} version")))
+}
 
 #[tauri::command]
 pub async fn verify_ffmpeg(ffmpeg: String) -> Result<(), Error> {
     let output = invoke_ffmpeg(&ffmpeg, with_default_args!("-version")).await?;
     let stdout = String::from_utf8_lossy(&output.stdout);
-    if stdout.trim_start().starts_with("ffmpeg version") {
-        Ok(())
-    } else {
-        Err(Error::ffmpeg_unavailable(ffmpeg))
+    match version_banner_program(&stdout) {
+        Some("ffmpeg") => Ok(()),
+        Some(found) => Err(Error::binary_mismatch("ffmpeg", found)),
+        None => Err(Error::ffmpeg_unavailable(ffmpeg)),
     }
 }
 
@@ -27,10 +50,10 @@ pub async fn verify_ffmpeg(ffmpeg: String) -> Result<(), Error> {
 pub async fn verify_ffprobe(ffprobe: String) -> Result<(), Error> {
     let output = invoke_ffprobe(&ffprobe, with_default_args!("-version")).await?;
     let stdout = String::from_utf8_lossy(&output.stdout);
-    if stdout.trim_start().starts_with("ffprobe version") {
-        Ok(())
-    } else {
-        Err(Error::ffprobe_unavailable(ffprobe))
+    match version_banner_program(&stdout) {
+        Some("ffprobe") => Ok(()),
+        Some(found) => Err(Error::binary_mismatch("ffprobe", found)),
+        None => Err(Error::ffprobe_unavailable(ffprobe)),
     }
 }
 
@@ -46,15 +69,35 @@ pub async fn verify_directory(path: String) -> Result<(), Error> {
 /// System basic information.
 #[derive(Debug, serde::Serialize)]
 pub struct SystemParticulars {
+    logical_cores: usize,
     ffmpeg: FFmpegParticulars,
 }
 
+/// Returns amount of logical cores of this machine.
+///
+/// Falls back to `1` if it is unknown,
+/// since [`std::thread::available_parallelism`] fails in some constrained environments, such as containers.
+pub fn logical_cores() -> usize {
+    match std::thread::available_parallelism() {
+        Ok(cores) => cores.get(),
+        Err(err) => {
+            warn!(
+                "failed to get amount of logical cores, fallback to 1: {}",
+                err
+            );
+            1
+        }
+    }
+}
+
 /// FFmpeg situations.
 #[derive(Debug, serde::Serialize)]
 pub struct FFmpegParticulars {
     banner: FFmpegBanner,
     codecs: Vec<FFmpegCodec>,
     hwaccels: Vec<String>,
+    protocols: FFmpegProtocols,
+    formats: Vec<FFmpegFormat>,
 }
 
 /// FFmpeg banner information.
@@ -67,6 +110,13 @@ pub struct FFmpegBanner {
     libraries: HashMap<String, [usize; 6]>,
 }
 
+/// Protocols supported by FFmpeg.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FFmpegProtocols {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
 /// Codec types supported by FFmpeg.
 #[derive(Debug, serde_repr::Serialize_repr)]
 #[repr(u8)]
@@ -78,6 +128,15 @@ pub enum FFmpegCodecType {
     Attachment = 4,
 }
 
+/// Format supported by FFmpeg.
+#[derive(Debug, serde::Serialize)]
+pub struct FFmpegFormat {
+    name: String,
+    description: String,
+    demuxing: bool,
+    muxing: bool,
+}
+
 /// Codec supported by FFmpeg.
 #[derive(Debug, serde::Serialize)]
 pub struct FFmpegCodec {
@@ -93,31 +152,101 @@ pub struct FFmpegCodec {
     lossless: bool,
 }
 
+/// A command returns protocols supported by ffmpeg.
+#[tauri::command]
+pub async fn supported_protocols(
+    config: tauri::State<'_, AppConfig>,
+) -> Result<FFmpegProtocols, Error> {
+    let ffmpeg = match config.lock().await.as_ref() {
+        Some(config) => config.ffmpeg().to_string(),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    match cached_particulars(&ffmpeg).protocols {
+        Some(protocols) => Ok(protocols),
+        None => ffmpeg_protocols(&ffmpeg).await,
+    }
+}
+
+/// Validates and verifies ffmpeg and ffprobe programs of a configuration.
+///
+/// Failures of all programs are collected into [`Error::ConfigurationUnavailable`],
+/// each reason is an error specific to a program, such as [`Error::FFmpegUnavailable`].
+async fn verify_configuration(config: &Config) -> Result<(), Error> {
+    let ffmpeg = async {
+        config.validate_ffmpeg()?;
+        verify_ffmpeg(config.ffmpeg().to_string()).await
+    };
+    let ffprobe = async {
+        config.validate_ffprobe()?;
+        verify_ffprobe(config.ffprobe().to_string()).await
+    };
+
+    let reasons = [ffmpeg.await, ffprobe.await]
+        .into_iter()
+        .filter_map(Result::err)
+        .collect::<Vec<_>>();
+    if reasons.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::configuration_unavailable(reasons))
+    }
+}
+
 /// A command returns current system and ffmpeg particulars.
 #[tauri::command]
 pub async fn load_configuration(
+    app_handle: tauri::AppHandle,
     app_config: tauri::State<'_, AppConfig>,
-    config: Config,
+    task_store: tauri::State<'_, TaskStore>,
+    mut config: Config,
 ) -> Result<SystemParticulars, Error> {
+    config.apply_env_overrides();
+    verify_configuration(&config).await?;
+
     let ffmpeg = config.ffmpeg();
     let ffmpeg_banner = ffmpeg_banner(ffmpeg).await?;
     let ffmpeg_codecs = ffmpeg_codecs(ffmpeg).await?;
     let ffmpeg_hwaccels = ffmpeg_hwaccels(ffmpeg).await?;
+    let ffmpeg_protocols = ffmpeg_protocols(ffmpeg).await?;
+    let ffmpeg_formats = ffmpeg_formats(ffmpeg).await?;
     let ffmpeg_particular = FFmpegParticulars {
         banner: ffmpeg_banner,
         codecs: ffmpeg_codecs,
         hwaccels: ffmpeg_hwaccels,
+        protocols: ffmpeg_protocols,
+        formats: ffmpeg_formats,
     };
 
     let system_particulars = SystemParticulars {
+        logical_cores: logical_cores(),
         ffmpeg: ffmpeg_particular,
     };
 
+    let background_mode = config.background_mode();
+    if background_mode != is_background_mode() {
+        set_background_mode(background_mode);
+        task_store.apply_priority(background_mode).await;
+    }
+    select_background_mode(&app_handle, background_mode);
+    set_detach_children(config.detach_children());
+    set_dry_run(config.dry_run());
+
     *app_config.lock().await = Some(config);
 
     Ok(system_particulars)
 }
 
+/// A command returns configuration currently used by backend,
+/// with environment variable overrides applied.
+#[tauri::command]
+pub async fn effective_config(app_config: tauri::State<'_, AppConfig>) -> Result<Config, Error> {
+    match app_config.lock().await.as_ref() {
+        Some(config) => Ok(config.clone()),
+        None => Err(Error::configuration_not_loaded()),
+    }
+}
+
 /// Extracts ffmpeg basic information from banner and wraps them into [`Banner`].
 async fn ffmpeg_banner(ffmpeg: &str) -> Result<FFmpegBanner, Error> {
     static VERSION_AND_COPYRIGHT_EXTRACTOR: &'static str = r"^ffmpeg version (\S+) (.+)$";
@@ -131,6 +260,10 @@ async fn ffmpeg_banner(ffmpeg: &str) -> Result<FFmpegBanner, Error> {
     static LIBS_REGEX: OnceLock<Regex> = OnceLock::new();
 
     let output = invoke_ffmpeg(ffmpeg, with_default_args!("-version")).await?;
+    update_version_cache(
+        ffmpeg,
+        parse_ffmpeg_version(&String::from_utf8_lossy(&output.stdout)),
+    );
 
     let mut banner = FFmpegBanner {
         version: None,
@@ -214,48 +347,45 @@ async fn ffmpeg_banner(ffmpeg: &str) -> Result<FFmpegBanner, Error> {
     Ok(banner)
 }
 
-// /// Formats supported by FFmpeg.
-// #[derive(Debug, serde::Serialize)]
-// pub struct Format {
-//     name: String,
-//     description: String,
-//     demuxing: bool,
-//     muxing: bool,
-// }
-
-// /// Extracts ffmpeg formats and wraps into [`Format`].
-// async fn ffmpeg_formats(ffmpeg: &str) -> Result<Vec<Format>, Error> {
-//     static FORMAT_EXTRACTOR: &'static str = r"^ (.{1})(.{1}) (\S+) (.+)$";
-//     static FORMAT_REGEX: OnceLock<Regex> = OnceLock::new();
-
-//     let output = invoke_ffmpeg(ffmpeg, with_default_args!("-formats")).await?;
-
-//     let mut formats = Vec::with_capacity(128);
-//     let format_regex = FORMAT_REGEX.get_or_init(|| Regex::new(FORMAT_EXTRACTOR).unwrap());
-//     for line in String::from_utf8_lossy(&output.stdout).lines().skip(4) {
-//         let Some(caps) = format_regex.captures(line) else {
-//             continue;
-//         };
-
-//         let (Some(demuxing), Some(muxing), Some(name), Some(description)) = (
-//             caps.get(1).map(|m| m.as_str() == "D"),
-//             caps.get(2).map(|m| m.as_str() == "E"),
-//             caps.get(3).map(|m| m.as_str().trim().to_string()),
-//             caps.get(4).map(|m| m.as_str().trim().to_string()),
-//         ) else {
-//             continue;
-//         };
-
-//         formats.push(Format {
-//             name,
-//             description,
-//             demuxing,
-//             muxing,
-//         });
-//     }
-
-//     Ok(formats)
-// }
+/// Extracts ffmpeg formats and wraps into [`FFmpegFormat`].
+///
+/// A line listing several names separated by commas, such as `mov,mp4,m4a`,
+/// is split into several formats sharing the same description and flags.
+/// Device flag printed by newer ffmpeg is accepted but ignored.
+async fn ffmpeg_formats(ffmpeg: &str) -> Result<Vec<FFmpegFormat>, Error> {
+    static FORMAT_EXTRACTOR: &'static str = r"^ ([D ])([E ])(?:[d ])? (\S+) +(.+)$";
+    static FORMAT_REGEX: OnceLock<Regex> = OnceLock::new();
+
+    let output = invoke_ffmpeg(ffmpeg, with_default_args!("-formats")).await?;
+
+    let mut formats = Vec::with_capacity(512);
+    let format_regex = FORMAT_REGEX.get_or_init(|| Regex::new(FORMAT_EXTRACTOR).unwrap());
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some(caps) = format_regex.captures(line) else {
+            continue;
+        };
+
+        let (Some(demuxing), Some(muxing), Some(names), Some(description)) = (
+            caps.get(1).map(|m| m.as_str() == "D"),
+            caps.get(2).map(|m| m.as_str() == "E"),
+            caps.get(3).map(|m| m.as_str().trim()),
+            caps.get(4).map(|m| m.as_str().trim().to_string()),
+        ) else {
+            continue;
+        };
+
+        for name in names.split(',').filter(|name| !name.is_empty()) {
+            formats.push(FFmpegFormat {
+                name: name.to_string(),
+                description: description.clone(),
+                demuxing,
+                muxing,
+            });
+        }
+    }
+
+    Ok(formats)
+}
 
 /// Extracts ffmpeg codecs and wraps into [`Codec`].
 async fn ffmpeg_codecs(ffmpeg: &str) -> Result<Vec<FFmpegCodec>, Error> {
@@ -356,17 +486,479 @@ async fn ffmpeg_codecs(ffmpeg: &str) -> Result<Vec<FFmpegCodec>, Error> {
         });
     }
 
+    update_particulars_cache(ffmpeg, |cache| {
+        cache.decoders = Some(coder_names(&codecs, false));
+        cache.encoders = Some(coder_names(&codecs, true));
+    });
+
     Ok(codecs)
 }
 
-/// Extracts ffmpeg hard acceleration methods.
+/// Returns names accepted by `-c` as encoders if `encode` is `true`, or as decoders otherwise,
+/// which are all coders along with codecs having the flag, since `-c:v h264` picks the default coder.
+fn coder_names(codecs: &[FFmpegCodec], encode: bool) -> Vec<String> {
+    codecs
+        .iter()
+        .flat_map(|codec| {
+            let (flagged, coders) = if encode {
+                (codec.encode, &codec.encoders)
+            } else {
+                (codec.decode, &codec.decoders)
+            };
+            let name = flagged && !coders.contains(&codec.name);
+            coders.iter().chain(name.then_some(&codec.name)).cloned()
+        })
+        .collect()
+}
+
+/// Extracts ffmpeg hard acceleration methods and caches them.
+///
+/// The first line is header `Hardware acceleration methods:`, which is skipped.
+/// Blank lines, such as a line only containing a carriage return on Windows, are skipped as well.
 async fn ffmpeg_hwaccels(ffmpeg: &str) -> Result<Vec<String>, Error> {
     let output = invoke_ffmpeg(ffmpeg, with_default_args!("-hwaccels")).await?;
     let stdout = String::from_utf8_lossy(&output.stdout);
-    Ok(stdout
+    let hwaccels = stdout
         .lines()
         .skip(1)
+        .map(|method| method.trim())
         .filter(|method| !method.is_empty())
-        .map(|method| method.trim().to_string())
-        .collect())
+        .map(|method| method.to_string())
+        .collect::<Vec<_>>();
+
+    update_particulars_cache(ffmpeg, |cache| cache.hwaccels = Some(hwaccels.clone()));
+
+    Ok(hwaccels)
+}
+
+/// Particulars of an ffmpeg program cached when extracting,
+/// for validating tasks without invoking ffmpeg again.
+#[derive(Debug, Clone, Default)]
+pub struct CachedParticulars {
+    pub protocols: Option<FFmpegProtocols>,
+    pub hwaccels: Option<Vec<String>>,
+    /// Names accepted by `-c` of input args, see [`coder_names`].
+    pub decoders: Option<Vec<String>>,
+    /// Names accepted by `-c` of output args, see [`coder_names`].
+    pub encoders: Option<Vec<String>>,
+}
+
+/// Cached particulars of each ffmpeg program.
+fn particulars_cache() -> &'static Mutex<HashMap<String, CachedParticulars>> {
+    static PARTICULARS_CACHE: OnceLock<Mutex<HashMap<String, CachedParticulars>>> = OnceLock::new();
+    PARTICULARS_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Updates cached particulars of an ffmpeg program.
+fn update_particulars_cache<F>(ffmpeg: &str, f: F)
+where
+    F: FnOnce(&mut CachedParticulars),
+{
+    if let Ok(mut cache) = particulars_cache().lock() {
+        f(cache.entry(ffmpeg.to_string()).or_default());
+    }
+}
+
+/// Returns cached particulars of an ffmpeg program, if extracted before.
+pub fn cached_particulars(ffmpeg: &str) -> CachedParticulars {
+    particulars_cache()
+        .lock()
+        .ok()
+        .and_then(|cache| cache.get(ffmpeg).cloned())
+        .unwrap_or_default()
+}
+
+/// Semantic version of ffmpeg, as `(major, minor, patch)`.
+pub type FFmpegVersion = (usize, usize, usize);
+
+/// First libavutil version, as `(major, minor)`, shipped by each ffmpeg release, newest first.
+/// Git builds report no release version, which are regarded as the newest release they follow.
+static LIBAVUTIL_RELEASES: [((usize, usize), FFmpegVersion); 8] = [
+    ((59, 39), (7, 1, 0)),
+    ((59, 8), (7, 0, 0)),
+    ((58, 29), (6, 1, 0)),
+    ((58, 2), (6, 0, 0)),
+    ((57, 28), (5, 1, 0)),
+    ((57, 17), (5, 0, 0)),
+    ((56, 70), (4, 4, 0)),
+    ((56, 51), (4, 3, 0)),
+];
+
+/// Parses ffmpeg version from output of `ffmpeg -version`.
+///
+/// Release versions, such as `n6.1.1`, `4.4.2` or `6.1.1-3ubuntu5`, are parsed from the first line,
+/// with a missing patch version regarded as `0`.
+/// Git builds, such as `N-113000-g5f2b0bd` or `2024-01-01-git-abcdef`,
+/// are resolved by version of libavutil instead.
+fn parse_ffmpeg_version(stdout: &str) -> Option<FFmpegVersion> {
+    static RELEASE_EXTRACTOR: &'static str = r"^ffmpeg version n?(\d+)\.(\d+)(?:\.(\d+))?";
+    static LIBAVUTIL_EXTRACTOR: &'static str = r"^\s*libavutil\s+(\d+)\.\s*(\d+)\.";
+
+    static RELEASE_REGEX: OnceLock<Regex> = OnceLock::new();
+    static LIBAVUTIL_REGEX: OnceLock<Regex> = OnceLock::new();
+
+    let release_regex = RELEASE_REGEX.get_or_init(|| Regex::new(RELEASE_EXTRACTOR).unwrap());
+    let libavutil_regex = LIBAVUTIL_REGEX.get_or_init(|| Regex::new(LIBAVUTIL_EXTRACTOR).unwrap());
+
+    let mut lines = stdout.trim_start().lines();
+    if let Some(caps) = lines.next().and_then(|line| release_regex.captures(line)) {
+        let number = |i: usize| caps.get(i).and_then(|m| m.as_str().parse::<usize>().ok());
+        if let (Some(major), Some(minor)) = (number(1), number(2)) {
+            return Some((major, minor, number(3).unwrap_or(0)));
+        }
+    }
+
+    let (major, minor) = lines.find_map(|line| {
+        let caps = libavutil_regex.captures(line)?;
+        Some((
+            caps.get(1)?.as_str().parse::<usize>().ok()?,
+            caps.get(2)?.as_str().parse::<usize>().ok()?,
+        ))
+    })?;
+    LIBAVUTIL_RELEASES
+        .iter()
+        .find(|(libavutil, _)| (major, minor) >= *libavutil)
+        .map(|(_, version)| *version)
+}
+
+/// Version of an ffmpeg program cached along with modified time of its executable.
+#[derive(Debug, Clone, Copy)]
+struct CachedVersion {
+    modified: Option<SystemTime>,
+    version: Option<FFmpegVersion>,
+}
+
+/// Cached versions of each ffmpeg program.
+fn version_cache() -> &'static Mutex<HashMap<String, CachedVersion>> {
+    static VERSION_CACHE: OnceLock<Mutex<HashMap<String, CachedVersion>>> = OnceLock::new();
+    VERSION_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Returns modified time of executable of a program.
+/// Programs not being a path, such as programs found from `PATH`, have no modified time.
+fn executable_modified(program: &str) -> Option<SystemTime> {
+    let executable = split_program(program)
+        .map(|(executable, _)| executable)
+        .unwrap_or_else(|| program.to_string());
+    std::fs::metadata(executable)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Updates cached version of an ffmpeg program.
+fn update_version_cache(ffmpeg: &str, version: Option<FFmpegVersion>) {
+    if let Ok(mut cache) = version_cache().lock() {
+        cache.insert(
+            ffmpeg.to_string(),
+            CachedVersion {
+                modified: executable_modified(ffmpeg),
+                version,
+            },
+        );
+    }
+}
+
+/// Returns version of an ffmpeg program, for gating behaviors differing between ffmpeg versions.
+///
+/// Version is read from `ffmpeg -version` on first call and cached per program,
+/// which is extracted when loading configuration already in most cases.
+/// Cache is invalidated once executable of program is modified, such as ffmpeg being upgraded.
+///
+/// ffmpeg runs asynchronously, never blocking workers of async runtime on a cache miss.
+///
+/// Returns `None` if ffmpeg fails or version is not recognized.
+pub async fn ffmpeg_version(program: &str) -> Option<FFmpegVersion> {
+    let cached = version_cache()
+        .lock()
+        .ok()
+        .and_then(|cache| cache.get(program).copied());
+    if let Some(cached) = cached {
+        if cached.modified == executable_modified(program) {
+            return cached.version;
+        }
+    }
+
+    let output = create_process(program, with_default_args!("-version"))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .ok()?;
+    let version = parse_ffmpeg_version(&String::from_utf8_lossy(&output.stdout));
+    update_version_cache(program, version);
+
+    version
+}
+
+/// Types of a preset, mirroring presets saved by frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde_repr::Deserialize_repr)]
+#[repr(u8)]
+pub enum PresetType {
+    /// Args usable as both input and output args.
+    Universal = 0,
+    /// Input args, codecs are decoders.
+    Decode = 1,
+    /// Output args, codecs are encoders.
+    Encode = 2,
+}
+
+/// A preset saved by frontend, fields not validated are ignored.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Preset {
+    pub id: String,
+    pub r#type: PresetType,
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// Validation result of a preset.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PresetValidation {
+    pub id: String,
+    pub name: String,
+    pub ok: bool,
+    /// Codecs referenced by preset, which are unavailable in the current ffmpeg.
+    pub unknown_codecs: Vec<String>,
+}
+
+/// A command validates presets against the current ffmpeg,
+/// flagging codecs referenced by presets but unavailable in the current ffmpeg build,
+/// which happens after moving machines or changing ffmpeg builds.
+///
+/// Codecs of decode presets are checked against decoders and codecs of encode presets against encoders,
+/// while codecs of universal presets are accepted by either of them.
+/// Presets are stored by frontend, hence they are passed in.
+#[tauri::command]
+pub async fn validate_presets(
+    app_config: tauri::State<'_, AppConfig>,
+    presets: Vec<Preset>,
+) -> Result<Vec<PresetValidation>, Error> {
+    let ffmpeg = match app_config.lock().await.as_ref() {
+        Some(config) => config.ffmpeg().to_string(),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    let particulars = cached_particulars(&ffmpeg);
+    let (Some(decoders), Some(encoders)) = (particulars.decoders, particulars.encoders) else {
+        return Err(Error::configuration_not_loaded());
+    };
+
+    let validations = presets
+        .into_iter()
+        .map(|preset| {
+            let unknown_codecs = find_unknown_codecs(&preset, &decoders, &encoders);
+            PresetValidation {
+                id: preset.id,
+                name: preset.name,
+                ok: unknown_codecs.is_empty(),
+                unknown_codecs,
+            }
+        })
+        .collect();
+
+    Ok(validations)
+}
+
+/// Finds codecs referenced by a preset but neither decoders nor encoders, depending on type of preset.
+fn find_unknown_codecs(preset: &Preset, decoders: &[String], encoders: &[String]) -> Vec<String> {
+    codec_args(&preset.args)
+        .into_iter()
+        .filter(|codec| {
+            let is_decoder = decoders.iter().any(|decoder| decoder == codec);
+            let is_encoder = encoders.iter().any(|encoder| encoder == codec);
+            match preset.r#type {
+                PresetType::Universal => !is_decoder && !is_encoder,
+                PresetType::Decode => !is_decoder,
+                PresetType::Encode => !is_encoder,
+            }
+        })
+        .map(|codec| codec.to_string())
+        .collect()
+}
+
+/// Extracts ffmpeg supported protocols and caches them.
+async fn ffmpeg_protocols(ffmpeg: &str) -> Result<FFmpegProtocols, Error> {
+    let output = invoke_ffmpeg(ffmpeg, with_default_args!("-protocols")).await?;
+    let protocols = parse_protocols(&String::from_utf8_lossy(&output.stdout));
+
+    update_particulars_cache(ffmpeg, |cache| cache.protocols = Some(protocols.clone()));
+
+    Ok(protocols)
+}
+
+/// Parses output of `ffmpeg -protocols`, which lists protocols below `Input:` and `Output:` headers.
+fn parse_protocols(stdout: &str) -> FFmpegProtocols {
+    let mut protocols = FFmpegProtocols {
+        inputs: Vec::with_capacity(64),
+        outputs: Vec::with_capacity(64),
+    };
+
+    let mut section = None as Option<&mut Vec<String>>;
+    for line in stdout.lines() {
+        let line = line.trim();
+        match line {
+            "" => {}
+            "Input:" => section = Some(&mut protocols.inputs),
+            "Output:" => section = Some(&mut protocols.outputs),
+            _ if line.ends_with(':') => section = None,
+            _ => {
+                if let Some(section) = section.as_mut() {
+                    section.push(line.to_string());
+                }
+            }
+        }
+    }
+
+    protocols
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::{config::Config, error::Error};
+
+    use super::{
+        coder_names, find_unknown_codecs, logical_cores, parse_ffmpeg_version, parse_protocols,
+        verify_configuration, version_banner_program, FFmpegCodec, FFmpegCodecType, Preset,
+        PresetType,
+    };
+
+    #[test]
+    fn parses_release_versions() {
+        let stdout = "ffmpeg version n6.1.1 Copyright (c) 2000-2023 the FFmpeg developers\n\
+            built with gcc 13.2.1 (GCC) 20230801\n";
+        assert_eq!(parse_ffmpeg_version(stdout), Some((6, 1, 1)));
+
+        let stdout =
+            "ffmpeg version 4.4.2-0ubuntu0.22.04.1 Copyright (c) 2000-2021 the FFmpeg developers\n";
+        assert_eq!(parse_ffmpeg_version(stdout), Some((4, 4, 2)));
+
+        let stdout = "ffmpeg version 7.0 Copyright (c) 2000-2024 the FFmpeg developers\n";
+        assert_eq!(parse_ffmpeg_version(stdout), Some((7, 0, 0)));
+    }
+
+    #[test]
+    fn resolves_git_builds_by_libavutil() {
+        let stdout =
+            "ffmpeg version N-113000-g5f2b0bd Copyright (c) 2000-2023 the FFmpeg developers\n\
+            built with gcc 13.2.0 (crosstool-NG 1.25.0.232_c175b21)\n\
+            configuration: --enable-gpl\n\
+            libavutil      58. 36.100 / 58. 36.100\n\
+            libavcodec     60. 37.100 / 60. 37.100\n";
+        assert_eq!(parse_ffmpeg_version(stdout), Some((6, 1, 0)));
+
+        let stdout = "ffmpeg version 2024-07-01-git-abcdef-full_build-www.gyan.dev\n\
+            libavutil      59.  8.100 / 59.  8.100\n";
+        assert_eq!(parse_ffmpeg_version(stdout), Some((7, 0, 0)));
+    }
+
+    #[test]
+    fn rejects_unrecognized_versions() {
+        assert_eq!(parse_ffmpeg_version(""), None);
+        assert_eq!(
+            parse_ffmpeg_version("ffmpeg version N-1-gabcdef\nlibavcodec 60. 3.100\n"),
+            None
+        );
+    }
+
+    fn codec(name: &str, decoders: &[&str], encoders: &[&str]) -> FFmpegCodec {
+        FFmpegCodec {
+            name: name.to_string(),
+            description: String::new(),
+            decoders: decoders.iter().map(|coder| coder.to_string()).collect(),
+            encoders: encoders.iter().map(|coder| coder.to_string()).collect(),
+            r#type: FFmpegCodecType::Video,
+            decode: !decoders.is_empty(),
+            encode: !encoders.is_empty(),
+            intra: false,
+            lossy: true,
+            lossless: false,
+        }
+    }
+
+    fn preset(r#type: PresetType, args: &[&str]) -> Preset {
+        Preset {
+            id: "preset".to_string(),
+            r#type,
+            name: "preset".to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_codec_names_having_flags() {
+        let codecs = [
+            // a fake encoder listed by its coder name only
+            codec("h264", &["h264"], &["libx264", "h264_fake"]),
+            codec("prores", &["prores"], &[]),
+        ];
+        let decoders = coder_names(&codecs, false);
+        let encoders = coder_names(&codecs, true);
+        assert_eq!(decoders, vec!["h264", "prores"]);
+        assert_eq!(encoders, vec!["libx264", "h264_fake", "h264"]);
+
+        let encode = preset(PresetType::Encode, &["-c:v", "h264", "-c:v:1", "h264_fake"]);
+        assert!(find_unknown_codecs(&encode, &decoders, &encoders).is_empty());
+
+        let encode = preset(PresetType::Encode, &["-c:v", "prores", "-c:a", "copy"]);
+        assert_eq!(
+            find_unknown_codecs(&encode, &decoders, &encoders),
+            vec!["prores"]
+        );
+
+        let universal = preset(PresetType::Universal, &["-c:v", "prores"]);
+        assert!(find_unknown_codecs(&universal, &decoders, &encoders).is_empty());
+    }
+
+    #[test]
+    fn parses_input_and_output_protocols() {
+        let stdout = "Supported file protocols:\n\
+            Input:\n  file\n  http\n  pipe\n\
+            Output:\n  file\n  pipe\n  rtmp\n";
+        let protocols = parse_protocols(stdout);
+        assert_eq!(protocols.inputs, vec!["file", "http", "pipe"]);
+        assert_eq!(protocols.outputs, vec!["file", "pipe", "rtmp"]);
+    }
+
+    #[test]
+    fn tells_swapped_programs_by_version_banner() {
+        let ffmpeg = "ffmpeg version 6.1.1 Copyright (c) 2000-2023 the FFmpeg developers\n";
+        let ffprobe = "\nffprobe version 6.1.1 Copyright (c) 2007-2023 the FFmpeg developers\n";
+        assert_eq!(version_banner_program(ffmpeg), Some("ffmpeg"));
+        assert_eq!(version_banner_program(ffprobe), Some("ffprobe"));
+        assert_eq!(version_banner_program("ffplay version 6.1.1"), None);
+        assert_eq!(version_banner_program(""), None);
+    }
+
+    #[tokio::test]
+    async fn collects_failures_of_each_program() {
+        let missing = std::env::temp_dir().join(format!("missing-{}", uuid::Uuid::new_v4()));
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "loglevel": "INFO",
+            "ffmpeg": " ",
+            "ffprobe": missing,
+        }))
+        .unwrap();
+
+        let Err(Error::ConfigurationUnavailable { reasons }) = verify_configuration(&config).await
+        else {
+            panic!("expected configuration unavailable");
+        };
+        assert!(matches!(
+            reasons.as_slice(),
+            [
+                Error::EmptyProgramPath { .. },
+                Error::FFprobeNotFound { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn has_at_least_one_logical_core() {
+        let expected = std::thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(1);
+        assert_eq!(logical_cores(), expected);
+        assert!(logical_cores() >= 1);
+    }
 }
@@ -1,23 +1,188 @@
-use crate::{
-    handlers::{config::AppConfig, error::Error, tasks::store::TaskStore},
-    with_default_args,
+use std::{
+    collections::HashMap,
+    fs,
+    future::Future,
+    path::PathBuf,
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
 };
 
-use super::process::invoke_ffprobe_json_metadata;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{error, warn};
+use tauri::Manager;
+use tokio::{io::AsyncReadExt, sync::Semaphore};
+
+use crate::handlers::{
+    config::{AppConfig, Config},
+    error::Error,
+    tasks::{
+        history::{TaskHistory, TaskReport, MAX_HISTORY_ENTRIES},
+        loudnorm,
+        message::{StopReason, TaskMessage, TASK_MESSAGE_EVENT},
+        progress::{find_progress_type, find_seek_hints, ProgressType},
+        state_machine::TaskStateCode,
+        store::{ClearResult, TaskStore, TransitionResult},
+        validation::{
+            find_copy_with_filter, has_mapped_streams_to_check, has_protocol_scheme, is_file_input,
+            output_format, reads_stdin, validate_mapped_streams, validate_output_formats,
+            validate_shape, validate_task, ValidationContext, ValidationError,
+        },
+    },
+};
+
+use super::{
+    probe::{invoke_ffprobe_probed_streams, ProbedStreams},
+    process::{create_process, invoke_ffprobe_json_metadata, quote_command, Shell},
+    system::{cached_particulars, ffmpeg_version, FFmpegVersion},
+};
 
 /// A structure receiving ffmpeg command line arguments.
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TaskArgs {
+    /// Args placed before all inputs.
+    #[serde(default)]
+    pub global_args: Vec<String>,
     pub inputs: Vec<TaskInputArgs>,
     pub outputs: Vec<TaskOutputArgs>,
+    /// Args placed after the last output and before the trailing `-y`,
+    /// such as a second output group. Progress args here are never recognized.
+    #[serde(default)]
+    pub append_args: Vec<String>,
+    /// Recomputes progress type when resuming,
+    /// useful when inputs are still being written when task starts.
+    #[serde(default)]
+    pub reprobe_on_resume: bool,
+    /// Appends each progress message as a JSON line to this file, if specified.
+    #[serde(default)]
+    pub progress_log: Option<String>,
+    /// Copies global metadata and chapters from the first input to each output,
+    /// by injecting `-map_metadata 0 -map_chapters 0`.
+    #[serde(default)]
+    pub preserve_metadata: bool,
+    /// Deletes input files once task finished,
+    /// only if all outputs are distinct existing files other than inputs.
+    #[serde(default)]
+    pub delete_source_on_finish: bool,
+    /// Rejects suspicious args, such as stream copy combined with filters,
+    /// streams mapped into a container unable to hold them
+    /// or `-f` disagreeing with output extension, by an error rather than a warning message.
+    #[serde(default)]
+    pub strict: bool,
+    /// Encodes file outputs to [`PARTIAL_OUTPUT_EXTENSION`] suffixed paths first,
+    /// and renames them to final paths only once task finished.
+    #[serde(default)]
+    pub atomic_output: bool,
+    /// Collects deprecation warnings printed by ffmpeg and sends them once task finished.
+    #[serde(default)]
+    pub collect_warnings: bool,
+    /// Captures final summary of each output printed by ffmpeg and sends them once task finished.
+    #[serde(default)]
+    pub final_stats: bool,
+    /// Reads progress from a TCP connection by `-progress tcp://127.0.0.1:<port>` instead of stdout,
+    /// leaving stdout of ffmpeg unused.
+    ///
+    /// Listening socket is bound to localhost only and accepts a single connection from ffmpeg,
+    /// so progress is never exposed to other machines.
+    #[serde(default)]
+    pub progress_over_tcp: bool,
+    /// Maximum amount of retries if task errors during running, no retry if `0`.
+    #[serde(default)]
+    pub max_retries: usize,
+    /// Base delay in milliseconds before retrying, grows by `backoff`.
+    #[serde(default)]
+    pub retry_delay_ms: u64,
+    #[serde(default)]
+    pub backoff: BackoffStrategy,
+    /// Sends advisory hints as a warning message once task started, such as misplaced `-ss`.
+    #[serde(default)]
+    pub hints: bool,
+    /// Maximum time in milliseconds waiting for ffmpeg to exit after killed when stopping,
+    /// defaults to [`DEFAULT_KILL_TIMEOUT_MS`].
+    #[serde(default)]
+    pub kill_timeout_ms: Option<u64>,
+    /// Normalizes audio loudness of outputs to this integrated loudness in LUFS by two-pass `loudnorm` filter,
+    /// see [`loudnorm`](crate::handlers::tasks::loudnorm).
+    #[serde(default)]
+    pub loudnorm_target: Option<f64>,
 }
 
+/// Default maximum time in milliseconds waiting for ffmpeg to exit after killed.
+pub const DEFAULT_KILL_TIMEOUT_MS: u64 = 10 * 1000;
+
+/// Maximum delay in milliseconds before retrying a task, no matter what backoff strategy is.
+pub const MAX_RETRY_DELAY_MS: u64 = 5 * 60 * 1000;
+
+/// Strategies of growing delays between retries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum BackoffStrategy {
+    /// Delays `retry_delay_ms` every time.
+    #[default]
+    Fixed,
+    /// Delays `retry_delay_ms` multiplied by attempt number.
+    Linear,
+    /// Delays `retry_delay_ms` doubled every attempt.
+    Exponential,
+}
+
+impl BackoffStrategy {
+    /// Computes delay of a one based retry attempt, capped by [`MAX_RETRY_DELAY_MS`].
+    pub fn delay(&self, retry_delay_ms: u64, attempt: usize) -> Duration {
+        let attempt = attempt.max(1) as u64;
+        let delay_ms = match self {
+            BackoffStrategy::Fixed => retry_delay_ms,
+            BackoffStrategy::Linear => retry_delay_ms.saturating_mul(attempt),
+            BackoffStrategy::Exponential => {
+                let factor = 2u64.checked_pow((attempt - 1) as u32).unwrap_or(u64::MAX);
+                retry_delay_ms.saturating_mul(factor)
+            }
+        };
+
+        Duration::from_millis(delay_ms.min(MAX_RETRY_DELAY_MS))
+    }
+}
+
+/// Extension appended to paths of outputs being encoded when `atomic_output` enabled.
+pub static PARTIAL_OUTPUT_EXTENSION: &'static str = ".part";
+
+/// Output formats unable to carry global metadata and chapters.
+static NO_METADATA_FORMATS: [&'static str; 6] =
+    ["null", "rawvideo", "image2", "image2pipe", "s16le", "f32le"];
+
 impl TaskArgs {
-    /// Converts to ffmpeg command line arguments.
+    /// Returns maximum time waiting for ffmpeg to exit after killed.
+    pub fn kill_timeout(&self) -> Duration {
+        Duration::from_millis(self.kill_timeout_ms.unwrap_or(DEFAULT_KILL_TIMEOUT_MS))
+    }
+
+    /// Converts to ffmpeg command line arguments, writing progress to stdout.
     pub fn to_cli_args(&self) -> Vec<String> {
-        let prepend_args = with_default_args!("-progress", "-", "-nostats")
-            .iter()
-            .map(|str| *str);
+        self.to_cli_args_with_progress("-")
+    }
+
+    /// Converts to ffmpeg command line arguments, writing progress to `progress_url`.
+    ///
+    /// Args are ordered as: built-in global args, `global_args`,
+    /// args and path of each input, args and path of each output, `append_args` and `-y`.
+    pub fn to_cli_args_with_progress(&self, progress_url: &str) -> Vec<String> {
+        // prefixes stderr lines with log levels, telling warnings and infos apart from errors
+        let loglevel = if self.final_stats {
+            "level+info"
+        } else if self.collect_warnings {
+            "level+warning"
+        } else {
+            "error"
+        };
+        let prepend_args = [
+            "-hide_banner",
+            "-loglevel",
+            loglevel,
+            "-progress",
+            progress_url,
+            "-nostats",
+        ]
+        .into_iter()
+        .chain(self.global_args.iter().map(|arg| arg.as_str()));
         let input_args = self.inputs.iter().flat_map(|input| {
             input
                 .args
@@ -25,17 +190,39 @@ impl TaskArgs {
                 .map(|param| param.as_str())
                 .chain(["-i", input.path.as_str()])
         });
-        let output_args = self.outputs.iter().flat_map(|output| {
-            output
-                .args
+        let partial_outputs = self
+            .outputs
+            .iter()
+            .map(|output| self.partial_output(output))
+            .collect::<Vec<_>>();
+        let output_args =
+            self.outputs
                 .iter()
-                .map(|param| param.as_str())
-                .chain(match &output.path {
-                    Some(path) => [path.as_ref(), "", ""],
-                    None => ["-f", "null", "-"],
-                })
-        });
-        let append_args = [("-y")];
+                .zip(partial_outputs.iter())
+                .flat_map(|(output, partial_output)| {
+                    output
+                        .args
+                        .iter()
+                        .map(|param| param.as_str())
+                        .chain(self.metadata_args(output).iter().map(|arg| *arg))
+                        .chain(match partial_output {
+                            // format can no longer be guessed from extension of a partial output
+                            Some((_, format)) if !output.args.iter().any(|arg| arg == "-f") => {
+                                ["-f", format.as_str()]
+                            }
+                            _ => ["", ""],
+                        })
+                        .chain(match (&output.path, partial_output) {
+                            (Some(_), Some((partial_path, _))) => [partial_path.as_str(), "", ""],
+                            (Some(path), None) => [path.as_str(), "", ""],
+                            (None, _) => ["-f", "null", "-"],
+                        })
+                });
+        let append_args = self
+            .append_args
+            .iter()
+            .map(|arg| arg.as_str())
+            .chain(["-y"]);
         let args = prepend_args
             .chain(input_args)
             .chain(output_args)
@@ -46,22 +233,291 @@ impl TaskArgs {
 
         args
     }
+
+    /// Validates shape of args, see [`validate_shape`] for details.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        validate_shape(self)
+    }
+
+    /// Merges default global args and default output args from configuration into args.
+    /// Defaults are applied to file outputs only, null outputs are skipped.
+    ///
+    /// User specified flags always win, a default flag, as well as its value,
+    /// is dropped if the same flag is specified already.
+    ///
+    /// `-vsync` and `-fps_mode` in defaults are translated into the one expected by
+    /// version of the configured ffmpeg, see [`normalize_sync_args`].
+    /// User specified ones are never translated, see [`find_sync_flag_mismatch`].
+    /// `version` is version of the configured ffmpeg, see [`ffmpeg_version`].
+    pub fn apply_defaults(&mut self, config: &Config, version: Option<FFmpegVersion>) {
+        let default_global_args = normalize_sync_args(config.default_global_args(), version);
+        let default_output_args = normalize_sync_args(config.default_output_args(), version);
+
+        self.global_args = merge_default_args(&default_global_args, &self.global_args);
+        for output in self
+            .outputs
+            .iter_mut()
+            .filter(|output| output.path.is_some())
+        {
+            output.args = merge_default_args(&default_output_args, &output.args);
+        }
+    }
+
+    /// Returns partial path and container format of an output if `atomic_output` enabled.
+    ///
+    /// Null, stdout and protocol outputs are never encoded atomically,
+    /// nor are outputs whose format can't be found, since ffmpeg guesses format from extension.
+    fn partial_output(&self, output: &TaskOutputArgs) -> Option<(String, String)> {
+        if !self.atomic_output {
+            return None;
+        }
+
+        let path = output.path.as_ref()?;
+        if path == "-" || has_protocol_scheme(path) {
+            return None;
+        }
+
+        let format = output_format(output)?;
+        Some((format!("{path}{PARTIAL_OUTPUT_EXTENSION}"), format))
+    }
+
+    /// Returns `true` if any output is written to a pre-opened file descriptor.
+    ///
+    /// Such a task runs only once, since its file descriptors are closed once it ended,
+    /// see [`fd_output`](crate::handlers::tasks::fd_output).
+    pub fn writes_to_fds(&self) -> bool {
+        #[cfg(all(unix, feature = "fd-output"))]
+        return !crate::handlers::tasks::fd_output::output_fds(self).is_empty();
+
+        #[cfg(not(all(unix, feature = "fd-output")))]
+        false
+    }
+
+    /// Returns partial paths and final paths of all outputs encoded atomically.
+    pub fn partial_outputs(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.outputs
+            .iter()
+            .filter_map(|output| {
+                let (partial_path, _) = self.partial_output(output)?;
+                let path = output.path.as_ref()?;
+                Some((PathBuf::from(partial_path), PathBuf::from(path)))
+            })
+            .collect()
+    }
+
+    /// Returns arguments copying metadata and chapters to an output if `preserve_metadata` enabled.
+    /// Outputs unable to carry metadata and outputs already mapping metadata are skipped.
+    fn metadata_args(&self, output: &TaskOutputArgs) -> &'static [&'static str] {
+        if !self.preserve_metadata || output.path.is_none() {
+            return &[];
+        }
+
+        if let Some(format) = output_format(output) {
+            if NO_METADATA_FORMATS.iter().any(|f| *f == format.as_str()) {
+                return &[];
+            }
+        }
+
+        let maps_metadata = output.args.iter().any(|arg| arg == "-map_metadata");
+        let maps_chapters = output.args.iter().any(|arg| arg == "-map_chapters");
+        match (maps_metadata, maps_chapters) {
+            (false, false) => &["-map_metadata", "0", "-map_chapters", "0"],
+            (false, true) => &["-map_metadata", "0"],
+            (true, false) => &["-map_chapters", "0"],
+            (true, true) => &[],
+        }
+    }
 }
 
-#[derive(Debug, serde::Deserialize)]
+/// Prepends default args to args, dropping default flags specified in args already.
+///
+/// A flag is followed by a value unless the next arg is another flag,
+/// such as `-movflags +faststart` and `-map_metadata 0`, while `-shortest` has no value.
+/// `-vsync` and `-fps_mode` are regarded as the same flag.
+fn merge_default_args(defaults: &[String], args: &[String]) -> Vec<String> {
+    let is_flag = |arg: &str| arg.starts_with('-') && arg.parse::<f64>().is_err();
+    let is_specified = |flag: &String| {
+        args.contains(flag) || (is_sync_flag(flag) && args.iter().any(|arg| is_sync_flag(arg)))
+    };
+
+    let mut merged = Vec::with_capacity(defaults.len() + args.len());
+    let mut defaults = defaults.iter().peekable();
+    while let Some(flag) = defaults.next() {
+        let value = defaults.next_if(|value| !is_flag(value));
+        if is_flag(flag) && is_specified(flag) {
+            continue;
+        }
+
+        merged.push(flag.clone());
+        merged.extend(value.cloned());
+    }
+    merged.extend(args.iter().cloned());
+
+    merged
+}
+
+/// First ffmpeg version accepting `-fps_mode`, which deprecates `-vsync` since then.
+pub const FPS_MODE_VERSION: FFmpegVersion = (5, 1, 0);
+
+/// Returns `true` if arg is `-vsync`, or `-fps_mode` with or without a stream specifier.
+fn is_sync_flag(arg: &str) -> bool {
+    arg == "-vsync" || arg == "-fps_mode" || arg.starts_with("-fps_mode:")
+}
+
+/// Translates `-vsync` and `-fps_mode` in args into the one expected by ffmpeg of `version`,
+/// `-fps_mode` since [`FPS_MODE_VERSION`] and `-vsync` before.
+///
+/// Numeric values of `-vsync` are translated into names, since `-fps_mode` accepts names only.
+/// Stream specifiers of `-fps_mode` are dropped, since `-vsync` applies to all video streams.
+/// Args are returned untouched if version is unknown.
+fn normalize_sync_args(args: &[String], version: Option<FFmpegVersion>) -> Vec<String> {
+    let Some(version) = version else {
+        return args.to_vec();
+    };
+    let fps_mode = version >= FPS_MODE_VERSION;
+
+    let mut normalized = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !is_sync_flag(arg) {
+            normalized.push(arg.clone());
+            continue;
+        }
+        let Some(value) = args.next() else {
+            normalized.push(arg.clone());
+            break;
+        };
+
+        if fps_mode {
+            let flag = if arg == "-vsync" {
+                "-fps_mode"
+            } else {
+                arg.as_str()
+            };
+            let value = match value.as_str() {
+                "-1" => "auto",
+                "0" => "passthrough",
+                "1" => "cfr",
+                "2" => "vfr",
+                value => value,
+            };
+            normalized.push(flag.to_string());
+            normalized.push(value.to_string());
+        } else {
+            normalized.push("-vsync".to_string());
+            normalized.push(value.clone());
+        }
+    }
+
+    normalized
+}
+
+/// Finds `-vsync` or `-fps_mode` in args which ffmpeg of `version` doesn't expect,
+/// and returns a warning suggesting the other one.
+/// `-fps_mode` fails ffmpeg before [`FPS_MODE_VERSION`], while `-vsync` is deprecated since then.
+///
+/// Returns `None` if version is unknown.
+pub fn find_sync_flag_mismatch(args: &TaskArgs, version: Option<FFmpegVersion>) -> Option<String> {
+    let (major, minor, patch) = version?;
+    let fps_mode = (major, minor, patch) >= FPS_MODE_VERSION;
+
+    let flag = args
+        .global_args
+        .iter()
+        .chain(args.outputs.iter().flat_map(|output| output.args.iter()))
+        .chain(args.append_args.iter())
+        .find(|arg| is_sync_flag(arg) && (arg.as_str() == "-vsync") == fps_mode)?;
+    if fps_mode {
+        Some(format!(
+            "{flag} is deprecated since ffmpeg {}.{}, use -fps_mode instead",
+            FPS_MODE_VERSION.0, FPS_MODE_VERSION.1
+        ))
+    } else {
+        Some(format!(
+            "{flag} is unsupported by ffmpeg {major}.{minor}.{patch}, use -vsync instead"
+        ))
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TaskInputArgs {
     pub path: String,
     #[serde(default = "Vec::new")]
     pub args: Vec<String>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TaskOutputArgs {
     /// Output path could be None in some situation,
     /// such as exports to null.
     pub path: Option<String>,
     #[serde(default = "Vec::new")]
     pub args: Vec<String>,
+    /// Pre-opened file descriptor written by ffmpeg through `pipe:<fd>`, replacing `path`.
+    /// Only available on Unix with `fd-output` feature, and a task with it runs only once.
+    /// See [`fd_output`](crate::handlers::tasks::fd_output) for details.
+    #[cfg(all(unix, feature = "fd-output"))]
+    #[serde(default)]
+    pub fd: Option<i32>,
+}
+
+/// Validates task args before launching, using cached particulars of the ffmpeg program.
+fn validate(ffmpeg: &str, args: &TaskArgs) -> Result<(), Error> {
+    let particulars = cached_particulars(ffmpeg);
+    let context = ValidationContext {
+        input_protocols: particulars.protocols.map(|protocols| protocols.inputs),
+        hwaccels: particulars.hwaccels,
+    };
+    validate_task(args, &context)
+}
+
+/// Probes streams of all file inputs once, indexed by input,
+/// shared by all validations cross-referencing streams of inputs.
+/// Device, virtual and protocol inputs are never probed and return `None`.
+///
+/// File inputs are probed concurrently, results are in the same order as inputs.
+async fn probe_inputs(ffprobe: &str, args: &TaskArgs) -> Result<Vec<Option<ProbedStreams>>, Error> {
+    let handles = args
+        .inputs
+        .iter()
+        .map(|input| {
+            is_file_input(input).then(|| {
+                let ffprobe = ffprobe.to_string();
+                let path = input.path.clone();
+                tokio::spawn(async move { invoke_ffprobe_probed_streams(&ffprobe, &path).await })
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut probes = Vec::with_capacity(handles.len());
+    for handle in handles {
+        match handle {
+            Some(handle) => probes.push(Some(handle.await.map_err(Error::internal)??)),
+            None => probes.push(None),
+        }
+    }
+
+    Ok(probes)
+}
+
+/// Validates all probed file inputs have at least one stream detectable by ffprobe,
+/// otherwise the task finishes instantly producing garbage.
+fn validate_input_streams(args: &TaskArgs, probes: &[Option<ProbedStreams>]) -> Result<(), Error> {
+    for (input, probe) in args.inputs.iter().zip(probes) {
+        let Some(probe) = probe else {
+            continue;
+        };
+        if probe
+            .streams
+            .as_ref()
+            .map(|streams| streams.is_empty())
+            .unwrap_or(true)
+        {
+            return Err(Error::empty_or_invalid_input(&input.path));
+        }
+    }
+
+    Ok(())
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -69,7 +525,110 @@ pub struct TaskId {
     id: String,
 }
 
+/// Returns a snapshot of loaded configuration,
+/// so that preparing tasks, which probes inputs, never holds lock of configuration.
+async fn loaded_config(config: &AppConfig) -> Result<Config, Error> {
+    config
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(Error::configuration_not_loaded)
+}
+
+/// Validates args of a new task and applies defaults from configuration,
+/// returning args along with warnings of suspicious args.
+/// Suspicious args are rejected instead if `strict` enabled.
+async fn prepare_task(
+    config: &Config,
+    mut args: TaskArgs,
+) -> Result<(TaskArgs, Vec<String>), Error> {
+    args.validate().map_err(Error::invalid_task_args)?;
+    if let Some(target_i) = args.loudnorm_target {
+        loudnorm::validate_target(target_i)?;
+    }
+    let version = ffmpeg_version(config.ffmpeg()).await;
+    args.apply_defaults(config, version);
+    #[cfg(all(unix, feature = "fd-output"))]
+    crate::handlers::tasks::fd_output::bind_output_fds(&mut args)?;
+    validate(config.ffmpeg(), &args)?;
+    let probes = probe_inputs(config.ffprobe(), &args).await?;
+    validate_input_streams(&args, &probes)?;
+
+    let copy_with_filter = find_copy_with_filter(&args).map(|filter| filter.to_string());
+    if let (Some(filter), true) = (&copy_with_filter, args.strict) {
+        return Err(Error::copy_with_filter(filter));
+    }
+
+    let mut warnings = Vec::new();
+    if let Some(filter) = copy_with_filter {
+        warnings.push(format!("filter {filter} is combined with stream copy"));
+    }
+    if let Err(err) = validate_output_formats(&args) {
+        if args.strict {
+            return Err(err);
+        }
+        warnings.push(err.to_string());
+    }
+    if has_mapped_streams_to_check(&args) {
+        // inputs ffprobe complains about are skipped by mapped streams validation
+        for (input, probe) in args.inputs.iter().zip(&probes) {
+            let Some(probe) = probe.as_ref().filter(|probe| !probe.stderr.is_empty()) else {
+                continue;
+            };
+            if args.strict {
+                return Err(Error::ffprobe_runtime_error(&probe.stderr));
+            }
+            warnings.push(format!(
+                "streams of input {} are not validated, ffprobe reports: {}",
+                input.path, probe.stderr
+            ));
+        }
+        let input_streams = probes
+            .into_iter()
+            .map(|probe| {
+                probe
+                    .filter(|probe| probe.stderr.is_empty())
+                    .and_then(|probe| probe.streams)
+            })
+            .collect::<Vec<_>>();
+        if let Err(err) = validate_mapped_streams(&args, &input_streams) {
+            if args.strict {
+                return Err(err);
+            }
+            warnings.push(err.to_string());
+        }
+    }
+    if args.hints {
+        warnings.extend(find_seek_hints(&args));
+    }
+    if let Some(warning) = find_sync_flag_mismatch(&args, version) {
+        warnings.push(warning);
+    }
+    if cfg!(windows) && reads_stdin(&args) {
+        warnings.push("pausing is disabled for tasks reading from stdin".to_string());
+    }
+
+    Ok((args, warnings))
+}
+
+/// Logs warnings of a started task and sends them to frontend, if any.
+fn send_warnings(app_handle: &tauri::AppHandle, id: String, warnings: Vec<String>) {
+    if warnings.is_empty() {
+        return;
+    }
+
+    warnings
+        .iter()
+        .for_each(|reason| warn!("[{}] {}", id, reason));
+    if let Err(err) = app_handle.emit_all(TASK_MESSAGE_EVENT, TaskMessage::warning(id, warnings)) {
+        error!("failed to send message to frontend: {}", err);
+    }
+}
+
 /// A command starts a new task.
+///
+/// If `start_paused` is `true`, task is added in held state without spawning ffmpeg,
+/// and starts once resumed by [`resume_task`].
 #[tauri::command]
 pub async fn start_task(
     app_handle: tauri::AppHandle,
@@ -77,53 +636,341 @@ pub async fn start_task(
     task_store: tauri::State<'_, TaskStore>,
     id: String,
     args: TaskArgs,
+    start_paused: Option<bool>,
 ) -> Result<(), Error> {
-    let config = config.lock().await;
-    let Some(config) = config.as_ref() else {
-        return Err(Error::configuration_not_loaded());
-    };
+    let config = loaded_config(&config).await?;
+    let (args, warnings) = prepare_task(&config, args).await?;
 
-    task_store
-        .start(
-            id,
-            args,
-            app_handle,
-            config.ffmpeg().to_string(),
-            config.ffprobe().to_string(),
-        )
-        .await?;
+    let (ffmpeg, ffprobe) = (config.ffmpeg().to_string(), config.ffprobe().to_string());
+    if start_paused.unwrap_or(false) {
+        task_store
+            .start_paused(id.clone(), args, app_handle.clone(), ffmpeg, ffprobe)
+            .await?;
+    } else {
+        task_store
+            .start(id.clone(), args, app_handle.clone(), ffmpeg, ffprobe)
+            .await?;
+    }
+
+    send_warnings(&app_handle, id, warnings);
 
     Ok(())
 }
 
+/// Maximum amount of tasks prepared concurrently when starting a batch.
+const PREPARE_CONCURRENCY: usize = 4;
+
+/// Maps items by `f` concurrently, running at most `limit` of them at once.
+/// Results are in the same order as items provided, a panicked one fails as an internal error.
+async fn map_concurrent<T, R, F, Fut>(items: Vec<T>, limit: usize, f: F) -> Vec<Result<R, Error>>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = Result<R, Error>> + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(limit));
+    let handles = items
+        .into_iter()
+        .map(|item| {
+            let semaphore = Arc::clone(&semaphore);
+            let future = f(item);
+            tokio::spawn(async move {
+                let _permit = semaphore.acquire().await;
+                future.await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut results = Vec::with_capacity(handles.len());
+    for handle in handles {
+        results.push(
+            handle
+                .await
+                .map_err(Error::internal)
+                .and_then(|result| result),
+        );
+    }
+    results
+}
+
+/// Prepares many tasks concurrently, see [`prepare_task`].
+/// Results are in the same order as tasks provided.
+async fn prepare_batch(
+    config: Config,
+    tasks: Vec<(String, TaskArgs)>,
+) -> Vec<(String, Result<(TaskArgs, Vec<String>), Error>)> {
+    let config = Arc::new(config);
+    let (ids, args): (Vec<_>, Vec<_>) = tasks.into_iter().unzip();
+    let results = map_concurrent(args, PREPARE_CONCURRENCY, |args| {
+        let config = Arc::clone(&config);
+        async move { prepare_task(&config, args).await }
+    })
+    .await;
+
+    ids.into_iter().zip(results).collect()
+}
+
+/// A command starts many tasks at once, returning result of each task in order,
+/// so that failures of some tasks, such as duplicated ids or invalid args, never fail others.
+///
+/// Args of all tasks are validated concurrently first, then valid tasks are added to store
+/// under a single lock acquisition and started, see [`TaskStore::start_many`].
+#[tauri::command]
+pub async fn start_batch(
+    app_handle: tauri::AppHandle,
+    config: tauri::State<'_, AppConfig>,
+    task_store: tauri::State<'_, TaskStore>,
+    tasks: Vec<(String, TaskArgs)>,
+) -> Result<Vec<Result<(), Error>>, Error> {
+    let config = loaded_config(&config).await?;
+    let (ffmpeg, ffprobe) = (config.ffmpeg().to_string(), config.ffprobe().to_string());
+
+    let mut results = Vec::with_capacity(tasks.len());
+    let mut prepared = Vec::with_capacity(tasks.len());
+    let mut prepared_warnings = Vec::with_capacity(tasks.len());
+    for (id, result) in prepare_batch(config, tasks).await {
+        match result {
+            Ok((args, warnings)) => {
+                results.push(None);
+                prepared.push((id.clone(), args));
+                prepared_warnings.push((id, warnings));
+            }
+            Err(err) => results.push(Some(Err(err))),
+        }
+    }
+
+    let started = task_store
+        .start_many(prepared, app_handle.clone(), ffmpeg, ffprobe)
+        .await;
+    for ((id, warnings), result) in prepared_warnings.into_iter().zip(started.iter()) {
+        if result.is_ok() {
+            send_warnings(&app_handle, id, warnings);
+        }
+    }
+
+    // fills results of prepared tasks into vacancies in order
+    let mut started = started.into_iter();
+    Ok(results
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| started.next().unwrap())) // safely unwrap, one result for each vacancy
+        .collect())
+}
+
+/// A command returns the full ffmpeg command line of a task without starting it,
+/// quoted for being copied and pasted into `shell`, which defaults to the native shell of platform.
+/// See [`quote_arg`](super::process::quote_arg) for quoting rules of each shell.
+#[tauri::command]
+pub async fn preview_command(
+    config: tauri::State<'_, AppConfig>,
+    mut args: TaskArgs,
+    shell: Option<Shell>,
+) -> Result<String, Error> {
+    let config = loaded_config(&config).await?;
+    let version = ffmpeg_version(config.ffmpeg()).await;
+    args.apply_defaults(&config, version);
+    #[cfg(all(unix, feature = "fd-output"))]
+    crate::handlers::tasks::fd_output::bind_output_fds(&mut args)?;
+    Ok(quote_command(
+        config.ffmpeg(),
+        args.to_cli_args(),
+        shell.unwrap_or_else(Shell::native),
+    ))
+}
+
+/// A command returns reports of completed tasks from history, newest first.
+/// `limit` defaults to [`MAX_HISTORY_ENTRIES`].
+#[tauri::command]
+pub async fn task_history(
+    history: tauri::State<'_, TaskHistory>,
+    limit: Option<usize>,
+) -> Result<Vec<TaskReport>, Error> {
+    let limit = limit.unwrap_or(MAX_HISTORY_ENTRIES);
+    Ok(history.read(limit).await)
+}
+
 /// A command stops a new task.
+/// Stop reason defaults to [`StopReason::UserRequested`].
 #[tauri::command]
-pub async fn stop_task(task_store: tauri::State<'_, TaskStore>, id: String) -> Result<(), Error> {
-    task_store.stop(&id).await?;
+pub async fn stop_task(
+    task_store: tauri::State<'_, TaskStore>,
+    id: String,
+    reason: Option<StopReason>,
+) -> Result<(), Error> {
+    task_store.stop(&id, reason.unwrap_or_default()).await?;
     Ok(())
 }
 
-/// A command pauses a new task.
+/// A command returns amount of active tasks,
+/// which helps to warn users before exiting.
 #[tauri::command]
-pub async fn pause_task(task_store: tauri::State<'_, TaskStore>, id: String) -> Result<(), Error> {
-    task_store.pause(&id).await?;
+pub async fn active_task_count(task_store: tauri::State<'_, TaskStore>) -> Result<usize, Error> {
+    Ok(task_store.count().await)
+}
+
+/// A command sets maximum amount of running tasks, no limitation if `0`.
+/// Tasks beyond limit wait in queued state until running tasks end.
+#[tauri::command]
+pub async fn set_max_concurrent(
+    task_store: tauri::State<'_, TaskStore>,
+    max_concurrent: usize,
+) -> Result<(), Error> {
+    task_store.set_max_concurrent(max_concurrent).await;
     Ok(())
 }
 
-/// A command resumes a new task.
+/// A command stops all tasks currently in a state and returns ids of tasks stopped.
+/// Stop reason defaults to [`StopReason::UserRequested`].
+#[tauri::command]
+pub async fn stop_tasks_where(
+    task_store: tauri::State<'_, TaskStore>,
+    state: TaskStateCode,
+    reason: Option<StopReason>,
+) -> Result<Vec<String>, Error> {
+    task_store
+        .stop_where(state, reason.unwrap_or_default())
+        .await
+}
+
+/// A command replaces args of a task not yet running, which is queued, held or idle.
+/// New args are validated the same as starting a task.
+#[tauri::command]
+pub async fn update_task_args(
+    app_handle: tauri::AppHandle,
+    config: tauri::State<'_, AppConfig>,
+    task_store: tauri::State<'_, TaskStore>,
+    id: String,
+    args: TaskArgs,
+) -> Result<(), Error> {
+    let config = loaded_config(&config).await?;
+    let (args, warnings) = prepare_task(&config, args).await?;
+    task_store.update_args(&id, args).await?;
+
+    send_warnings(&app_handle, id, warnings);
+
+    Ok(())
+}
+
+/// A command resets an ended task, which is stopped, finished or errored, back to held state.
+/// Task starts again with the same args once resumed by [`resume_task`].
+///
+/// Task is held rather than idle, and fails as not found once dropped from ended tasks,
+/// see [`TaskStore::reset`].
 #[tauri::command]
-pub async fn resume_task(task_store: tauri::State<'_, TaskStore>, id: String) -> Result<(), Error> {
-    task_store.resume(&id).await?;
+pub async fn reset_task(task_store: tauri::State<'_, TaskStore>, id: String) -> Result<(), Error> {
+    task_store.reset(&id).await
+}
+
+/// A command restarts a task with the same id and args.
+#[tauri::command]
+pub async fn restart_task(
+    task_store: tauri::State<'_, TaskStore>,
+    id: String,
+) -> Result<(), Error> {
+    task_store.restart(&id).await?;
     Ok(())
 }
 
+/// A command pauses a new task.
+#[tauri::command]
+pub async fn pause_task(
+    task_store: tauri::State<'_, TaskStore>,
+    id: String,
+) -> Result<TransitionResult, Error> {
+    task_store.pause(&id).await
+}
+
+/// A command resumes a new task.
+#[tauri::command]
+pub async fn resume_task(
+    task_store: tauri::State<'_, TaskStore>,
+    id: String,
+) -> Result<TransitionResult, Error> {
+    task_store.resume(&id).await
+}
+
+pub static QUEUE_CLEARED_EVENT: &'static str = "queue_cleared";
+
+/// A command stops all tasks and discards all not yet started tasks.
+#[tauri::command]
+pub async fn clear_all(
+    app_handle: tauri::AppHandle,
+    task_store: tauri::State<'_, TaskStore>,
+) -> Result<ClearResult, Error> {
+    let result = task_store.clear_all().await;
+    if let Err(err) = app_handle.emit_all(QUEUE_CLEARED_EVENT, result) {
+        error!("failed to emit event \"{QUEUE_CLEARED_EVENT}\": {err}");
+    }
+
+    Ok(result)
+}
+
+/// Metadata of a media cached along with modified time of the file.
+struct CachedMetadata {
+    modified: Option<SystemTime>,
+    metadata: String,
+}
+
+/// Cache of plain json metadata returned by [`media_metadata`], by path.
+///
+/// An entry is regarded as stale once modified time of the file changes,
+/// but files replaced keeping modified time, or on file systems without modified time,
+/// should be evicted explicitly by [`clear_metadata_cache`] or [`clear_metadata_cache_entry`].
+#[derive(Default)]
+pub struct MetadataCache {
+    entries: Mutex<HashMap<String, CachedMetadata>>,
+}
+
+impl MetadataCache {
+    /// Returns cached metadata of a path, if not stale.
+    fn get(&self, path: &str) -> Option<String> {
+        let entries = self.entries.lock().ok()?;
+        let cached = entries.get(path)?;
+        (cached.modified == file_modified(path)).then(|| cached.metadata.clone())
+    }
+
+    /// Caches metadata of a path.
+    fn insert(&self, path: String, metadata: String) {
+        if let Ok(mut entries) = self.entries.lock() {
+            let modified = file_modified(&path);
+            entries.insert(path, CachedMetadata { modified, metadata });
+        }
+    }
+
+    /// Evicts all entries and returns amount of entries evicted.
+    pub fn clear(&self) -> usize {
+        self.entries
+            .lock()
+            .map(|mut entries| entries.drain().count())
+            .unwrap_or(0)
+    }
+
+    /// Evicts entry of a path and returns amount of entries evicted, `0` or `1`.
+    pub fn remove(&self, path: &str) -> usize {
+        self.entries
+            .lock()
+            .map(|mut entries| entries.remove(path).map(|_| 1).unwrap_or(0))
+            .unwrap_or(0)
+    }
+}
+
+/// Returns modified time of a file, `None` if unavailable.
+fn file_modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
 /// A command returns media properties using ffprobe.
 ///
 /// Preventing unnecessary conversion between json object and plain text,
 /// this command return plain json text from stdout directly without serializing to json object.
+///
+/// Results are cached by path in [`MetadataCache`].
 #[tauri::command]
 pub async fn media_metadata(
     config: tauri::State<'_, AppConfig>,
+    metadata_cache: tauri::State<'_, MetadataCache>,
     path: String,
 ) -> Result<String, Error> {
     let config = config.lock().await;
@@ -131,6 +978,768 @@ pub async fn media_metadata(
         return Err(Error::configuration_not_loaded());
     };
 
+    if let Some(metadata) = metadata_cache.get(&path) {
+        return Ok(metadata);
+    }
+
     let metadata = invoke_ffprobe_json_metadata(config.ffprobe(), &path).await?;
+    metadata_cache.insert(path, metadata.clone());
     Ok(metadata)
 }
+
+/// A command evicts all cached metadata and returns amount of entries evicted.
+#[tauri::command]
+pub async fn clear_metadata_cache(
+    metadata_cache: tauri::State<'_, MetadataCache>,
+) -> Result<usize, Error> {
+    Ok(metadata_cache.clear())
+}
+
+/// A command evicts cached metadata of a path and returns amount of entries evicted, `0` or `1`.
+#[tauri::command]
+pub async fn clear_metadata_cache_entry(
+    metadata_cache: tauri::State<'_, MetadataCache>,
+    path: String,
+) -> Result<usize, Error> {
+    Ok(metadata_cache.remove(&path))
+}
+
+/// Maximum amount of ffprobe processes running concurrently when probing many files.
+const PROBE_CONCURRENCY: usize = 4;
+
+/// Probing result of a single file.
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum ProbeResult {
+    /// Plain json text of metadata, same as [`media_metadata`] returns.
+    Probed {
+        path: String,
+        metadata: String,
+    },
+    Failed {
+        path: String,
+        error: Error,
+    },
+}
+
+/// A command returns metadata of many files in one call, probing them concurrently.
+/// Results are in the same order as paths provided.
+#[tauri::command]
+pub async fn probe_many(
+    config: tauri::State<'_, AppConfig>,
+    paths: Vec<String>,
+) -> Result<Vec<ProbeResult>, Error> {
+    let ffprobe = match config.lock().await.as_ref() {
+        Some(config) => config.ffprobe().to_string(),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    let results = map_concurrent(paths.clone(), PROBE_CONCURRENCY, |path| {
+        let ffprobe = ffprobe.clone();
+        async move { invoke_ffprobe_json_metadata(&ffprobe, &path).await }
+    })
+    .await;
+
+    Ok(paths
+        .into_iter()
+        .zip(results)
+        .map(|(path, result)| match result {
+            Ok(metadata) => ProbeResult::Probed { path, metadata },
+            Err(error) => ProbeResult::Failed { path, error },
+        })
+        .collect())
+}
+
+/// Maximum amount of ffprobe processes running concurrently when estimating a batch.
+const ESTIMATE_CONCURRENCY: usize = 4;
+
+/// Estimation of a single task in a batch.
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum TaskEstimate {
+    Estimated { progress_type: ProgressType },
+    Failed { error: Error },
+}
+
+/// Estimation of a whole batch.
+#[derive(Debug, serde::Serialize)]
+pub struct BatchEstimate {
+    /// Estimations of each task, in the same order as tasks provided.
+    tasks: Vec<TaskEstimate>,
+    count: usize,
+    /// Total duration in seconds, sums up from all duration based tasks.
+    total_duration: f64,
+}
+
+/// A command estimates total work of a batch of tasks without starting any of them.
+#[tauri::command]
+pub async fn estimate_batch(
+    config: tauri::State<'_, AppConfig>,
+    tasks: Vec<TaskArgs>,
+) -> Result<BatchEstimate, Error> {
+    let ffprobe = match config.lock().await.as_ref() {
+        Some(config) => config.ffprobe().to_string(),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    let results = map_concurrent(tasks, ESTIMATE_CONCURRENCY, |args| {
+        let ffprobe = ffprobe.clone();
+        async move { find_progress_type(&ffprobe, &args).await }
+    })
+    .await;
+
+    let mut estimates = Vec::with_capacity(results.len());
+    let mut total_duration = 0.0;
+    for result in results {
+        let estimate = match result {
+            Ok(progress_type) => {
+                match progress_type {
+                    ProgressType::ByDuration { duration, .. }
+                    | ProgressType::Auto { duration, .. } => total_duration += duration,
+                    ProgressType::ByFileSize { .. } | ProgressType::Unspecified { .. } => {}
+                };
+                TaskEstimate::Estimated { progress_type }
+            }
+            Err(error) => TaskEstimate::Failed { error },
+        };
+        estimates.push(estimate);
+    }
+
+    Ok(BatchEstimate {
+        count: estimates.len(),
+        tasks: estimates,
+        total_duration,
+    })
+}
+
+/// Maximum duration in seconds of an encode preview.
+const MAX_PREVIEW_DURATION: f64 = 30.0;
+/// Previews older than this are removed when a new preview starts.
+const PREVIEW_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+/// Returns directory storing encode previews.
+fn preview_directory() -> PathBuf {
+    std::env::temp_dir().join("ffmpeg-transcoder-previews")
+}
+
+/// Removes previews older than [`PREVIEW_RETENTION`].
+fn cleanup_previews(dir: &PathBuf) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let now = SystemTime::now();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .map(|elapsed| elapsed > PREVIEW_RETENTION)
+            .unwrap_or(false);
+        if expired {
+            if let Err(err) = fs::remove_file(entry.path()) {
+                warn!(
+                    "failed to remove expired preview {}: {}",
+                    entry.path().to_string_lossy(),
+                    err
+                );
+            }
+        }
+    }
+}
+
+/// A command encodes a short segment of input to a temporary file for previewing.
+///
+/// Preview runs as a normal task with the specified id, progress is reported as usual.
+/// Args are prepared the same as starting a task, so that preview encodes as the real task does.
+/// Returns path of the temporary file, which is available after task finished.
+#[tauri::command]
+pub async fn encode_preview(
+    app_handle: tauri::AppHandle,
+    config: tauri::State<'_, AppConfig>,
+    task_store: tauri::State<'_, TaskStore>,
+    id: String,
+    input: String,
+    args: Vec<String>,
+    start: f64,
+    duration: f64,
+) -> Result<String, Error> {
+    if !(duration > 0.0 && duration <= MAX_PREVIEW_DURATION) {
+        return Err(Error::preview_duration_out_of_range(MAX_PREVIEW_DURATION));
+    }
+
+    let config = loaded_config(&config).await?;
+
+    let dir = preview_directory();
+    cleanup_previews(&dir);
+
+    let extension = PathBuf::from(&input)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or("mkv".to_string());
+    let path = dir
+        .join(format!("{}.{}", uuid::Uuid::new_v4(), extension))
+        .to_string_lossy()
+        .to_string();
+
+    let args = TaskArgs {
+        inputs: vec![TaskInputArgs {
+            path: input,
+            args: vec!["-ss".to_string(), start.max(0.0).to_string()],
+        }],
+        outputs: vec![TaskOutputArgs {
+            path: Some(path.clone()),
+            args: args
+                .into_iter()
+                .chain(["-t".to_string(), duration.to_string()])
+                .collect(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let (args, warnings) = prepare_task(&config, args).await?;
+
+    let (ffmpeg, ffprobe) = (config.ffmpeg().to_string(), config.ffprobe().to_string());
+    task_store
+        .start(id.clone(), args, app_handle.clone(), ffmpeg, ffprobe)
+        .await?;
+
+    send_warnings(&app_handle, id, warnings);
+
+    Ok(path)
+}
+
+/// Maximum size in bytes of output captured by [`capture_output`].
+pub const MAX_CAPTURE_SIZE: usize = 16 * 1024 * 1024;
+
+/// A command encodes the only output of args to stdout of ffmpeg and returns the bytes directly
+/// instead of writing to disk, for small outputs such as a single extracted frame or a short clip.
+/// Bytes are returned as a base64 string, since IPC serializes bytes as a JSON array of numbers.
+///
+/// Output path is ignored, container format should be specified by `-f` then,
+/// since it can't be guessed from stdout. Defaults from configuration are never applied.
+/// ffmpeg is killed once output exceeds `max_size`, which defaults to and is bounded by [`MAX_CAPTURE_SIZE`],
+/// returning [`Error::CaptureTooLarge`].
+///
+/// ffmpeg runs once without reporting progress, rather than as a task.
+#[tauri::command]
+pub async fn capture_output(
+    config: tauri::State<'_, AppConfig>,
+    args: TaskArgs,
+    max_size: Option<usize>,
+) -> Result<String, Error> {
+    let ffmpeg = match config.lock().await.as_ref() {
+        Some(config) => config.ffmpeg().to_string(),
+        None => return Err(Error::configuration_not_loaded()),
+    };
+
+    args.validate().map_err(Error::invalid_task_args)?;
+    let [output] = args.outputs.as_slice() else {
+        return Err(Error::invalid_capture_args(
+            "exactly one output is required",
+        ));
+    };
+    if !output.args.iter().any(|arg| arg == "-f") {
+        return Err(Error::invalid_capture_args(
+            "output format should be specified by -f",
+        ));
+    }
+    validate(&ffmpeg, &args)?;
+    let max_size = max_size.unwrap_or(MAX_CAPTURE_SIZE).min(MAX_CAPTURE_SIZE);
+
+    let input_args = args.inputs.iter().flat_map(|input| {
+        input
+            .args
+            .iter()
+            .map(|arg| arg.as_str())
+            .chain(["-i", input.path.as_str()])
+    });
+    let cli_args = ["-hide_banner", "-loglevel", "error", "-nostats"]
+        .into_iter()
+        .chain(args.global_args.iter().map(|arg| arg.as_str()))
+        .chain(input_args)
+        .chain(output.args.iter().map(|arg| arg.as_str()))
+        .chain(["pipe:1"])
+        .collect::<Vec<_>>();
+
+    let mut child = create_process(&ffmpeg, cli_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => Error::ffmpeg_not_found(&ffmpeg),
+            std::io::ErrorKind::PermissionDenied => Error::binary_not_executable(&ffmpeg),
+            _ => Error::ffmpeg_unavailable_with_raw_error(&ffmpeg, err),
+        })?;
+
+    // reads stderr concurrently, otherwise ffmpeg blocks once stderr pipe is full
+    let mut stderr = child.stderr.take().unwrap(); // safely unwrap
+    let stderr_handle = tokio::spawn(async move {
+        let mut buf = Vec::new();
+        let _ = stderr.read_to_end(&mut buf).await;
+        buf
+    });
+
+    // reads one byte more than maximum size, telling an output of exactly maximum size apart
+    let mut captured = Vec::new();
+    let stdout = child.stdout.take().unwrap(); // safely unwrap
+    stdout
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut captured)
+        .await
+        .map_err(Error::internal)?;
+    if captured.len() > max_size {
+        let _ = child.kill().await;
+        return Err(Error::capture_too_large(max_size));
+    }
+
+    let status = child.wait().await.map_err(Error::internal)?;
+    if !status.success() {
+        let stderr = stderr_handle.await.unwrap_or_default();
+        let stderr = String::from_utf8_lossy(&stderr);
+        return Err(Error::ffmpeg_runtime_error(
+            stderr.lines().last().unwrap_or_default().trim(),
+        ));
+    }
+
+    Ok(STANDARD.encode(&captured))
+}
+
+/// A command normalizes audio loudness of input by two-pass `loudnorm` filter.
+///
+/// Both passes run in a single task as two phases, see [`loudnorm`](crate::handlers::tasks::loudnorm).
+/// `target_i` should be in [`MIN_LOUDNORM_TARGET`](loudnorm::MIN_LOUDNORM_TARGET)
+/// to [`MAX_LOUDNORM_TARGET`](loudnorm::MAX_LOUDNORM_TARGET) LUFS. Returns id of the task.
+#[tauri::command]
+pub async fn normalize_loudness(
+    app_handle: tauri::AppHandle,
+    config: tauri::State<'_, AppConfig>,
+    task_store: tauri::State<'_, TaskStore>,
+    input: String,
+    output: String,
+    target_i: f64,
+) -> Result<String, Error> {
+    loudnorm::validate_target(target_i)?;
+    let config = loaded_config(&config).await?;
+
+    let args = TaskArgs {
+        inputs: vec![TaskInputArgs {
+            path: input,
+            args: vec![],
+        }],
+        outputs: vec![TaskOutputArgs {
+            path: Some(output),
+            ..Default::default()
+        }],
+        loudnorm_target: Some(target_i),
+        ..Default::default()
+    };
+    let (args, warnings) = prepare_task(&config, args).await?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let (ffmpeg, ffprobe) = (config.ffmpeg().to_string(), config.ffprobe().to_string());
+    task_store
+        .start(id.clone(), args, app_handle.clone(), ffmpeg, ffprobe)
+        .await?;
+
+    send_warnings(&app_handle, id.clone(), warnings);
+
+    Ok(id)
+}
+
+/// Returns `true` if segment output template contains a sequence number pattern, such as `%d` or `%03d`.
+fn has_sequence_pattern(template: &str) -> bool {
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+
+        let rest = chars.clone().find(|c| !c.is_ascii_digit());
+        if rest == Some('d') {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// A command splits input into equal time segments by stream copying,
+/// in a single task using `segment` muxer. Returns id of the task.
+///
+/// `output_template` must contain a sequence number pattern, such as `out_%03d.mp4`.
+/// Since streams are copied, segments are cut at keyframes and may be a little longer than `segment_seconds`.
+#[tauri::command]
+pub async fn split_by_time(
+    app_handle: tauri::AppHandle,
+    config: tauri::State<'_, AppConfig>,
+    task_store: tauri::State<'_, TaskStore>,
+    input: String,
+    segment_seconds: f64,
+    output_template: String,
+) -> Result<String, Error> {
+    if !(segment_seconds.is_finite() && segment_seconds > 0.0) {
+        return Err(Error::invalid_segment_args(format!(
+            "segment duration must be positive, got {segment_seconds}"
+        )));
+    }
+    if !has_sequence_pattern(&output_template) {
+        return Err(Error::invalid_segment_args(format!(
+            "output template {output_template} contains no sequence number pattern such as %d"
+        )));
+    }
+
+    let config = loaded_config(&config).await?;
+
+    let args = TaskArgs {
+        inputs: vec![TaskInputArgs {
+            path: input,
+            args: vec![],
+        }],
+        outputs: vec![TaskOutputArgs {
+            path: Some(output_template),
+            args: [
+                "-map",
+                "0",
+                "-c",
+                "copy",
+                "-f",
+                "segment",
+                "-segment_time",
+                &segment_seconds.to_string(),
+                "-reset_timestamps",
+                "1",
+            ]
+            .into_iter()
+            .map(|arg| arg.to_string())
+            .collect(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let (args, warnings) = prepare_task(&config, args).await?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let (ffmpeg, ffprobe) = (config.ffmpeg().to_string(), config.ffprobe().to_string());
+    task_store
+        .start(id.clone(), args, app_handle.clone(), ffmpeg, ffprobe)
+        .await?;
+
+    send_warnings(&app_handle, id.clone(), warnings);
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::{config::Config, error::Error};
+
+    use super::{
+        has_sequence_pattern, map_concurrent, merge_default_args, normalize_sync_args,
+        prepare_batch, BackoffStrategy, TaskArgs, TaskInputArgs, TaskOutputArgs,
+        MAX_RETRY_DELAY_MS, PARTIAL_OUTPUT_EXTENSION,
+    };
+
+    fn config() -> Config {
+        serde_json::from_value(serde_json::json!({
+            "loglevel": "INFO",
+            "ffmpeg": "ffmpeg-not-installed",
+            "ffprobe": "ffprobe-not-installed",
+        }))
+        .unwrap()
+    }
+
+    fn args(path: &str, input_args: &[&str]) -> TaskArgs {
+        TaskArgs {
+            inputs: vec![TaskInputArgs {
+                path: path.to_string(),
+                args: input_args.iter().map(|arg| arg.to_string()).collect(),
+            }],
+            outputs: vec![TaskOutputArgs::default()],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn prepares_mixed_batch_in_order() {
+        let missing = std::env::temp_dir().join(format!("missing-{}.mp4", uuid::Uuid::new_v4()));
+        let tasks = vec![
+            ("virtual".to_string(), args("testsrc", &["-f", "lavfi"])),
+            ("empty".to_string(), TaskArgs::default()),
+            ("missing".to_string(), args(&missing.to_string_lossy(), &[])),
+            ("stdin".to_string(), args("-", &[])),
+        ];
+
+        let results = prepare_batch(config(), tasks).await;
+        let ids = results
+            .iter()
+            .map(|(id, _)| id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["virtual", "empty", "missing", "stdin"]);
+        assert!(results[0].1.is_ok());
+        assert!(matches!(results[1].1, Err(Error::InvalidTaskArgs { .. })));
+        assert!(matches!(results[2].1, Err(Error::InputNotFound { .. })));
+        assert!(results[3].1.is_ok());
+    }
+
+    fn outputs_args(outputs: &[(Option<&str>, &[&str])]) -> TaskArgs {
+        TaskArgs {
+            outputs: outputs
+                .iter()
+                .map(|(path, output_args)| TaskOutputArgs {
+                    path: path.map(|path| path.to_string()),
+                    args: output_args.iter().map(|arg| arg.to_string()).collect(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..args("in.mkv", &[])
+        }
+    }
+
+    /// Args of ffmpeg following `-i <input>` and before trailing `-y`.
+    fn output_cli_args(args: &TaskArgs) -> Vec<String> {
+        let cli_args = args.to_cli_args();
+        let start = cli_args.iter().position(|arg| arg == "in.mkv").unwrap() + 1;
+        cli_args[start..cli_args.len() - 1].to_vec()
+    }
+
+    #[test]
+    fn preserves_metadata_of_file_outputs() {
+        let mut args = outputs_args(&[
+            (Some("a.mp4"), &[]),
+            (Some("b.mp4"), &["-map_metadata", "-1"]),
+            (Some("c.yuv"), &["-f", "rawvideo"]),
+            (None, &[]),
+        ]);
+        args.preserve_metadata = true;
+        assert_eq!(
+            output_cli_args(&args),
+            vec![
+                "-map_metadata",
+                "0",
+                "-map_chapters",
+                "0",
+                "a.mp4",
+                "-map_metadata",
+                "-1",
+                "-map_chapters",
+                "0",
+                "b.mp4",
+                "-f",
+                "rawvideo",
+                "c.yuv",
+                "-f",
+                "null",
+                "-",
+            ]
+        );
+
+        args.preserve_metadata = false;
+        assert_eq!(output_cli_args(&args)[0], "a.mp4");
+    }
+
+    #[test]
+    fn encodes_file_outputs_to_partial_paths() {
+        let mut args = outputs_args(&[
+            (Some("a.mp4"), &[]),
+            (Some("b.bin"), &["-f", "matroska"]),
+            (Some("c.unknown"), &[]),
+            (Some("rtmp://example.com/live"), &["-f", "flv"]),
+            (None, &[]),
+        ]);
+        args.atomic_output = true;
+        assert_eq!(
+            output_cli_args(&args),
+            vec![
+                "-f",
+                "mp4",
+                &format!("a.mp4{PARTIAL_OUTPUT_EXTENSION}"),
+                "-f",
+                "matroska",
+                &format!("b.bin{PARTIAL_OUTPUT_EXTENSION}"),
+                "c.unknown",
+                "-f",
+                "flv",
+                "rtmp://example.com/live",
+                "-f",
+                "null",
+                "-",
+            ]
+        );
+        assert_eq!(args.partial_outputs().len(), 2);
+    }
+
+    fn owned(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn merges_default_args_unless_specified() {
+        let defaults = owned(&["-movflags", "+faststart", "-shortest", "-threads", "4"]);
+        assert_eq!(
+            merge_default_args(&defaults, &owned(&["-c:v", "libx264"])),
+            owned(&[
+                "-movflags",
+                "+faststart",
+                "-shortest",
+                "-threads",
+                "4",
+                "-c:v",
+                "libx264"
+            ])
+        );
+        assert_eq!(
+            merge_default_args(&defaults, &owned(&["-threads", "0", "-shortest"])),
+            owned(&["-movflags", "+faststart", "-threads", "0", "-shortest"])
+        );
+        // negative numbers are values rather than flags
+        assert_eq!(
+            merge_default_args(&owned(&["-map_metadata", "-1"]), &[]),
+            owned(&["-map_metadata", "-1"])
+        );
+    }
+
+    #[test]
+    fn applies_default_output_args_to_file_outputs_only() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "loglevel": "INFO",
+            "ffmpeg": "ffmpeg",
+            "ffprobe": "ffprobe",
+            "defaultGlobalArgs": ["-threads", "4"],
+            "defaultOutputArgs": ["-movflags", "+faststart"],
+        }))
+        .unwrap();
+        let mut args = outputs_args(&[(Some("a.mp4"), &[]), (None, &[])]);
+        args.apply_defaults(&config, None);
+
+        assert_eq!(args.global_args, owned(&["-threads", "4"]));
+        assert_eq!(args.outputs[0].args, owned(&["-movflags", "+faststart"]));
+        assert!(args.outputs[1].args.is_empty());
+    }
+
+    #[test]
+    fn places_append_args_after_outputs() {
+        let mut args = outputs_args(&[(Some("a.mp4"), &["-c:v", "libx264"])]);
+        args.append_args = owned(&["-map", "0:a", "b.m4a"]);
+        let cli_args = args.to_cli_args();
+        assert_eq!(
+            cli_args[cli_args.len() - 7..],
+            owned(&["-c:v", "libx264", "a.mp4", "-map", "0:a", "b.m4a", "-y"])
+        );
+    }
+
+    #[test]
+    fn grows_retry_delays_by_backoff() {
+        let delays = |backoff: BackoffStrategy| {
+            (1..=4)
+                .map(|attempt| backoff.delay(1000, attempt).as_millis() as u64)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(delays(BackoffStrategy::Fixed), vec![1000, 1000, 1000, 1000]);
+        assert_eq!(
+            delays(BackoffStrategy::Linear),
+            vec![1000, 2000, 3000, 4000]
+        );
+        assert_eq!(
+            delays(BackoffStrategy::Exponential),
+            vec![1000, 2000, 4000, 8000]
+        );
+    }
+
+    #[test]
+    fn caps_retry_delays() {
+        let max = std::time::Duration::from_millis(MAX_RETRY_DELAY_MS);
+        assert_eq!(BackoffStrategy::Exponential.delay(1000, 100), max);
+        assert_eq!(BackoffStrategy::Linear.delay(u64::MAX, usize::MAX), max);
+        // attempts are one based
+        assert_eq!(
+            BackoffStrategy::Exponential.delay(1000, 0).as_millis(),
+            1000
+        );
+    }
+
+    #[test]
+    fn finds_sequence_patterns() {
+        for template in ["out_%d.mp4", "out_%03d.mp4", "%d/out.mp4"] {
+            assert!(has_sequence_pattern(template), "{template}");
+        }
+        for template in ["out.mp4", "100%.mp4", "out_%s.mp4", "out_%03.mp4", "out_%"] {
+            assert!(!has_sequence_pattern(template), "{template}");
+        }
+    }
+
+    #[test]
+    fn translates_vsync_into_fps_mode() {
+        let args = owned(&["-c:v", "libx264", "-vsync", "1", "-fps_mode:v:0", "vfr"]);
+        assert_eq!(
+            normalize_sync_args(&args, Some((6, 0, 0))),
+            owned(&[
+                "-c:v",
+                "libx264",
+                "-fps_mode",
+                "cfr",
+                "-fps_mode:v:0",
+                "vfr"
+            ])
+        );
+    }
+
+    #[test]
+    fn translates_fps_mode_into_vsync() {
+        let args = owned(&["-fps_mode:v", "passthrough", "-vsync", "2"]);
+        assert_eq!(
+            normalize_sync_args(&args, Some((4, 4, 0))),
+            owned(&["-vsync", "passthrough", "-vsync", "2"])
+        );
+    }
+
+    #[test]
+    fn keeps_sync_args_of_unknown_version() {
+        let args = owned(&["-vsync", "1", "-fps_mode"]);
+        assert_eq!(normalize_sync_args(&args, None), args);
+        assert_eq!(
+            normalize_sync_args(&args, Some((6, 0, 0))),
+            owned(&["-fps_mode", "cfr", "-fps_mode"])
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_concurrently_in_order_within_limit() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let results = map_concurrent((0..10).collect(), 3, |index: u64| {
+            let running = Arc::clone(&running);
+            let max_running = Arc::clone(&max_running);
+            async move {
+                let current = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(current, Ordering::SeqCst);
+                // later items finish first
+                tokio::time::sleep(std::time::Duration::from_millis(20 - index)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                if index == 5 {
+                    Err(Error::task_not_found(index.to_string()))
+                } else {
+                    Ok(index * 2)
+                }
+            }
+        })
+        .await;
+
+        assert!(max_running.load(Ordering::SeqCst) <= 3);
+        assert_eq!(results.len(), 10);
+        for (index, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(doubled) => assert_eq!(doubled, index as u64 * 2),
+                Err(_) => assert_eq!(index, 5),
+            }
+        }
+    }
+}
@@ -1,15 +1,44 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
-use log::LevelFilter;
+use log::{info, LevelFilter};
 use tokio::sync::Mutex;
 
+use super::{commands::process::split_program, error::Error};
+
 pub type AppConfig = Arc<Mutex<Option<Config>>>;
 
-#[derive(Debug, Clone, serde::Deserialize)]
+/// Environment variable overriding ffmpeg program of configuration.
+pub static FFMPEG_ENV: &'static str = "FFMPEG_TRANSCODER_FFMPEG";
+/// Environment variable overriding ffprobe program of configuration.
+pub static FFPROBE_ENV: &'static str = "FFMPEG_TRANSCODER_FFPROBE";
+
+/// Configuration of backend.
+///
+/// Configuration is sent back to frontend as is by `effective_config`,
+/// sensitive fields added in future should be marked by `#[serde(skip_serializing)]`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Config {
     loglevel: LevelFilter,
     ffmpeg: String,
     ffprobe: String,
+    /// Args placed before inputs of every task.
+    #[serde(default)]
+    default_global_args: Vec<String>,
+    /// Args applied to every file output of every task.
+    #[serde(default)]
+    default_output_args: Vec<String>,
+    /// Runs ffmpeg processes in low priority, toggled by system tray.
+    #[serde(default)]
+    background_mode: bool,
+    /// Keeps ffmpeg processes running if app is killed without shutting down,
+    /// they are bound to lifetime of app by default.
+    #[serde(default)]
+    detach_children: bool,
+    /// Simulates tasks without spawning ffmpeg, only honored in debug builds,
+    /// see [`dry_run`](super::tasks::dry_run).
+    #[serde(default)]
+    dry_run: bool,
 }
 
 impl Config {
@@ -27,4 +56,142 @@ impl Config {
     pub fn ffprobe(&self) -> &str {
         &self.ffprobe
     }
-}
\ No newline at end of file
+
+    /// Gets default global args.
+    pub fn default_global_args(&self) -> &[String] {
+        &self.default_global_args
+    }
+
+    /// Gets default output args.
+    pub fn default_output_args(&self) -> &[String] {
+        &self.default_output_args
+    }
+
+    /// Gets background mode.
+    pub fn background_mode(&self) -> bool {
+        self.background_mode
+    }
+
+    /// Gets whether ffmpeg processes are detached from lifetime of app.
+    pub fn detach_children(&self) -> bool {
+        self.detach_children
+    }
+
+    /// Gets whether tasks are simulated without spawning ffmpeg.
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Overrides ffmpeg and ffprobe programs by environment variables [`FFMPEG_ENV`]
+    /// and [`FFPROBE_ENV`], if present and non-empty.
+    pub fn apply_env_overrides(&mut self) {
+        self.apply_overrides(|name| std::env::var(name).ok());
+    }
+
+    /// Overrides ffmpeg and ffprobe programs by variables named [`FFMPEG_ENV`]
+    /// and [`FFPROBE_ENV`] found by `lookup`, if present and non-empty.
+    fn apply_overrides(&mut self, lookup: impl Fn(&str) -> Option<String>) {
+        if let Some(ffmpeg) = lookup(FFMPEG_ENV).filter(|v| !v.is_empty()) {
+            info!("ffmpeg program overridden by {FFMPEG_ENV}: {ffmpeg}");
+            self.ffmpeg = ffmpeg;
+        }
+
+        if let Some(ffprobe) = lookup(FFPROBE_ENV).filter(|v| !v.is_empty()) {
+            info!("ffprobe program overridden by {FFPROBE_ENV}: {ffprobe}");
+            self.ffprobe = ffprobe;
+        }
+    }
+
+    /// Validates ffmpeg program, see [`validate_program`].
+    pub fn validate_ffmpeg(&self) -> Result<(), Error> {
+        validate_program(&self.ffmpeg, "ffmpeg", |program| {
+            Error::ffmpeg_not_found(program)
+        })
+    }
+
+    /// Validates ffprobe program, see [`validate_program`].
+    pub fn validate_ffprobe(&self) -> Result<(), Error> {
+        validate_program(&self.ffprobe, "ffprobe", |program| {
+            Error::ffprobe_not_found(program)
+        })
+    }
+}
+
+/// Validates program named `name` is non-empty, splits properly
+/// and exists on disk if executable is specified as an absolute path,
+/// returning error made by `not_found` otherwise.
+/// Bare command names are resolved via `PATH` and are not checked for existence.
+fn validate_program(program: &str, name: &str, not_found: fn(&str) -> Error) -> Result<(), Error> {
+    if program.trim().is_empty() {
+        return Err(Error::empty_program_path(name));
+    }
+
+    let Some((executable, _)) = split_program(program) else {
+        return Err(Error::malformed_program(program));
+    };
+    if Path::new(&executable).is_absolute() && !Path::new(&executable).is_file() {
+        Err(not_found(program))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::error::Error;
+
+    use super::{Config, FFMPEG_ENV, FFPROBE_ENV};
+
+    fn config(ffmpeg: &str, ffprobe: &str) -> Config {
+        serde_json::from_value(serde_json::json!({
+            "loglevel": "INFO",
+            "ffmpeg": ffmpeg,
+            "ffprobe": ffprobe,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn validates_program_paths() {
+        let missing = std::env::temp_dir().join(format!("missing-{}", uuid::Uuid::new_v4()));
+        let missing = missing.to_string_lossy();
+
+        let valid = config("ffmpeg", "nice -n 10 ffprobe");
+        assert!(valid.validate_ffmpeg().is_ok());
+        assert!(valid.validate_ffprobe().is_ok());
+        assert!(matches!(
+            config("  ", "ffprobe").validate_ffmpeg(),
+            Err(Error::EmptyProgramPath { .. })
+        ));
+        assert!(matches!(
+            config("ffmpeg", "\"ffprobe").validate_ffprobe(),
+            Err(Error::MalformedProgram { .. })
+        ));
+        assert!(matches!(
+            config(&missing, "ffprobe").validate_ffmpeg(),
+            Err(Error::FFmpegNotFound { .. })
+        ));
+        assert!(matches!(
+            config("ffmpeg", &missing).validate_ffprobe(),
+            Err(Error::FFprobeNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn overrides_programs_by_env() {
+        let mut config = config("ffmpeg", "ffprobe");
+        config.apply_overrides(|name| {
+            if name == FFMPEG_ENV {
+                Some("/opt/ffmpeg/bin/ffmpeg".to_string())
+            } else if name == FFPROBE_ENV {
+                Some(String::new())
+            } else {
+                None
+            }
+        });
+
+        assert_eq!(config.ffmpeg(), "/opt/ffmpeg/bin/ffmpeg");
+        // empty variables never override
+        assert_eq!(config.ffprobe(), "ffprobe");
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use super::tasks::validation::ValidationError;
+
 /// An error that aims to send error information to frontend.
 /// For i18n purpose, when an error thrown, it does not send text to frontend,
 /// otherwise, it send only an [`ErrorKind`] and some necessary keywords.
@@ -25,6 +27,21 @@ pub enum Error {
         #[serde(skip_serializing)]
         program: String,
     },
+    EmptyProgramPath {
+        name: String,
+    },
+    BinaryMismatch {
+        expected: String,
+        found: String,
+    },
+    /// Program wrapped by a command has an unterminated quote.
+    MalformedProgram {
+        program: String,
+    },
+    /// Program exists but is not executable, such as lacking permissions or being a text file.
+    BinaryNotExecutable {
+        program: String,
+    },
     FFmpegUnavailable {
         #[serde(skip_serializing)]
         program: String,
@@ -43,6 +60,9 @@ pub enum Error {
     FFprobeRuntimeError {
         reason: String,
     },
+    InvalidFFprobeQuery {
+        reason: String,
+    },
     #[cfg(unix)]
     FFmpegPidNotFound,
     #[cfg(unix)]
@@ -53,13 +73,108 @@ pub enum Error {
     DirectoryNotFound {
         path: String,
     },
+    FileNotFound {
+        path: String,
+    },
+    UnsupportedHashAlgorithm {
+        algorithm: String,
+    },
     TaskNotFound {
         id: String,
     },
     TaskExisting {
         id: String,
     },
+    TaskAlreadyRunning {
+        id: String,
+    },
+    TaskStateNotActionable {
+        state: String,
+    },
+    QueueFull {
+        max: usize,
+    },
+    OutputInUse {
+        path: String,
+    },
+    /// Fields of task args are invalid, all invalid fields are collected.
+    InvalidTaskArgs {
+        errors: Vec<ValidationError>,
+    },
+    InputNotFound {
+        path: String,
+    },
+    /// Input is a zero-byte file or has no stream detectable by ffprobe.
+    EmptyOrInvalidInput {
+        path: String,
+    },
+    UnsupportedProtocol {
+        protocol: String,
+    },
+    HwaccelUnavailable {
+        name: String,
+    },
+    PreviewDurationOutOfRange {
+        max: f64,
+    },
+    IncompatibleOutput {
+        codec: String,
+        container: String,
+    },
+    CopyWithFilter {
+        filter: String,
+    },
+    /// ffmpeg exited normally but an output file is zero bytes,
+    /// commonly caused by mismatched `-map` or `-ss` past end of input.
+    EmptyOutput {
+        path: String,
+    },
+    /// Disk of an output is full during encoding.
+    DiskFull {
+        path: String,
+    },
+    /// Output file descriptor is not opened or reserved by task, only with `fd-output` feature.
+    InvalidOutputFd {
+        fd: i32,
+    },
+    /// Task writing to output file descriptors can't run again, since they are closed once task ended.
+    OutputFdNotReusable {
+        id: String,
+    },
+    LoudnessMeasurementFailed {
+        reason: String,
+    },
+    LoudnessTargetOutOfRange {
+        min: f64,
+        max: f64,
+    },
+    InvalidSegmentArgs {
+        reason: String,
+    },
+    /// A stream mapped by `-map` can't be held by the output container,
+    /// such as a bitmap subtitle mapped into MP4.
+    UnsupportedStreamInContainer {
+        codec: String,
+        container: String,
+        stream: String,
+    },
+    /// Format specified by `-f` of an output disagrees with format implied by its file extension,
+    /// such as `out.mkv` muxed by `-f mp4`.
+    OutputFormatMismatch {
+        path: String,
+        format: String,
+        implied: String,
+    },
+    InvalidCaptureArgs {
+        reason: String,
+    },
+    /// Output captured from stdout exceeds maximum size in bytes.
+    CaptureTooLarge {
+        max: usize,
+    },
     ConfigurationNotLoaded,
+    /// Reasons are errors specific to a failing component, such as
+    /// [`Error::FFmpegUnavailable`], [`Error::FFprobeNotFound`] or [`Error::DirectoryNotFound`].
     ConfigurationUnavailable {
         reasons: Vec<Error>,
     },
@@ -103,6 +218,42 @@ impl Error {
         }
     }
 
+    pub fn empty_program_path<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::EmptyProgramPath { name: name.into() }
+    }
+
+    pub fn binary_mismatch<S1, S2>(expected: S1, found: S2) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        Self::BinaryMismatch {
+            expected: expected.into(),
+            found: found.into(),
+        }
+    }
+
+    pub fn malformed_program<S>(program: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::MalformedProgram {
+            program: program.into(),
+        }
+    }
+
+    pub fn binary_not_executable<S>(program: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::BinaryNotExecutable {
+            program: program.into(),
+        }
+    }
+
     pub fn ffmpeg_unavailable_with_raw_error<S, E>(program: S, raw_error: E) -> Self
     where
         S: Into<String>,
@@ -163,6 +314,15 @@ impl Error {
         }
     }
 
+    pub fn invalid_ffprobe_query<S>(reason: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::InvalidFFprobeQuery {
+            reason: reason.into(),
+        }
+    }
+
     pub fn directory_not_found<S>(path: S) -> Self
     where
         S: Into<String>,
@@ -170,6 +330,22 @@ impl Error {
         Self::DirectoryNotFound { path: path.into() }
     }
 
+    pub fn file_not_found<S>(path: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::FileNotFound { path: path.into() }
+    }
+
+    pub fn unsupported_hash_algorithm<S>(algorithm: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::UnsupportedHashAlgorithm {
+            algorithm: algorithm.into(),
+        }
+    }
+
     pub fn task_not_found<S>(id: S) -> Self
     where
         S: Into<String>,
@@ -184,6 +360,177 @@ impl Error {
         Self::TaskExisting { id: id.into() }
     }
 
+    pub fn task_already_running<S>(id: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::TaskAlreadyRunning { id: id.into() }
+    }
+
+    pub fn task_state_not_actionable<S>(state: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::TaskStateNotActionable {
+            state: state.into(),
+        }
+    }
+
+    pub fn queue_full(max: usize) -> Self {
+        Self::QueueFull { max }
+    }
+
+    pub fn output_in_use<S>(path: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::OutputInUse { path: path.into() }
+    }
+
+    pub fn invalid_task_args(errors: Vec<ValidationError>) -> Self {
+        Self::InvalidTaskArgs { errors }
+    }
+
+    pub fn input_not_found<S>(path: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::InputNotFound { path: path.into() }
+    }
+
+    pub fn empty_or_invalid_input<S>(path: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::EmptyOrInvalidInput { path: path.into() }
+    }
+
+    pub fn unsupported_protocol<S>(protocol: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::UnsupportedProtocol {
+            protocol: protocol.into(),
+        }
+    }
+
+    pub fn hwaccel_unavailable<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::HwaccelUnavailable { name: name.into() }
+    }
+
+    pub fn preview_duration_out_of_range(max: f64) -> Self {
+        Self::PreviewDurationOutOfRange { max }
+    }
+
+    pub fn incompatible_output<S1, S2>(codec: S1, container: S2) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        Self::IncompatibleOutput {
+            codec: codec.into(),
+            container: container.into(),
+        }
+    }
+
+    pub fn copy_with_filter<S>(filter: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::CopyWithFilter {
+            filter: filter.into(),
+        }
+    }
+
+    pub fn empty_output<S>(path: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::EmptyOutput { path: path.into() }
+    }
+
+    pub fn disk_full<S>(path: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::DiskFull { path: path.into() }
+    }
+
+    pub fn invalid_output_fd(fd: i32) -> Self {
+        Self::InvalidOutputFd { fd }
+    }
+
+    pub fn output_fd_not_reusable<S>(id: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::OutputFdNotReusable { id: id.into() }
+    }
+
+    pub fn invalid_segment_args<S>(reason: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::InvalidSegmentArgs {
+            reason: reason.into(),
+        }
+    }
+
+    pub fn unsupported_stream_in_container<S1, S2, S3>(codec: S1, container: S2, stream: S3) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+        S3: Into<String>,
+    {
+        Self::UnsupportedStreamInContainer {
+            codec: codec.into(),
+            container: container.into(),
+            stream: stream.into(),
+        }
+    }
+
+    pub fn output_format_mismatch<S1, S2, S3>(path: S1, format: S2, implied: S3) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+        S3: Into<String>,
+    {
+        Self::OutputFormatMismatch {
+            path: path.into(),
+            format: format.into(),
+            implied: implied.into(),
+        }
+    }
+
+    pub fn invalid_capture_args<S>(reason: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::InvalidCaptureArgs {
+            reason: reason.into(),
+        }
+    }
+
+    pub fn capture_too_large(max: usize) -> Self {
+        Self::CaptureTooLarge { max }
+    }
+
+    pub fn loudness_measurement_failed<S>(reason: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::LoudnessMeasurementFailed {
+            reason: reason.into(),
+        }
+    }
+
+    pub fn loudness_target_out_of_range(min: f64, max: f64) -> Self {
+        Self::LoudnessTargetOutOfRange { min, max }
+    }
+
     pub fn configuration_not_loaded() -> Self {
         Self::ConfigurationNotLoaded
     }
@@ -209,6 +556,20 @@ impl Display for Error {
             Error::FFprobeNotFound { program, .. } => {
                 f.write_fmt(format_args!("ffprobe binary not found: {}", program))
             }
+            Error::EmptyProgramPath { name } => {
+                f.write_fmt(format_args!("{} program path is empty", name))
+            }
+            Error::BinaryMismatch { expected, found } => f.write_fmt(format_args!(
+                "expected {} program but found {} program",
+                expected, found
+            )),
+            Error::MalformedProgram { program } => f.write_fmt(format_args!(
+                "program has an unterminated quote: {}",
+                program
+            )),
+            Error::BinaryNotExecutable { program } => {
+                f.write_fmt(format_args!("binary is not executable: {}", program))
+            }
             Error::FFmpegUnavailable {
                 program, raw_error, ..
             } => match raw_error {
@@ -233,6 +594,9 @@ impl Display for Error {
             Error::FFprobeRuntimeError { reason } => {
                 f.write_fmt(format_args!("ffprobe runtime error: {}", reason))
             }
+            Error::InvalidFFprobeQuery { reason } => {
+                f.write_fmt(format_args!("invalid ffprobe query: {}", reason))
+            }
             #[cfg(unix)]
             Error::FFmpegPidNotFound => f.write_str("failed to get pid of ffmpeg process"),
             #[cfg(unix)]
@@ -243,12 +607,112 @@ impl Display for Error {
             Error::DirectoryNotFound { path, .. } => {
                 f.write_fmt(format_args!("directory not found: {}", path))
             }
+            Error::FileNotFound { path } => f.write_fmt(format_args!("file not found: {}", path)),
+            Error::UnsupportedHashAlgorithm { algorithm } => {
+                f.write_fmt(format_args!("hash algorithm not supported: {}", algorithm))
+            }
             Error::TaskNotFound { id, .. } => {
                 f.write_fmt(format_args!("task with specified id not found: {}", id))
             }
             Error::TaskExisting { id, .. } => {
                 f.write_fmt(format_args!("task with specified id is existing: {}", id))
             }
+            Error::TaskAlreadyRunning { id } => f.write_fmt(format_args!(
+                "task with specified id is already running: {}",
+                id
+            )),
+            Error::TaskStateNotActionable { state } => f.write_fmt(format_args!(
+                "tasks in state {} can not be acted upon",
+                state
+            )),
+            Error::QueueFull { max } => {
+                f.write_fmt(format_args!("task queue is full, maximum {} tasks", max))
+            }
+            Error::OutputInUse { path } => f.write_fmt(format_args!(
+                "output is being written by another task: {}",
+                path
+            )),
+            Error::InvalidTaskArgs { errors } => f.write_fmt(format_args!(
+                "invalid task args: {}",
+                errors
+                    .iter()
+                    .map(|error| error.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            Error::InputNotFound { path, .. } => {
+                f.write_fmt(format_args!("input file not found: {}", path))
+            }
+            Error::EmptyOrInvalidInput { path } => {
+                f.write_fmt(format_args!("input file is empty or invalid: {}", path))
+            }
+            Error::UnsupportedProtocol { protocol } => f.write_fmt(format_args!(
+                "protocol not supported by ffmpeg: {}",
+                protocol
+            )),
+            Error::HwaccelUnavailable { name } => f.write_fmt(format_args!(
+                "hardware acceleration method not supported by ffmpeg: {}",
+                name
+            )),
+            Error::PreviewDurationOutOfRange { max } => f.write_fmt(format_args!(
+                "preview duration should be greater than 0 and no more than {} seconds",
+                max
+            )),
+            Error::IncompatibleOutput { codec, container } => f.write_fmt(format_args!(
+                "codec {} is incompatible with container {}",
+                codec, container
+            )),
+            Error::CopyWithFilter { filter } => f.write_fmt(format_args!(
+                "filter {} is combined with stream copy",
+                filter
+            )),
+            Error::EmptyOutput { path } => f.write_fmt(format_args!(
+                "output file is empty, nothing was encoded: {}",
+                path
+            )),
+            Error::DiskFull { path } => {
+                f.write_fmt(format_args!("no space left on device of output: {}", path))
+            }
+            Error::InvalidOutputFd { fd } => {
+                f.write_fmt(format_args!("invalid output file descriptor: {}", fd))
+            }
+            Error::OutputFdNotReusable { id } => f.write_fmt(format_args!(
+                "task writing to output file descriptors can not run again: {}",
+                id
+            )),
+            Error::InvalidSegmentArgs { reason } => {
+                f.write_fmt(format_args!("invalid segment args: {}", reason))
+            }
+            Error::UnsupportedStreamInContainer {
+                codec,
+                container,
+                stream,
+            } => f.write_fmt(format_args!(
+                "stream {} of codec {} is unsupported by container {}",
+                stream, codec, container
+            )),
+            Error::OutputFormatMismatch {
+                path,
+                format,
+                implied,
+            } => f.write_fmt(format_args!(
+                "output {} is muxed as {} by -f, but its extension implies {}",
+                path, format, implied
+            )),
+            Error::InvalidCaptureArgs { reason } => {
+                f.write_fmt(format_args!("invalid capture args: {}", reason))
+            }
+            Error::CaptureTooLarge { max } => f.write_fmt(format_args!(
+                "captured output exceeds maximum size of {} bytes",
+                max
+            )),
+            Error::LoudnessMeasurementFailed { reason } => {
+                f.write_fmt(format_args!("loudness measurement failed: {}", reason))
+            }
+            Error::LoudnessTargetOutOfRange { min, max } => f.write_fmt(format_args!(
+                "loudness target should be in [{}, {}] LUFS",
+                min, max
+            )),
             Error::ConfigurationNotLoaded => f.write_str("configuration not loaded"),
             Error::ConfigurationUnavailable { reasons } => {
                 #[cfg(windows)]
@@ -1,4 +1,6 @@
 pub mod commands;
+pub mod config;
 pub mod error;
 pub mod tasks;
-pub mod config;
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -0,0 +1,199 @@
+use std::path::PathBuf;
+
+use log::{info, warn};
+
+use crate::handlers::commands::task::TaskArgs;
+
+use super::validation::{is_file_input, resolved_output_paths};
+
+/// Finds source files of a finished task safe to delete.
+///
+/// Nothing is deleted unless every output is a distinct existing file,
+/// so null, stdout and protocol outputs keep all sources.
+/// Inputs which are outputs themselves, such as in-place transcoding, are never deleted.
+fn deletable_sources(args: &TaskArgs) -> Vec<PathBuf> {
+    let outputs = resolved_output_paths(args);
+    if outputs.is_empty() || outputs.len() != args.outputs.len() {
+        return Vec::new();
+    }
+    if !outputs.iter().all(|output| output.is_file()) {
+        return Vec::new();
+    }
+    if outputs
+        .iter()
+        .enumerate()
+        .any(|(i, output)| outputs[..i].contains(output))
+    {
+        return Vec::new();
+    }
+
+    let mut sources: Vec<PathBuf> = Vec::new();
+    for input in args.inputs.iter().filter(|input| is_file_input(input)) {
+        let Ok(source) = PathBuf::from(&input.path).canonicalize() else {
+            continue;
+        };
+        if !source.is_file() || outputs.contains(&source) || sources.contains(&source) {
+            continue;
+        }
+        sources.push(source);
+    }
+    sources
+}
+
+/// Deletes source files of a finished task if `delete_source_on_finish` enabled.
+pub async fn delete_sources(id: &str, args: &TaskArgs) {
+    if !args.delete_source_on_finish {
+        return;
+    }
+
+    let sources = deletable_sources(args);
+    if sources.is_empty() {
+        warn!(
+            "[{}] no source file deleted, outputs are not distinct existing files",
+            id
+        );
+        return;
+    }
+
+    for source in sources {
+        match tokio::fs::remove_file(&source).await {
+            Ok(_) => info!("[{}] source file deleted: {}", id, source.display()),
+            Err(err) => warn!(
+                "[{}] failed to delete source file {}: {}",
+                id,
+                source.display(),
+                err
+            ),
+        }
+    }
+}
+
+/// Renames partial outputs of a finished task to final paths if `atomic_output` enabled.
+///
+/// Falls back to copying and deleting if renaming fails, such as renaming across file systems.
+pub async fn finalize_partial_outputs(id: &str, args: &TaskArgs) {
+    for (partial_path, path) in args.partial_outputs() {
+        if tokio::fs::rename(&partial_path, &path).await.is_ok() {
+            info!("[{}] partial output renamed to {}", id, path.display());
+            continue;
+        }
+
+        let copied = async {
+            tokio::fs::copy(&partial_path, &path).await?;
+            tokio::fs::remove_file(&partial_path).await
+        };
+        match copied.await {
+            Ok(_) => info!("[{}] partial output copied to {}", id, path.display()),
+            Err(err) => warn!(
+                "[{}] failed to move partial output {} to {}: {}",
+                id,
+                partial_path.display(),
+                path.display(),
+                err
+            ),
+        }
+    }
+}
+
+/// Deletes partial outputs of a stopped or errored task if `atomic_output` enabled.
+pub async fn remove_partial_outputs(id: &str, args: &TaskArgs) {
+    for (partial_path, _) in args.partial_outputs() {
+        match tokio::fs::remove_file(&partial_path).await {
+            Ok(_) => info!(
+                "[{}] partial output deleted: {}",
+                id,
+                partial_path.display()
+            ),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => warn!(
+                "[{}] failed to delete partial output {}: {}",
+                id,
+                partial_path.display(),
+                err
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::handlers::commands::task::{TaskArgs, TaskInputArgs, TaskOutputArgs};
+
+    use super::{deletable_sources, finalize_partial_outputs, remove_partial_outputs};
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cleanup-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    fn args(inputs: &[&Path], outputs: &[Option<&Path>]) -> TaskArgs {
+        TaskArgs {
+            inputs: inputs
+                .iter()
+                .map(|path| TaskInputArgs {
+                    path: path.to_string_lossy().to_string(),
+                    args: vec![],
+                })
+                .collect(),
+            outputs: outputs
+                .iter()
+                .map(|path| TaskOutputArgs {
+                    path: path.map(|path| path.to_string_lossy().to_string()),
+                    ..Default::default()
+                })
+                .collect(),
+            delete_source_on_finish: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn deletes_sources_of_distinct_existing_outputs() {
+        let dir = temp_dir();
+        let (input, output) = (dir.join("in.mkv"), dir.join("out.mp4"));
+        std::fs::write(&input, b"in").unwrap();
+        std::fs::write(&output, b"out").unwrap();
+
+        let sources = deletable_sources(&args(&[&input, &input], &[Some(&output)]));
+        assert_eq!(sources, vec![input.canonicalize().unwrap()]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_sources_of_missing_null_or_in_place_outputs() {
+        let dir = temp_dir();
+        let (input, output) = (dir.join("in.mkv"), dir.join("out.mp4"));
+        std::fs::write(&input, b"in").unwrap();
+
+        assert!(deletable_sources(&args(&[&input], &[Some(&output)])).is_empty());
+        assert!(deletable_sources(&args(&[&input], &[None])).is_empty());
+        assert!(deletable_sources(&args(&[&input], &[Some(&input)])).is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn renames_or_removes_partial_outputs() {
+        let dir = temp_dir();
+        let (input, output) = (dir.join("in.mkv"), dir.join("out.mp4"));
+        let mut args = args(&[&input], &[Some(&output)]);
+        args.atomic_output = true;
+        let (partial, _) = args.partial_outputs().pop().unwrap();
+
+        std::fs::write(&partial, b"out").unwrap();
+        finalize_partial_outputs("task", &args).await;
+        assert!(!partial.exists());
+        assert_eq!(std::fs::read(&output).unwrap(), b"out");
+
+        std::fs::write(&partial, b"partial").unwrap();
+        remove_partial_outputs("task", &args).await;
+        assert!(!partial.exists());
+        assert_eq!(std::fs::read(&output).unwrap(), b"out");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
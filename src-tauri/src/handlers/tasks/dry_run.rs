@@ -0,0 +1,239 @@
+//! Dry run developer mode, simulating lifecycle of tasks without spawning ffmpeg.
+//!
+//! Once enabled, [`Idle`](super::state_machine::Idle) starts a simulated running state,
+//! which emits a few synthetic progress messages and then finishes,
+//! helping developing frontend and orchestration of tasks without real encodes.
+//!
+//! Dry run is enabled by `dryRun` of configuration in debug builds only,
+//! or by environment variable [`DRY_RUN_ENV`] in any builds,
+//! so that it is never enabled accidentally in release builds.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::{info, warn};
+use tauri::Manager;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    message::{TaskLifecycleEvent, TaskMessage, TaskRunningMessage, TASK_MESSAGE_EVENT},
+    progress::{Phase, ProgressType},
+    state_machine::{Errored, Finished, Stopped, TaskState, TaskStateCode},
+    task::Task,
+};
+
+/// Environment variable enabling dry run in any builds if set to `1`.
+pub static DRY_RUN_ENV: &'static str = "FFMPEG_TRANSCODER_DRY_RUN";
+
+/// Duration in seconds of media a simulated task pretends to transcode.
+const SIMULATED_DURATION: f64 = 10.0;
+
+/// Amount of synthetic progress messages a simulated task emits before finishing.
+const SIMULATED_STEPS: usize = 5;
+
+/// Interval between synthetic progress messages.
+const SIMULATED_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether tasks are simulated instead of spawning ffmpeg.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Returns `true` if tasks are simulated instead of spawning ffmpeg.
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::SeqCst)
+}
+
+/// Enables or disables dry run for tasks started later.
+///
+/// `configured` from configuration is only honored in debug builds,
+/// while [`DRY_RUN_ENV`] set to `1` enables dry run in any builds.
+pub fn set_dry_run(configured: bool) {
+    let by_env = std::env::var(DRY_RUN_ENV)
+        .map(|value| value == "1")
+        .unwrap_or(false);
+    if configured && !cfg!(debug_assertions) && !by_env {
+        warn!("dry run configured but ignored in release builds, set {DRY_RUN_ENV}=1 to enable");
+    }
+
+    let enabled = by_env || (configured && cfg!(debug_assertions));
+    if enabled != DRY_RUN.swap(enabled, Ordering::SeqCst) {
+        info!("dry run {}", if enabled { "enabled" } else { "disabled" });
+    }
+}
+
+/// Simulated running state, emitting synthetic progress messages and finishing without ffmpeg.
+///
+/// Pausing is not supported, since no process is there to be paused.
+pub struct DryRunning {
+    cancellation: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+impl DryRunning {
+    /// Starts simulating a task.
+    pub fn start(task: Task) -> Self {
+        info!("[{}] dry run, simulating task without ffmpeg", task.data.id);
+
+        let cancellation = CancellationToken::new();
+        let cancellation_cloned = cancellation.clone();
+        let handle = tokio::spawn(async move {
+            if simulate(&task, cancellation_cloned).await {
+                task.finish(Vec::new()).await;
+            }
+        });
+
+        Self {
+            cancellation,
+            handle,
+        }
+    }
+}
+
+/// Emits synthetic progress messages, returns `true` if simulation completes without cancelled.
+async fn simulate(task: &Task, cancellation: CancellationToken) -> bool {
+    let mut message = simulated_message(task.data.id.to_string());
+
+    let send = |message: &TaskRunningMessage| {
+        if let Err(err) = task
+            .data
+            .app_handle
+            .emit_all(TASK_MESSAGE_EVENT, TaskMessage::running(message))
+        {
+            warn!(
+                "[{}] failed to send simulated message: {}",
+                task.data.id, err
+            );
+        }
+    };
+
+    // interim message without any progress, the same as a real task initializing
+    send(&message);
+
+    for step in 1..=SIMULATED_STEPS {
+        tokio::select! {
+            _ = cancellation.cancelled() => return false,
+            _ = tokio::time::sleep(SIMULATED_INTERVAL) => {}
+        };
+
+        simulate_step(&mut message, step);
+        send(&message);
+        task.send_lifecycle(TaskLifecycleEvent::progress(&task.data.id, message.percent));
+        message.clear();
+    }
+
+    true
+}
+
+/// Creates the interim message of a simulated task, without any progress.
+fn simulated_message(id: String) -> TaskRunningMessage {
+    let progress_type = ProgressType::ByDuration {
+        duration: SIMULATED_DURATION,
+        duration_source_input: None,
+    };
+    TaskRunningMessage::new(id, progress_type, Phase::single())
+}
+
+/// Fills a cleared message with synthetic progress of one based `step`.
+fn simulate_step(message: &mut TaskRunningMessage, step: usize) {
+    let output_time = SIMULATED_DURATION * step as f64 / SIMULATED_STEPS as f64;
+    message.output_time_ms = Some((output_time * 1000000.0) as usize);
+    message.speed = Some(1.0);
+    message.has_activity = true;
+    message.push_raw(format!("out_time_ms={}", message.output_time_ms.unwrap())); // safely unwrap
+    message.push_raw("progress=continue".to_string());
+    message.percent = message.compute_percent();
+    message.eta_ms = message.compute_eta_ms();
+}
+
+#[async_trait]
+impl TaskState for DryRunning {
+    fn code(&self) -> TaskStateCode {
+        TaskStateCode::Running
+    }
+
+    fn message(&self) -> Option<&str> {
+        None
+    }
+
+    fn watchdog_exited(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    fn is_simulated(&self) -> bool {
+        true
+    }
+
+    async fn start(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to start a running task", task.data.id);
+        self
+    }
+
+    async fn pause(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to pause a simulated task", task.data.id);
+        self
+    }
+
+    async fn resume(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to resume a running task", task.data.id);
+        self
+    }
+
+    async fn stop(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+        self.cancellation.cancel();
+        Box::new(Stopped)
+    }
+
+    async fn finish(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+        Box::new(Finished)
+    }
+
+    async fn error(self: Box<Self>, _task: Task, reason: String) -> Box<dyn TaskState> {
+        self.cancellation.cancel();
+        Box::new(Errored { reason })
+    }
+
+    async fn reset(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to reset a running task", task.data.id);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{simulate_step, simulated_message, SIMULATED_DURATION, SIMULATED_STEPS};
+
+    #[test]
+    fn starts_with_interim_message() {
+        let message = simulated_message("task".to_string());
+        assert_eq!(message.percent, None);
+        assert_eq!(message.output_time_ms, None);
+        assert!(!message.has_activity);
+        assert!(message.raw.is_empty());
+    }
+
+    #[test]
+    fn advances_to_full_duration() {
+        let mut message = simulated_message("task".to_string());
+        let mut percents = Vec::new();
+        let mut etas = Vec::new();
+        for step in 1..=SIMULATED_STEPS {
+            simulate_step(&mut message, step);
+            assert!(message.has_activity);
+            assert_eq!(message.raw.len(), 2);
+            assert_eq!(message.raw[1], "progress=continue");
+            percents.push(message.percent.unwrap().round() as usize);
+            etas.push(message.eta_ms.unwrap());
+            message.clear();
+        }
+
+        assert_eq!(percents, vec![20, 40, 60, 80, 100]);
+        assert_eq!(
+            etas,
+            vec![8000, 6000, 4000, 2000, 0],
+            "eta of {SIMULATED_DURATION}s at speed 1"
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Outputs to pre-opened file descriptors, enabled by `fd-output` feature.
+//!
+//! Sandboxed distributions, such as Flatpak, can only write to paths granted by a portal,
+//! which hands over an already opened file descriptor instead of a path.
+//! Such outputs are written by ffmpeg through `pipe:<fd>`.
+//!
+//! # Platform support
+//!
+//! Only Unix platforms are supported, since file descriptors are inherited by ffmpeg process
+//! by clearing `FD_CLOEXEC` before exec. Field `fd` of output args doesn't exist on other platforms
+//! or without the feature, and is ignored if sent by frontend.
+//!
+//! # Ownership
+//!
+//! A file descriptor is owned by task once started, and is closed once task ended.
+//! Hence a task writing to file descriptors runs only once:
+//! it is never retried, and restarting or resetting it fails with
+//! [`Error::OutputFdNotReusable`](crate::handlers::error::Error::OutputFdNotReusable),
+//! otherwise ffmpeg would write to a closed descriptor or to one reused for another file.
+//! Ask the portal for a new descriptor and start a new task instead.
+
+use log::warn;
+use nix::libc;
+use tokio::process::Command;
+
+use crate::handlers::{commands::task::TaskArgs, error::Error};
+
+/// Returns file descriptors of all outputs.
+pub fn output_fds(args: &TaskArgs) -> Vec<i32> {
+    args.outputs.iter().filter_map(|output| output.fd).collect()
+}
+
+/// Points outputs having a file descriptor to `pipe:<fd>`, replacing their paths.
+///
+/// Standard streams are rejected, since stdin and stderr are used by task,
+/// and stdout is used by progress unless `progress_over_tcp` enabled.
+pub fn bind_output_fds(args: &mut TaskArgs) -> Result<(), Error> {
+    let progress_over_tcp = args.progress_over_tcp;
+    for output in args.outputs.iter_mut() {
+        let Some(fd) = output.fd else {
+            continue;
+        };
+
+        let reserved = fd == 0 || fd == 2 || (fd == 1 && !progress_over_tcp);
+        if fd < 0 || reserved {
+            return Err(Error::invalid_output_fd(fd));
+        }
+        // fails if fd is not opened
+        if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+            return Err(Error::invalid_output_fd(fd));
+        }
+
+        output.path = Some(format!("pipe:{fd}"));
+    }
+
+    Ok(())
+}
+
+/// Makes file descriptors of outputs inherited by ffmpeg process,
+/// by clearing `FD_CLOEXEC` flag in child process before exec.
+pub fn inherit_output_fds(command: &mut Command, args: &TaskArgs) {
+    let fds = output_fds(args);
+    if fds.is_empty() {
+        return;
+    }
+
+    unsafe {
+        command.pre_exec(move || {
+            for fd in fds.iter() {
+                let flags = libc::fcntl(*fd, libc::F_GETFD);
+                if flags == -1 || libc::fcntl(*fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Closes file descriptors of outputs once task ended.
+pub fn close_output_fds(id: &str, args: &TaskArgs) {
+    close_fds(id, output_fds(args));
+}
+
+/// Closes file descriptors of old outputs not used by new args, once args of a task replaced.
+pub fn close_replaced_output_fds(id: &str, old_args: &TaskArgs, new_args: &TaskArgs) {
+    let new_fds = output_fds(new_args);
+    close_fds(
+        id,
+        output_fds(old_args)
+            .into_iter()
+            .filter(|fd| !new_fds.contains(fd))
+            .collect(),
+    );
+}
+
+fn close_fds(id: &str, fds: Vec<i32>) {
+    for fd in fds {
+        if unsafe { libc::close(fd) } == -1 {
+            warn!(
+                "[{}] failed to close output fd {}: {}",
+                id,
+                fd,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
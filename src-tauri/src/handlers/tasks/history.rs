@@ -0,0 +1,203 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{error, warn};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::handlers::{commands::task::TaskArgs, error::Error};
+
+use super::message::FinalStats;
+
+/// File name of completed tasks history, placed in app data directory.
+pub static HISTORY_FILE: &'static str = "history.json";
+
+/// Maximum amount of reports kept in history, oldest ones are dropped first.
+pub const MAX_HISTORY_ENTRIES: usize = 500;
+
+/// Result of a completed task.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum TaskResult {
+    Finished,
+    Errored { reason: String },
+}
+
+/// Report of a completed task kept in history.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TaskReport {
+    pub id: String,
+    pub args: TaskArgs,
+    pub result: TaskResult,
+    /// Unix timestamp in milliseconds when task created.
+    pub created_at: u64,
+    /// Unix timestamp in milliseconds when task completed.
+    pub completed_at: u64,
+    #[serde(default)]
+    pub final_stats: Vec<FinalStats>,
+}
+
+/// Converts a system time into a unix timestamp in milliseconds.
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Bounded on-disk history of completed tasks.
+///
+/// Reports are appended by a dedicated writer task one at a time,
+/// so concurrent completions never interleave writes to the history file.
+pub struct TaskHistory {
+    path: Option<PathBuf>,
+    sender: Option<UnboundedSender<TaskReport>>,
+}
+
+impl TaskHistory {
+    /// Creates a task history persisting to `path` and starts its writer task.
+    /// History is disabled if `path` is `None`.
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let Some(path) = path else {
+            warn!("app data directory unavailable, task history disabled");
+            return Self {
+                path: None,
+                sender: None,
+            };
+        };
+
+        let (sender, mut receiver) = mpsc::unbounded_channel::<TaskReport>();
+        let writer_path = path.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut reports = load_reports(&writer_path).await;
+            while let Some(report) = receiver.recv().await {
+                append_report(&mut reports, report, MAX_HISTORY_ENTRIES);
+                if let Err(err) = save_reports(&writer_path, &reports).await {
+                    error!("failed to write task history: {}", err);
+                }
+            }
+        });
+
+        Self {
+            path: Some(path),
+            sender: Some(sender),
+        }
+    }
+
+    /// Appends a report, written later by writer task.
+    pub fn append(&self, report: TaskReport) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+
+        if let Err(err) = sender.send(report) {
+            error!("[{}] failed to append task history", err.0.id);
+        }
+    }
+
+    /// Reads reports from history file, newest first.
+    pub async fn read(&self, limit: usize) -> Vec<TaskReport> {
+        let Some(path) = &self.path else {
+            return Vec::new();
+        };
+
+        load_reports(path)
+            .await
+            .into_iter()
+            .rev()
+            .take(limit)
+            .collect()
+    }
+}
+
+/// Pushes a report to the back and drops oldest reports beyond `max_entries`.
+fn append_report(reports: &mut VecDeque<TaskReport>, report: TaskReport, max_entries: usize) {
+    reports.push_back(report);
+    while reports.len() > max_entries {
+        reports.pop_front();
+    }
+}
+
+/// Loads reports from history file, oldest first.
+/// A missing or malformed history file is regarded as empty.
+async fn load_reports(path: &Path) -> VecDeque<TaskReport> {
+    let Ok(content) = tokio::fs::read(path).await else {
+        return VecDeque::new();
+    };
+
+    match serde_json::from_slice(&content) {
+        Ok(reports) => reports,
+        Err(err) => {
+            warn!("malformed task history {}: {}", path.display(), err);
+            VecDeque::new()
+        }
+    }
+}
+
+/// Saves reports to a temporary file and renames it to history file,
+/// so readers never see a partially written history.
+async fn save_reports(path: &Path, reports: &VecDeque<TaskReport>) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await.map_err(Error::io)?;
+    }
+
+    let content = serde_json::to_vec(reports).map_err(|err| Error::io(err.into()))?;
+    let temp_path = path.with_extension("json.tmp");
+    tokio::fs::write(&temp_path, content)
+        .await
+        .map_err(Error::io)?;
+    tokio::fs::rename(&temp_path, path)
+        .await
+        .map_err(Error::io)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::{append_report, load_reports, save_reports, TaskReport, TaskResult};
+
+    fn report(id: &str) -> TaskReport {
+        TaskReport {
+            id: id.to_string(),
+            args: Default::default(),
+            result: TaskResult::Finished,
+            created_at: 0,
+            completed_at: 0,
+            final_stats: Vec::new(),
+        }
+    }
+
+    fn ids(reports: &VecDeque<TaskReport>) -> Vec<&str> {
+        reports.iter().map(|report| report.id.as_str()).collect()
+    }
+
+    #[test]
+    fn drops_oldest_reports() {
+        let mut reports = VecDeque::new();
+        for id in ["a", "b", "c", "d"] {
+            append_report(&mut reports, report(id), 3);
+        }
+        assert_eq!(ids(&reports), vec!["b", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn saves_and_loads_reports() {
+        let dir = std::env::temp_dir().join(format!("history-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("history.json");
+        assert!(load_reports(&path).await.is_empty());
+
+        let reports = VecDeque::from([report("a"), report("b")]);
+        save_reports(&path, &reports).await.unwrap();
+        assert_eq!(ids(&load_reports(&path).await), vec!["a", "b"]);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(load_reports(&path).await.is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
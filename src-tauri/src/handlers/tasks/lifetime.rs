@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tokio::process::{Child, Command};
+
+/// Whether ffmpeg processes keep running if app is killed without shutting down.
+static DETACH_CHILDREN: AtomicBool = AtomicBool::new(false);
+
+/// Returns `true` if ffmpeg processes are detached from app.
+pub fn is_detach_children() -> bool {
+    DETACH_CHILDREN.load(Ordering::SeqCst)
+}
+
+/// Detaches or binds ffmpeg processes started later from lifetime of app.
+/// Running processes are not changed.
+pub fn set_detach_children(detached: bool) {
+    DETACH_CHILDREN.store(detached, Ordering::SeqCst);
+}
+
+/// Binds lifetime of a process to app before spawning, unless children are detached,
+/// so that it never outlives app even if app is killed by `SIGKILL` or crashes.
+///
+/// On Unix, process is placed in its own process group, leaving signals sent to group of app alone.
+/// Processes reading stdin inherited from app, as `reads_stdin` tells, stay in group of app instead,
+/// since a background process group reading from terminal is stopped by `SIGTTIN`.
+/// On Linux, process receives `SIGTERM` once app dies, by `prctl(PR_SET_PDEATHSIG)`.
+/// Since death signal is bound to the thread spawning the process rather than app,
+/// it relies on worker threads of async runtime living as long as app.
+///
+/// On Windows, see [`bind_child_to_app`], which works after spawning.
+pub fn bind_command_to_app(command: &mut Command, reads_stdin: bool) {
+    if is_detach_children() {
+        return;
+    }
+
+    #[cfg(unix)]
+    if !reads_stdin {
+        command.process_group(0);
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe {
+        use nix::libc;
+
+        command.pre_exec(|| {
+            if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    #[cfg(not(unix))]
+    let _ = (command, reads_stdin);
+}
+
+/// Binds lifetime of a spawned process to app, unless children are detached.
+///
+/// On Windows, process is assigned to a job object of app with `JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE`,
+/// which is never closed explicitly, so system kills all processes in job once app exits by any means.
+///
+/// Does nothing on other platforms, see [`bind_command_to_app`].
+pub fn bind_child_to_app(child: &Child) -> Result<(), std::io::Error> {
+    if is_detach_children() {
+        return Ok(());
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::{
+            Foundation::HANDLE, System::JobObjects::AssignProcessToJobObject,
+        };
+
+        let Some(job) = app_job() else {
+            return Err(std::io::Error::last_os_error());
+        };
+        let Some(handle) = child.raw_handle() else {
+            return Ok(());
+        };
+
+        if unsafe { AssignProcessToJobObject(job, handle as HANDLE) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    #[cfg(not(windows))]
+    let _ = child;
+
+    Ok(())
+}
+
+/// Returns the job object of app, created on first call.
+#[cfg(windows)]
+fn app_job() -> Option<windows_sys::Win32::Foundation::HANDLE> {
+    use std::sync::OnceLock;
+
+    use windows_sys::Win32::{
+        Foundation::CloseHandle,
+        System::JobObjects::{
+            CreateJobObjectW, JobObjectExtendedLimitInformation, SetInformationJobObject,
+            JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        },
+    };
+
+    static APP_JOB: OnceLock<Option<isize>> = OnceLock::new();
+
+    *APP_JOB.get_or_init(|| unsafe {
+        let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+        if job == 0 {
+            return None;
+        }
+
+        let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        let result = SetInformationJobObject(
+            job,
+            JobObjectExtendedLimitInformation,
+            &info as *const _ as *const std::ffi::c_void,
+            std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        );
+        if result == 0 {
+            CloseHandle(job);
+            return None;
+        }
+
+        Some(job)
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::process::Stdio;
+
+    use nix::libc;
+    use tokio::process::Command;
+
+    use super::bind_command_to_app;
+
+    /// Spawns a sleeping process bound to app, returning process group of it.
+    async fn spawned_group(reads_stdin: bool) -> libc::pid_t {
+        let mut command = Command::new("sleep");
+        command.arg("5").stdin(Stdio::null()).kill_on_drop(true);
+        bind_command_to_app(&mut command, reads_stdin);
+
+        let mut child = command.spawn().unwrap();
+        let group = unsafe { libc::getpgid(child.id().unwrap() as libc::pid_t) };
+        child.kill().await.unwrap();
+        group
+    }
+
+    #[tokio::test]
+    async fn places_process_in_own_group() {
+        let group = spawned_group(false).await;
+        assert_ne!(group, unsafe { libc::getpgrp() });
+    }
+
+    #[tokio::test]
+    async fn keeps_stdin_readers_in_group_of_app() {
+        let group = spawned_group(true).await;
+        assert_eq!(group, unsafe { libc::getpgrp() });
+    }
+}
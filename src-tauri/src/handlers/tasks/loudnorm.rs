@@ -0,0 +1,552 @@
+//! Two-pass loudness normalization by `loudnorm` filter, enabled by `loudnorm_target` of task args.
+//!
+//! A task normalizing loudness runs ffmpeg twice as two phases of a single task.
+//! [`Measuring`] runs the first pass as phase `0`, measuring loudness of inputs without writing outputs,
+//! then [`Idle`](super::state_machine::Idle) runs the second pass as phase `1`,
+//! applying measured values to outputs, see [`phased_args`].
+//!
+//! Measurement is kept by task once measured, so that a retried or reset task skips the first pass.
+
+use std::{collections::VecDeque, process::Stdio};
+
+use async_trait::async_trait;
+use log::{info, trace, warn};
+use tauri::Manager;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::handlers::{
+    commands::{process::create_process, task::TaskArgs},
+    error::Error,
+};
+
+use super::{
+    lifetime::{bind_child_to_app, bind_command_to_app},
+    message::{TaskLifecycleEvent, TaskMessage, TaskRunningMessage, TASK_MESSAGE_EVENT},
+    progress::{find_progress_type, Phase},
+    state_machine::{
+        kill_process, spawn_error, Errored, Idle, ProgressLines, Stopped, TaskState, TaskStateCode,
+    },
+    task::Task,
+};
+
+/// True peak in dBTP used by loudness normalization.
+const LOUDNORM_TRUE_PEAK: f64 = -1.5;
+/// Loudness range in LU used by loudness normalization.
+const LOUDNORM_LOUDNESS_RANGE: f64 = 11.0;
+
+/// Minimum integrated loudness target in LUFS accepted by `loudnorm` filter.
+pub const MIN_LOUDNORM_TARGET: f64 = -70.0;
+/// Maximum integrated loudness target in LUFS accepted by `loudnorm` filter.
+pub const MAX_LOUDNORM_TARGET: f64 = -5.0;
+
+/// Output flags along with their values carried into the measuring pass,
+/// so that the same part of the same streams is measured as encoded by the second pass.
+static MEASURED_OUTPUT_FLAGS: [&'static str; 4] = ["-ss", "-t", "-to", "-map"];
+
+/// Maximum amount of the newest stderr lines kept by measuring pass,
+/// enough to hold the JSON block printed as the last lines.
+const MAX_MEASURE_STDERR_LINES: usize = 64;
+
+/// Phase of measuring pass.
+pub const MEASURE_PHASE: Phase = Phase { index: 0, total: 2 };
+/// Phase of applying pass.
+pub const APPLY_PHASE: Phase = Phase { index: 1, total: 2 };
+
+/// Measured values printed by the first pass of `loudnorm` filter.
+#[derive(Debug, serde::Deserialize)]
+struct LoudnormMeasurement {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+impl LoudnormMeasurement {
+    /// Extracts measurement from the JSON block printed by `loudnorm` to stderr.
+    fn from_stderr(stderr: &str) -> Result<Self, Error> {
+        let (Some(start), Some(end)) = (stderr.rfind('{'), stderr.rfind('}')) else {
+            return Err(Error::loudness_measurement_failed(
+                "measurement not found in ffmpeg output",
+            ));
+        };
+        if start > end {
+            return Err(Error::loudness_measurement_failed(
+                "measurement not found in ffmpeg output",
+            ));
+        }
+
+        let measurement = serde_json::from_str::<Self>(&stderr[start..=end])
+            .map_err(|err| Error::loudness_measurement_failed(err.to_string()))?;
+
+        // silent audio measures as -inf, which could not be applied to the second pass
+        let all_finite = [
+            &measurement.input_i,
+            &measurement.input_tp,
+            &measurement.input_lra,
+            &measurement.input_thresh,
+            &measurement.target_offset,
+        ]
+        .iter()
+        .all(|value| {
+            value
+                .trim()
+                .parse::<f64>()
+                .map(|value| value.is_finite())
+                .unwrap_or(false)
+        });
+        if !all_finite {
+            return Err(Error::loudness_measurement_failed(
+                "measurement contains invalid values",
+            ));
+        }
+
+        Ok(measurement)
+    }
+
+    /// Returns `loudnorm` filter of the second pass applying this measurement.
+    fn apply_filter(&self, target_i: f64) -> String {
+        format!(
+            "loudnorm=I={}:TP={}:LRA={}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            target_i,
+            LOUDNORM_TRUE_PEAK,
+            LOUDNORM_LOUDNESS_RANGE,
+            self.input_i.trim(),
+            self.input_tp.trim(),
+            self.input_lra.trim(),
+            self.input_thresh.trim(),
+            self.target_offset.trim(),
+        )
+    }
+}
+
+/// Validates integrated loudness target `target_i` is in range accepted by `loudnorm` filter.
+pub fn validate_target(target_i: f64) -> Result<(), Error> {
+    if (MIN_LOUDNORM_TARGET..=MAX_LOUDNORM_TARGET).contains(&target_i) {
+        Ok(())
+    } else {
+        Err(Error::loudness_target_out_of_range(
+            MIN_LOUDNORM_TARGET,
+            MAX_LOUDNORM_TARGET,
+        ))
+    }
+}
+
+/// Returns `true` if arg is an audio filter flag, `-af` or `-filter:a`.
+fn is_audio_filter_flag(arg: &str) -> bool {
+    arg == "-af" || arg == "-filter:a"
+}
+
+/// Chains `filter` after audio filters specified by output args,
+/// or appends `-af` of `filter` if none specified.
+/// Only the last audio filter flag takes effect in ffmpeg, hence `filter` is chained onto it.
+fn chain_audio_filter(output_args: &mut Vec<String>, filter: &str) {
+    let last_filter = output_args
+        .iter()
+        .rposition(|arg| is_audio_filter_flag(arg))
+        .filter(|index| index + 1 < output_args.len());
+    match last_filter {
+        Some(index) => output_args[index + 1] = format!("{},{}", output_args[index + 1], filter),
+        None => output_args.extend(["-af".to_string(), filter.to_string()]),
+    }
+}
+
+/// Returns ffmpeg args of the first pass, measuring loudness of inputs and discarding outputs.
+/// `loudnorm` prints measurement in info level.
+///
+/// Global args, audio filters and [`MEASURED_OUTPUT_FLAGS`] of the first output are carried,
+/// so that the measured audio is the same as the one encoded by the second pass.
+fn measure_args(args: &TaskArgs, target_i: f64) -> Vec<String> {
+    let measure_filter = format!(
+        "loudnorm=I={}:TP={}:LRA={}:print_format=json",
+        target_i, LOUDNORM_TRUE_PEAK, LOUDNORM_LOUDNESS_RANGE
+    );
+
+    let mut cli_args = ["-hide_banner", "-progress", "pipe:1", "-nostats"]
+        .into_iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>();
+    cli_args.extend(args.global_args.iter().cloned());
+    for input in &args.inputs {
+        cli_args.extend(input.args.iter().cloned());
+        cli_args.extend(["-i".to_string(), input.path.clone()]);
+    }
+
+    let mut output_args = Vec::new();
+    if let Some(output) = args.outputs.first() {
+        let mut iter = output.args.iter();
+        while let Some(arg) = iter.next() {
+            if MEASURED_OUTPUT_FLAGS.contains(&arg.as_str()) || is_audio_filter_flag(arg) {
+                output_args.push(arg.clone());
+                output_args.extend(iter.next().cloned());
+            }
+        }
+    }
+    chain_audio_filter(&mut output_args, &measure_filter);
+    cli_args.extend(output_args);
+    cli_args.extend(["-f", "null", "-"].into_iter().map(|arg| arg.to_string()));
+    cli_args
+}
+
+/// Returns args of the second pass, chaining the filter applying measurement after
+/// audio filters of each output, see [`chain_audio_filter`].
+pub fn phased_args(args: &TaskArgs, apply_filter: &str) -> TaskArgs {
+    let mut args = args.clone();
+    for output in &mut args.outputs {
+        chain_audio_filter(&mut output.args, apply_filter);
+    }
+    args
+}
+
+/// Running state of the first pass, measuring loudness of inputs.
+///
+/// Pausing is not supported, the first pass is stopped and measured again instead.
+pub struct Measuring {
+    cancellation: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+impl Measuring {
+    /// Starts measuring loudness of inputs.
+    pub fn start(task: Task, target_i: f64) -> Self {
+        info!("[{}] start measuring loudness", task.data.id);
+
+        let cancellation = CancellationToken::new();
+        let cancellation_cloned = cancellation.clone();
+        let handle = tokio::spawn(async move {
+            match measure(&task, target_i, cancellation_cloned).await {
+                Ok(Some(apply_filter)) => {
+                    let _ = task.data.loudnorm_filter.set(apply_filter);
+                    task.next_phase().await;
+                }
+                Ok(None) => info!("[{}] loudness measuring cancelled", task.data.id),
+                Err(err) => task.error(err.to_string()).await,
+            }
+        });
+
+        Self {
+            cancellation,
+            handle,
+        }
+    }
+}
+
+/// Runs the first pass and sends progress messages of it,
+/// returns `loudnorm` filter of the second pass, or `None` if cancelled.
+async fn measure(
+    task: &Task,
+    target_i: f64,
+    cancellation: CancellationToken,
+) -> Result<Option<String>, Error> {
+    let progress_type = find_progress_type(&task.data.ffprobe_program, &task.data.args).await?;
+
+    let mut command = create_process(
+        &task.data.ffmpeg_program,
+        measure_args(&task.data.args, target_i),
+    );
+    bind_command_to_app(&mut command, false);
+    let mut process = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| spawn_error(&task.data.ffmpeg_program, err))?;
+    if let Err(err) = bind_child_to_app(&process) {
+        warn!("[{}] failed to bind process to app: {}", task.data.id, err);
+    }
+
+    // stderr is read all along, otherwise ffmpeg blocks once pipe is full
+    let stderr = process.stderr.take().unwrap(); // safely unwrap
+    let stderr_handle = tokio::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        let mut recent = VecDeque::with_capacity(MAX_MEASURE_STDERR_LINES);
+        while let Ok(Some(line)) = lines.next_line().await {
+            if recent.len() >= MAX_MEASURE_STDERR_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(line);
+        }
+        Vec::from(recent).join("\n")
+    });
+
+    let mut reader = ProgressLines::new(process.stdout.take().unwrap()); // safely unwrap
+    let mut message =
+        TaskRunningMessage::new(task.data.id.to_string(), progress_type, MEASURE_PHASE);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let len = tokio::select! {
+            _ = cancellation.cancelled() => {
+                if let Err(err) = kill_process(&mut process, task).await {
+                    warn!("[{}] failed to kill ffmpeg: {}", task.data.id, err);
+                }
+                return Ok(None);
+            }
+            len = reader.read_line(&mut line) => len.map_err(Error::internal)?,
+        };
+        if len == 0 {
+            break;
+        }
+
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        trace!(
+            "[{}] capture measuring output: {}",
+            task.data.id,
+            line.trim()
+        );
+        match key.trim() {
+            "out_time_ms" => {
+                message.output_time_ms = message.parse_int_field("out_time_ms", value.trim());
+            }
+            "speed" => {
+                message.speed = value.trim().trim_end_matches('x').parse::<f64>().ok();
+            }
+            "progress" => {
+                message.has_activity |= message.output_time_ms.unwrap_or(0) > 0;
+                message.percent = message.compute_percent();
+                message.eta_ms = message.compute_eta_ms();
+                if let Err(err) = task
+                    .data
+                    .app_handle
+                    .emit_all(TASK_MESSAGE_EVENT, TaskMessage::running(&message))
+                {
+                    warn!("[{}] failed to send message: {}", task.data.id, err);
+                }
+                task.send_lifecycle(TaskLifecycleEvent::progress(&task.data.id, message.percent));
+                message.clear();
+            }
+            _ => {}
+        }
+    }
+
+    let status = process.wait().await.map_err(Error::internal)?;
+    let stderr = stderr_handle.await.unwrap_or_default();
+    if !status.success() {
+        return Err(Error::ffmpeg_runtime_error(
+            stderr.lines().last().unwrap_or_default().trim(),
+        ));
+    }
+
+    Ok(Some(
+        LoudnormMeasurement::from_stderr(&stderr)?.apply_filter(target_i),
+    ))
+}
+
+#[async_trait]
+impl TaskState for Measuring {
+    fn code(&self) -> TaskStateCode {
+        TaskStateCode::Running
+    }
+
+    fn message(&self) -> Option<&str> {
+        None
+    }
+
+    fn watchdog_exited(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Starts the second pass once measured, see [`Task::next_phase`].
+    async fn start(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        if task.data.loudnorm_filter.get().is_none() {
+            warn!("[{}] attempting to start a measuring task", task.data.id);
+            return self;
+        }
+        Box::new(Idle).start(task).await
+    }
+
+    async fn pause(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to pause a measuring task", task.data.id);
+        self
+    }
+
+    async fn resume(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to resume a measuring task", task.data.id);
+        self
+    }
+
+    async fn stop(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+        self.cancellation.cancel();
+        Box::new(Stopped)
+    }
+
+    async fn finish(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to finish a measuring task", task.data.id);
+        self
+    }
+
+    async fn error(self: Box<Self>, _task: Task, reason: String) -> Box<dyn TaskState> {
+        self.cancellation.cancel();
+        Box::new(Errored::from_string(reason))
+    }
+
+    async fn reset(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to reset a measuring task", task.data.id);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::commands::task::{TaskArgs, TaskInputArgs, TaskOutputArgs};
+
+    use super::{measure_args, phased_args, validate_target, LoudnormMeasurement};
+
+    fn args() -> TaskArgs {
+        TaskArgs {
+            inputs: vec![TaskInputArgs {
+                path: "in.wav".to_string(),
+                args: vec!["-ss".to_string(), "5".to_string()],
+            }],
+            outputs: vec![TaskOutputArgs {
+                path: Some("out.flac".to_string()),
+                args: vec!["-c:a".to_string(), "flac".to_string()],
+                ..Default::default()
+            }],
+            loudnorm_target: Some(-16.0),
+            ..Default::default()
+        }
+    }
+
+    static STDERR: &'static str = r#"[Parsed_loudnorm_0 @ 0x5581]
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.47",
+	"input_lra" : "18.06",
+	"input_thresh" : "-39.20",
+	"output_i" : "-16.58",
+	"output_tp" : "-1.50",
+	"output_lra" : "14.78",
+	"output_thresh" : "-27.71",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.58"
+}"#;
+
+    #[test]
+    fn measures_inputs_into_null() {
+        assert_eq!(
+            measure_args(&args(), -16.0),
+            vec![
+                "-hide_banner",
+                "-progress",
+                "pipe:1",
+                "-nostats",
+                "-ss",
+                "5",
+                "-i",
+                "in.wav",
+                "-af",
+                "loudnorm=I=-16:TP=-1.5:LRA=11:print_format=json",
+                "-f",
+                "null",
+                "-",
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_measurement_to_outputs() {
+        let filter = LoudnormMeasurement::from_stderr(STDERR)
+            .unwrap()
+            .apply_filter(-16.0);
+        assert_eq!(
+            filter,
+            "loudnorm=I=-16:TP=-1.5:LRA=11:measured_I=-27.61:measured_TP=-4.47:measured_LRA=18.06:measured_thresh=-39.20:offset=0.58:linear=true"
+        );
+
+        let phased = phased_args(&args(), &filter);
+        assert_eq!(phased.outputs[0].args, vec!["-c:a", "flac", "-af", &filter]);
+        assert_eq!(phased.inputs[0].args, args().inputs[0].args);
+    }
+
+    #[test]
+    fn rejects_missing_measurement() {
+        assert!(LoudnormMeasurement::from_stderr("Error opening input").is_err());
+        assert!(LoudnormMeasurement::from_stderr("} {").is_err());
+    }
+
+    #[test]
+    fn rejects_silent_measurement() {
+        let stderr = STDERR.replace("\"-27.61\"", "\"-inf\"");
+        assert!(LoudnormMeasurement::from_stderr(&stderr).is_err());
+    }
+
+    #[test]
+    fn chains_existing_audio_filters() {
+        let mut args = args();
+        args.outputs[0].args = ["-af", "highpass=f=80", "-c:a", "flac"]
+            .into_iter()
+            .map(|arg| arg.to_string())
+            .collect();
+
+        let phased = phased_args(&args, "loudnorm=I=-16");
+        assert_eq!(
+            phased.outputs[0].args,
+            vec!["-af", "highpass=f=80,loudnorm=I=-16", "-c:a", "flac"]
+        );
+    }
+
+    #[test]
+    fn measures_the_same_audio_as_encoded() {
+        let mut args = args();
+        args.global_args = vec!["-threads".to_string(), "2".to_string()];
+        args.outputs[0].args = [
+            "-map",
+            "0:a:1",
+            "-ss",
+            "10",
+            "-t",
+            "30",
+            "-filter:a",
+            "volume=2",
+            "-c:a",
+            "flac",
+        ]
+        .into_iter()
+        .map(|arg| arg.to_string())
+        .collect();
+
+        assert_eq!(
+            measure_args(&args, -16.0),
+            vec![
+                "-hide_banner",
+                "-progress",
+                "pipe:1",
+                "-nostats",
+                "-threads",
+                "2",
+                "-ss",
+                "5",
+                "-i",
+                "in.wav",
+                "-map",
+                "0:a:1",
+                "-ss",
+                "10",
+                "-t",
+                "30",
+                "-filter:a",
+                "volume=2,loudnorm=I=-16:TP=-1.5:LRA=11:print_format=json",
+                "-f",
+                "null",
+                "-",
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_loudness_target() {
+        for target_i in [-70.0, -16.0, -5.0] {
+            assert!(validate_target(target_i).is_ok(), "{target_i}");
+        }
+        for target_i in [-71.0, -4.0, 0.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(validate_target(target_i).is_err(), "{target_i}");
+        }
+    }
+}
@@ -1,13 +1,31 @@
+use std::{collections::HashMap, sync::OnceLock, time::SystemTime};
+
+use regex::Regex;
 use smallvec::SmallVec;
 
-use super::progress::ProgressType;
+use super::{
+    history::unix_millis,
+    progress::{Phase, ProgressType},
+};
 
 pub static TASK_MESSAGE_EVENT: &'static str = "transcoding";
+/// Event of structured task lifecycle events, see [`TaskLifecycleEvent`].
+pub static TASK_LIFECYCLE_EVENT: &'static str = "task-lifecycle";
+
+/// Maximum amount of unrecognized progress fields kept in [`TaskRunningMessage::extra`].
+pub const MAX_EXTRA_FIELDS: usize = 32;
+
+/// Maximum amount of raw lines kept in [`TaskRunningMessage::raw`], oldest ones are dropped first.
+pub const MAX_RAW_LINES: usize = 64;
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct TaskRunningMessage {
     pub id: String,
     pub progress_type: ProgressType,
+    /// Zero based index of current phase, progress of all phases make up the task progress.
+    pub phase: usize,
+    pub phases_total: usize,
+    /// Raw lines since the last progress block, at most [`MAX_RAW_LINES`] newest ones.
     pub raw: SmallVec<[String; 15]>,
     pub frame: Option<usize>,
     pub fps: Option<f64>,
@@ -17,13 +35,37 @@ pub struct TaskRunningMessage {
     pub dup_frames: Option<usize>,
     pub drop_frames: Option<usize>,
     pub speed: Option<f64>,
+    pub cpu_percent: Option<f64>,
+    pub rss_bytes: Option<usize>,
+    /// Overall progress of all phases in `[0, 100]`, never decreases during capturing.
+    /// `None` if progress type is [`ProgressType::Unspecified`].
+    pub percent: Option<f64>,
+    /// Estimated remaining time in milliseconds of all phases, computed from duration and speed.
+    /// `None` if duration or speed is unknown, such as [`ProgressType::ByFileSize`].
+    pub eta_ms: Option<usize>,
+    /// Whether `frame` or `output_time_ms` has ever advanced since task started,
+    /// telling that ffmpeg is working even if progress is unknown.
+    pub has_activity: bool,
+    /// Whether this is the first message after resuming.
+    /// `output_time_ms` continues from where ffmpeg paused,
+    /// so a gap between this and the previous message is not a regression.
+    pub resumed: bool,
+    /// Unrecognized `key=value` progress fields,
+    /// emitted by some ffmpeg forks or builds.
+    pub extra: HashMap<String, String>,
+    /// Integer fields reported as `N/A` by ffmpeg in this message, which are `None`.
+    pub unavailable: SmallVec<[&'static str; 5]>,
+    /// Integer fields failed to parse in this message, which are `None` as well.
+    pub malformed: SmallVec<[&'static str; 5]>,
 }
 
 impl TaskRunningMessage {
-    pub fn new(id: String, progress_type: ProgressType) -> Self {
+    pub fn new(id: String, progress_type: ProgressType, phase: Phase) -> Self {
         Self {
             id,
             progress_type,
+            phase: phase.index,
+            phases_total: phase.total,
             raw: SmallVec::new(),
             frame: None,
             fps: None,
@@ -33,6 +75,15 @@ impl TaskRunningMessage {
             dup_frames: None,
             drop_frames: None,
             speed: None,
+            cpu_percent: None,
+            rss_bytes: None,
+            percent: None,
+            eta_ms: None,
+            has_activity: false,
+            resumed: false,
+            extra: HashMap::new(),
+            unavailable: SmallVec::new(),
+            malformed: SmallVec::new(),
         }
     }
 
@@ -44,29 +95,468 @@ impl TaskRunningMessage {
         self.output_time_ms = None;
         self.dup_frames = None;
         self.drop_frames = None;
+        self.cpu_percent = None;
+        self.rss_bytes = None;
+        self.percent = None;
+        self.eta_ms = None;
+        self.resumed = false;
         self.raw.clear();
+        self.extra.clear();
+        self.unavailable.clear();
+        self.malformed.clear();
+    }
+
+    /// Pushes a raw line, dropping the oldest one if [`MAX_RAW_LINES`] reached,
+    /// so that lines never terminated by a `progress` field can't grow without bound.
+    pub fn push_raw(&mut self, line: String) {
+        if self.raw.len() >= MAX_RAW_LINES {
+            self.raw.remove(0);
+        }
+        self.raw.push(line);
+    }
+
+    /// Parses value of an integer progress field, such as `frame` or `total_size`.
+    ///
+    /// Returns `None` if value is `N/A`, which ffmpeg reports before the field is known,
+    /// or if value fails to parse, recording field in [`Self::unavailable`] or [`Self::malformed`]
+    /// respectively, so that no frames processed is told apart from an unknown frame count.
+    pub fn parse_int_field(&mut self, field: &'static str, value: &str) -> Option<usize> {
+        if value.eq_ignore_ascii_case("N/A") {
+            self.unavailable.push(field);
+            return None;
+        }
+
+        match value.parse::<usize>() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                self.malformed.push(field);
+                None
+            }
+        }
+    }
+
+    /// Computes overall progress of all phases in `[0, 100]` from current fields.
+    /// For [`ProgressType::Auto`], the larger one of duration and file size progress is taken.
+    ///
+    /// Returns `None` if progress type is [`ProgressType::Unspecified`].
+    pub fn compute_percent(&self) -> Option<f64> {
+        let ratio = |value: f64, total: f64| {
+            if total > 0.0 {
+                (value / total).clamp(0.0, 1.0)
+            } else {
+                0.0
+            }
+        };
+        // `out_time_ms` printed by ffmpeg is in microseconds indeed
+        let duration_ratio = |duration: f64| {
+            ratio(
+                self.output_time_ms.unwrap_or(0) as f64 / 1000000.0,
+                duration,
+            )
+        };
+        let size_ratio = |size: usize| ratio(self.total_size.unwrap_or(0) as f64, size as f64);
+
+        let phase_ratio = match self.progress_type {
+            ProgressType::ByDuration { duration, .. } => duration_ratio(duration),
+            ProgressType::ByFileSize { size } => size_ratio(size),
+            ProgressType::Auto {
+                duration,
+                file_size,
+                ..
+            } => duration_ratio(duration).max(size_ratio(file_size)),
+            ProgressType::Unspecified { .. } => return None,
+        };
+
+        let phases_total = self.phases_total.max(1) as f64;
+        Some(((self.phase as f64 + phase_ratio) / phases_total * 100.0).clamp(0.0, 100.0))
+    }
+
+    /// Computes estimated remaining time in milliseconds of all phases from current fields,
+    /// as remaining duration divided by speed. Each phase is regarded as taking the same duration.
+    ///
+    /// Remaining duration of current phase is clamped to `0`, since ffmpeg may report
+    /// output time past the estimated duration.
+    /// Returns `None` if progress type has no duration or speed is unknown or zero.
+    pub fn compute_eta_ms(&self) -> Option<usize> {
+        let duration = match self.progress_type {
+            ProgressType::ByDuration { duration, .. } | ProgressType::Auto { duration, .. } => {
+                duration
+            }
+            ProgressType::ByFileSize { .. } | ProgressType::Unspecified { .. } => return None,
+        };
+        let speed = self.speed.filter(|speed| *speed > 0.0)?;
+
+        // `out_time_ms` printed by ffmpeg is in microseconds indeed
+        let duration_ms = duration * 1000.0;
+        let output_time_ms = self.output_time_ms.unwrap_or(0) as f64 / 1000.0;
+        let following_phases = self.phases_total.saturating_sub(self.phase + 1) as f64;
+        let remaining_ms = (duration_ms - output_time_ms).max(0.0) + following_phases * duration_ms;
+
+        Some((remaining_ms / speed).round() as usize)
+    }
+
+    /// Stores an unrecognized progress field.
+    /// New keys are dropped once [`MAX_EXTRA_FIELDS`] reached,
+    /// but values of existing keys are still updated.
+    pub fn insert_extra(&mut self, key: &str, value: &str) {
+        if let Some(existing) = self.extra.get_mut(key) {
+            *existing = value.to_string();
+        } else if self.extra.len() < MAX_EXTRA_FIELDS {
+            self.extra.insert(key.to_string(), value.to_string());
+        }
+    }
+}
+
+/// Final summary of an output printed by ffmpeg on clean exit, sizes are in kB.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct FinalStats {
+    pub video_kb: usize,
+    pub audio_kb: usize,
+    pub subtitle_kb: usize,
+    pub other_kb: usize,
+    /// `None` if ffmpeg reports muxing overhead as unknown.
+    pub muxing_overhead_percent: Option<f64>,
+}
+
+impl FinalStats {
+    /// Parses a summary line, such as
+    /// `video:1024kB audio:256kB subtitle:0kB other streams:0kB global headers:0kB muxing overhead: 0.5%`.
+    /// Newer ffmpeg prints sizes in `KiB` instead of `kB`.
+    pub fn from_line(line: &str) -> Option<Self> {
+        static SUMMARY_EXTRACTOR: &'static str = r"video:(\d+)[kK]i?B audio:(\d+)[kK]i?B subtitle:(\d+)[kK]i?B other streams:(\d+)[kK]i?B .*muxing overhead: (\S+?)%?$";
+        static SUMMARY_REGEX: OnceLock<Regex> = OnceLock::new();
+
+        let summary_regex = SUMMARY_REGEX.get_or_init(|| Regex::new(SUMMARY_EXTRACTOR).unwrap());
+        let caps = summary_regex.captures(line)?;
+        let size = |i: usize| {
+            caps.get(i)
+                .and_then(|size| size.as_str().parse::<usize>().ok())
+        };
+
+        Some(Self {
+            video_kb: size(1)?,
+            audio_kb: size(2)?,
+            subtitle_kb: size(3)?,
+            other_kb: size(4)?,
+            muxing_overhead_percent: caps
+                .get(5)
+                .and_then(|overhead| overhead.as_str().parse().ok()),
+        })
     }
 }
 
+/// Reasons of stopping a task.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum StopReason {
+    #[default]
+    UserRequested,
+    BatchAborted,
+    Shutdown,
+    SizeExceeded,
+    Timeout,
+    Restarting,
+}
+
 /// Task message informing task situation.
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(tag = "state")]
 pub enum TaskMessage<'a> {
+    /// Task is created but held until resumed.
+    Held {
+        id: String,
+    },
     Running(&'a TaskRunningMessage),
-    Finished { id: String },
-    Errored { id: String, reason: String },
+    Stopped {
+        id: String,
+        reason: StopReason,
+    },
+    Finished {
+        id: String,
+        /// Final summary of each output, only captured if `final_stats` enabled.
+        final_stats: Vec<FinalStats>,
+    },
+    Errored {
+        id: String,
+        reason: String,
+    },
+    /// Task keeps going, but something suspicious found,
+    /// such as stream copy combined with filters or deprecated args.
+    Warning {
+        id: String,
+        warnings: Vec<String>,
+    },
 }
 
 impl<'a> TaskMessage<'a> {
+    pub fn held(id: String) -> Self {
+        Self::Held { id }
+    }
+
     pub fn running(msg: &'a TaskRunningMessage) -> Self {
         Self::Running(msg)
     }
 
-    pub fn finished(id: String) -> Self {
-        Self::Finished { id }
+    pub fn stopped(id: String, reason: StopReason) -> Self {
+        Self::Stopped { id, reason }
+    }
+
+    pub fn finished(id: String, final_stats: Vec<FinalStats>) -> Self {
+        Self::Finished { id, final_stats }
     }
 
     pub fn errored(id: String, reason: String) -> Self {
         Self::Errored { id, reason }
     }
+
+    pub fn warning(id: String, warnings: Vec<String>) -> Self {
+        Self::Warning { id, warnings }
+    }
+}
+
+/// Kinds of task lifecycle events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum TaskLifecycleKind {
+    #[serde(rename = "task.created")]
+    Created,
+    #[serde(rename = "task.started")]
+    Started,
+    #[serde(rename = "task.progress")]
+    Progress,
+    #[serde(rename = "task.paused")]
+    Paused,
+    #[serde(rename = "task.resumed")]
+    Resumed,
+    #[serde(rename = "task.finished")]
+    Finished,
+    #[serde(rename = "task.errored")]
+    Errored,
+    #[serde(rename = "task.removed")]
+    Removed,
+}
+
+/// Structured lifecycle event of a task, emitted on [`TASK_LIFECYCLE_EVENT`]
+/// along with messages on [`TASK_MESSAGE_EVENT`], for external listeners embedding app in a larger pipeline.
+///
+/// Every kind of event shares the same payload shape, fields not applying to a kind are `null`.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct TaskLifecycleEvent<'a> {
+    pub event: TaskLifecycleKind,
+    pub id: &'a str,
+    /// Unix timestamp in milliseconds when event emitted.
+    pub timestamp: u64,
+    /// Overall progress in `[0, 100]` of `task.progress`, `null` if progress is unknown.
+    pub percent: Option<f64>,
+    /// Reason of `task.errored`.
+    pub reason: Option<&'a str>,
+}
+
+impl<'a> TaskLifecycleEvent<'a> {
+    pub fn new(event: TaskLifecycleKind, id: &'a str) -> Self {
+        Self {
+            event,
+            id,
+            timestamp: unix_millis(SystemTime::now()),
+            percent: None,
+            reason: None,
+        }
+    }
+
+    pub fn progress(id: &'a str, percent: Option<f64>) -> Self {
+        Self {
+            percent,
+            ..Self::new(TaskLifecycleKind::Progress, id)
+        }
+    }
+
+    pub fn errored(id: &'a str, reason: &'a str) -> Self {
+        Self {
+            reason: Some(reason),
+            ..Self::new(TaskLifecycleKind::Errored, id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::tasks::progress::{Phase, ProgressType, UnspecifiedReason};
+
+    use super::{
+        FinalStats, TaskLifecycleEvent, TaskLifecycleKind, TaskRunningMessage, MAX_EXTRA_FIELDS,
+        MAX_RAW_LINES,
+    };
+
+    fn message(phase: Phase) -> TaskRunningMessage {
+        let progress_type = ProgressType::ByDuration {
+            duration: 10.0,
+            duration_source_input: None,
+        };
+        let mut message = TaskRunningMessage::new("task".to_string(), progress_type, phase);
+        // half of the phase, `out_time_ms` is in microseconds
+        message.output_time_ms = Some(5000000);
+        message.speed = Some(1.0);
+        message
+    }
+
+    #[test]
+    fn computes_percent_of_single_phase() {
+        let message = message(Phase::single());
+        assert_eq!(message.compute_percent(), Some(50.0));
+        assert_eq!(message.compute_eta_ms(), Some(5000));
+    }
+
+    #[test]
+    fn computes_percent_across_phases() {
+        let first = message(Phase { index: 0, total: 2 });
+        assert_eq!(first.compute_percent(), Some(25.0));
+        assert_eq!(first.compute_eta_ms(), Some(15000));
+
+        let second = message(Phase { index: 1, total: 2 });
+        assert_eq!(second.compute_percent(), Some(75.0));
+        assert_eq!(second.compute_eta_ms(), Some(5000));
+    }
+
+    #[test]
+    fn caps_extra_fields_but_updates_existing_ones() {
+        let mut message = message(Phase::single());
+        for i in 0..MAX_EXTRA_FIELDS + 8 {
+            message.insert_extra(&format!("key{i}"), "1");
+        }
+        assert_eq!(message.extra.len(), MAX_EXTRA_FIELDS);
+        assert!(!message
+            .extra
+            .contains_key(&format!("key{MAX_EXTRA_FIELDS}")));
+
+        message.insert_extra("key0", "2");
+        assert_eq!(message.extra["key0"], "2");
+        assert_eq!(message.extra.len(), MAX_EXTRA_FIELDS);
+    }
+
+    #[test]
+    fn parses_final_stats_lines() {
+        let stats = FinalStats::from_line(
+            "video:1024kB audio:256kB subtitle:0kB other streams:0kB global headers:0kB muxing overhead: 0.5%",
+        )
+        .unwrap();
+        assert_eq!(
+            (
+                stats.video_kb,
+                stats.audio_kb,
+                stats.subtitle_kb,
+                stats.other_kb
+            ),
+            (1024, 256, 0, 0)
+        );
+        assert_eq!(stats.muxing_overhead_percent, Some(0.5));
+
+        // newer ffmpeg prints `KiB` and unknown overhead
+        let stats = FinalStats::from_line(
+            "video:0KiB audio:128KiB subtitle:0KiB other streams:0KiB global headers:0KiB muxing overhead: unknown",
+        )
+        .unwrap();
+        assert_eq!(stats.audio_kb, 128);
+        assert_eq!(stats.muxing_overhead_percent, None);
+
+        assert!(FinalStats::from_line("frame=1 fps=0.0 q=0.0 size=0kB").is_none());
+    }
+
+    #[test]
+    fn computes_percent_by_file_size_or_both() {
+        let mut message = message(Phase::single());
+        message.total_size = Some(256);
+
+        message.progress_type = ProgressType::ByFileSize { size: 1024 };
+        assert_eq!(message.compute_percent(), Some(25.0));
+        assert_eq!(message.compute_eta_ms(), None);
+
+        // the larger one of duration and file size progress
+        message.progress_type = ProgressType::Auto {
+            duration: 10.0,
+            file_size: 1024,
+            duration_source_input: None,
+        };
+        assert_eq!(message.compute_percent(), Some(50.0));
+
+        message.progress_type = ProgressType::Unspecified {
+            reason: UnspecifiedReason::NoDurationOrSize,
+        };
+        assert_eq!(message.compute_percent(), None);
+    }
+
+    #[test]
+    fn clamps_percent_past_duration() {
+        let mut message = message(Phase::single());
+        message.output_time_ms = Some(12000000);
+        assert_eq!(message.compute_percent(), Some(100.0));
+        assert_eq!(message.compute_eta_ms(), Some(0));
+    }
+
+    #[test]
+    fn tells_unavailable_int_fields_apart_from_zero() {
+        let mut message = message(Phase::single());
+        let fields = [
+            "frame",
+            "total_size",
+            "out_time_ms",
+            "dup_frames",
+            "drop_frames",
+        ];
+        for field in fields {
+            assert_eq!(message.parse_int_field(field, "N/A"), None);
+            assert_eq!(message.parse_int_field(field, "0"), Some(0));
+        }
+        assert_eq!(message.unavailable.as_slice(), fields);
+        assert!(message.malformed.is_empty());
+
+        assert_eq!(message.parse_int_field("frame", "n/a"), None);
+        assert_eq!(message.parse_int_field("frame", "-1"), None);
+        assert_eq!(message.malformed.as_slice(), ["frame"]);
+
+        message.clear();
+        assert!(message.unavailable.is_empty());
+        assert!(message.malformed.is_empty());
+    }
+
+    #[test]
+    fn serializes_lifecycle_events_in_same_shape() {
+        let events = [
+            TaskLifecycleEvent::new(TaskLifecycleKind::Started, "task"),
+            TaskLifecycleEvent::progress("task", Some(50.0)),
+            TaskLifecycleEvent::errored("task", "ffmpeg exited"),
+        ];
+        let values = events
+            .iter()
+            .map(|event| serde_json::to_value(event).unwrap())
+            .collect::<Vec<_>>();
+
+        for (value, event) in values
+            .iter()
+            .zip(["task.started", "task.progress", "task.errored"])
+        {
+            assert_eq!(value["event"], event);
+            assert_eq!(value["id"], "task");
+            assert!(value["timestamp"].as_u64().unwrap() > 0);
+            let mut keys = value.as_object().unwrap().keys().collect::<Vec<_>>();
+            keys.sort();
+            assert_eq!(keys, ["event", "id", "percent", "reason", "timestamp"]);
+        }
+        assert_eq!(values[0]["percent"], serde_json::Value::Null);
+        assert_eq!(values[1]["percent"], 50.0);
+        assert_eq!(values[1]["reason"], serde_json::Value::Null);
+        assert_eq!(values[2]["reason"], "ffmpeg exited");
+    }
+
+    #[test]
+    fn keeps_newest_raw_lines_only() {
+        let mut message = message(Phase::single());
+        for index in 0..MAX_RAW_LINES + 10 {
+            message.push_raw(format!("line{index}"));
+            assert!(message.raw.len() <= MAX_RAW_LINES);
+        }
+        assert_eq!(message.raw.len(), MAX_RAW_LINES);
+        assert_eq!(message.raw[0], "line10");
+        assert_eq!(
+            message.raw[MAX_RAW_LINES - 1],
+            format!("line{}", MAX_RAW_LINES + 9)
+        );
+    }
 }
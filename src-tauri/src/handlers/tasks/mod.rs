@@ -1,5 +1,15 @@
-pub(self) mod task;
-pub(self) mod message;
-pub(self) mod state_machine;
-pub mod store;
+pub(self) mod cleanup;
+pub mod dry_run;
+#[cfg(all(unix, feature = "fd-output"))]
+pub mod fd_output;
+pub mod history;
+pub mod lifetime;
+pub mod loudnorm;
+pub mod message;
+pub mod priority;
 pub mod progress;
+pub(self) mod resource;
+pub mod state_machine;
+pub mod store;
+pub(self) mod task;
+pub mod validation;
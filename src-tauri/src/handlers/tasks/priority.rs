@@ -0,0 +1,123 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether background mode is enabled,
+/// ffmpeg processes run in low priority in background mode.
+static BACKGROUND_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Niceness of ffmpeg processes in background mode.
+#[cfg(unix)]
+const BACKGROUND_NICENESS: i32 = 10;
+
+/// Returns `true` if background mode is enabled.
+pub fn is_background_mode() -> bool {
+    BACKGROUND_MODE.load(Ordering::SeqCst)
+}
+
+/// Enables or disables background mode.
+/// Priorities of running processes are not changed, see [`apply_priority`].
+pub fn set_background_mode(enabled: bool) {
+    BACKGROUND_MODE.store(enabled, Ordering::SeqCst);
+}
+
+/// Applies low priority to a process if `background` is `true`, or normal priority otherwise.
+///
+/// On Unix, niceness is set by `setpriority`.
+/// On Linux, niceness is a per thread attribute, so every thread of the process is reniced,
+/// threads created later inherit niceness of the thread creating them.
+///
+/// Restoring normal priority may fail without privileges, since niceness could hardly be lowered.
+/// All threads are still attempted and the first error is returned.
+pub fn apply_priority(pid: u32, background: bool) -> Result<(), std::io::Error> {
+    #[cfg(unix)]
+    {
+        use nix::libc;
+
+        let niceness = if background { BACKGROUND_NICENESS } else { 0 };
+        let mut result = Ok(());
+        for id in thread_ids(pid) {
+            let applied =
+                unsafe { libc::setpriority(libc::PRIO_PROCESS, id as libc::id_t, niceness) };
+            if applied != 0 && result.is_ok() {
+                result = Err(std::io::Error::last_os_error());
+            }
+        }
+        result
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (pid, background);
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "process priority is only supported on unix",
+        ))
+    }
+}
+
+/// Returns ids of all threads of a process, listed from `/proc/<pid>/task`.
+/// Falls back to the process itself if threads are not listable, such as the process exited.
+#[cfg(target_os = "linux")]
+fn thread_ids(pid: u32) -> Vec<u32> {
+    let ids = std::fs::read_dir(format!("/proc/{pid}/task"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if ids.is_empty() {
+        vec![pid]
+    } else {
+        ids
+    }
+}
+
+/// Returns the process itself, since niceness applies to the whole process on other Unix.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn thread_ids(pid: u32) -> Vec<u32> {
+    vec![pid]
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::{process::Command, sync::mpsc};
+
+    use nix::libc;
+
+    use super::{apply_priority, thread_ids, BACKGROUND_NICENESS};
+
+    #[test]
+    fn lists_all_threads() {
+        let (sender, receiver) = mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || receiver.recv());
+
+        let ids = thread_ids(std::process::id());
+        assert!(ids.len() >= 2);
+        assert!(ids.contains(&std::process::id()));
+
+        drop(sender);
+        let _ = handle.join();
+    }
+
+    #[test]
+    fn falls_back_to_exited_process() {
+        let mut child = Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+
+        assert_eq!(thread_ids(pid), vec![pid]);
+    }
+
+    #[test]
+    fn applies_low_priority() {
+        let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+        let pid = child.id();
+
+        apply_priority(pid, true).unwrap();
+        let niceness = unsafe { libc::getpriority(libc::PRIO_PROCESS, pid as libc::id_t) };
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(niceness, BACKGROUND_NICENESS);
+    }
+}
@@ -1,29 +1,69 @@
-use std::{ffi::OsStr, sync::OnceLock};
+use std::{collections::HashMap, ffi::OsStr, sync::OnceLock};
 
+use log::warn;
 use ordered_float::OrderedFloat;
 use regex::Regex;
 use smallvec::SmallVec;
 
-use crate::{
-    handlers::{
-        commands::{
-            process::invoke_ffprobe,
-            task::{TaskInputArgs, TaskOutputArgs},
-        },
-        error::Error,
+use crate::handlers::{
+    commands::{
+        process::invoke_ffprobe_duration,
+        task::{TaskArgs, TaskInputArgs, TaskOutputArgs},
     },
-    with_default_args,
+    error::Error,
 };
 
-use super::task::Task;
+use super::validation::copied_streams;
 
 #[derive(Debug, Clone, Copy, serde::Serialize)]
 #[serde(tag = "type")]
 pub enum ProgressType {
-    ByDuration { duration: f64 },
-    ByFileSize { size: usize },
-    Auto { duration: f64, file_size: usize },
-    Unspecified,
+    ByDuration {
+        duration: f64,
+        /// Index of input providing the duration, `None` if the duration comes from output args.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        duration_source_input: Option<usize>,
+    },
+    ByFileSize {
+        size: usize,
+    },
+    Auto {
+        duration: f64,
+        file_size: usize,
+        /// Index of input providing the duration, `None` if the duration comes from output args.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        duration_source_input: Option<usize>,
+    },
+    Unspecified {
+        reason: UnspecifiedReason,
+    },
+}
+
+/// Reasons of a task progress being [`ProgressType::Unspecified`], told to frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum UnspecifiedReason {
+    /// Neither inputs nor outputs provide a duration or a file size,
+    /// such as live inputs or ffprobe reporting `N/A` without clipping args.
+    NoDurationOrSize,
+    /// Duration is not positive, such as clipping offsets exceeding durations of inputs.
+    NonPositiveDuration,
+}
+
+/// Phase of a task, for tasks running ffmpeg several times,
+/// such as normalizing loudness, see [`loudnorm`](super::loudnorm).
+/// Progress of a phase covers `1 / total` of the task progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Phase {
+    /// Zero based index of current phase.
+    pub index: usize,
+    pub total: usize,
+}
+
+impl Phase {
+    /// Phase of a task running ffmpeg only once.
+    pub fn single() -> Self {
+        Self { index: 0, total: 1 }
+    }
 }
 
 /// No FileSize for input progress source because
@@ -69,33 +109,45 @@ enum OutputProgressSource {
 ///     6. If both `input_duration` and `output_file_size` available, returns [`ProgressType::Auto`].
 ///     7. If both `output_duration` and `output_file_size` available, returns [`ProgressType::Auto`].
 ///     8. If all three fields available, returns [`ProgressType::Auto`] with the smallest duration and file size.
+///  - Reports index of input providing the duration, the first one if several inputs have the maximum duration.
+///  `output_duration` applying offset to input duration is provided by the input as well.
 ///
-pub async fn find_progress_type(task: &Task) -> Result<ProgressType, Error> {
-    let mut input_progress_sources = Vec::with_capacity(task.data.args.inputs.len());
-    let mut output_progress_sources = Vec::with_capacity(task.data.args.outputs.len());
-    for input in task.data.args.inputs.iter() {
-        let progress_type = find_input_progress_sources(&task.data.ffprobe_program, input).await?;
-        input_progress_sources.push(progress_type);
+pub async fn find_progress_type(ffprobe: &str, args: &TaskArgs) -> Result<ProgressType, Error> {
+    let mut input_progress_sources = Vec::with_capacity(args.inputs.len());
+    let mut output_progress_sources = Vec::with_capacity(args.outputs.len());
+    // probes each unique input path only once
+    let mut probed_durations: HashMap<&str, Option<f64>> =
+        HashMap::with_capacity(args.inputs.len());
+    for input in args.inputs.iter() {
+        if !probed_durations.contains_key(input.path.as_str()) {
+            let duration = invoke_ffprobe_duration(ffprobe, &input.path).await?;
+            probed_durations.insert(input.path.as_str(), duration);
+        }
     }
-    for output in task.data.args.outputs.iter() {
+    for input in args.inputs.iter() {
+        let duration = probed_durations.get(input.path.as_str()).copied().flatten();
+        input_progress_sources.push(find_input_progress_sources(input, duration));
+    }
+    for output in args.outputs.iter() {
         output_progress_sources.push(find_output_progress_sources(output));
     }
 
     let mut output_file_size: Option<usize> = None;
-    let mut output_duration: Option<f64> = None;
-    let input_duration =
-        input_progress_sources
-            .iter()
-            .fold(None as Option<f64>, |mut max, source| {
-                match source {
-                    InputProgressSource::Duration(d) => match max {
-                        Some(m) => max = Some(m.max(*d)),
-                        None => max = Some(*d),
-                    },
-                    InputProgressSource::Unspecified => {}
-                }
-                return max;
-            });
+    let mut output_duration: Option<(f64, Option<usize>)> = None;
+    // keeps maximum input duration along with index of the input
+    let input_duration = input_progress_sources.iter().enumerate().fold(
+        None as Option<(f64, Option<usize>)>,
+        |mut max, (index, source)| {
+            match source {
+                InputProgressSource::Duration(d) => match max {
+                    Some((m, _)) if m >= *d => {}
+                    _ => max = Some((*d, Some(index))),
+                },
+                InputProgressSource::Unspecified => {}
+            }
+            return max;
+        },
+    );
 
     let (durations, offsets, sizes) = output_progress_sources.iter().fold(
         (
@@ -117,60 +169,123 @@ pub async fn find_progress_type(task: &Task) -> Result<ProgressType, Error> {
     if sizes.len() != 0 && durations.len() == 0 && offsets.len() == 0 {
         output_file_size = Some(sizes.iter().sum());
     } else if sizes.len() == 0 && durations.len() != 0 && offsets.len() == 0 {
-        output_duration = Some(durations.iter().max().unwrap().0);
+        output_duration = Some((durations.iter().max().unwrap().0, None));
     } else if durations.len() == 0 && sizes.len() == 0 && offsets.len() != 0 {
-        if let Some(input_duration) = input_duration {
+        if let Some((input_duration, source)) = input_duration {
             let offset = offsets.iter().max().unwrap().0;
-            output_duration = Some(input_duration - offset);
+            output_duration = Some((input_duration - offset, source));
         }
     }
 
-    match (output_file_size, output_duration, input_duration) {
-        (None, None, None) => Ok(ProgressType::Unspecified),
-        (None, None, Some(duration)) | (None, Some(duration), None) => {
-            Ok(ProgressType::ByDuration { duration })
+    // takes the smaller duration along with its source
+    let min_duration = |a: (f64, Option<usize>), b: (f64, Option<usize>)| {
+        if b.0 < a.0 {
+            b
+        } else {
+            a
         }
-        (None, Some(output_duration), Some(input_duration)) => Ok(ProgressType::ByDuration {
-            duration: f64::min(output_duration, input_duration),
-        }),
-        (Some(size), None, None) => Ok(ProgressType::ByFileSize { size }),
-        (Some(size), None, Some(duration)) | (Some(size), Some(duration), None) => {
-            Ok(ProgressType::Auto {
+    };
+    let progress_type = match (output_file_size, output_duration, input_duration) {
+        (None, None, None) => ProgressType::Unspecified {
+            reason: UnspecifiedReason::NoDurationOrSize,
+        },
+        (None, None, Some((duration, source))) | (None, Some((duration, source)), None) => {
+            ProgressType::ByDuration {
                 duration,
-                file_size: size,
-            })
+                duration_source_input: source,
+            }
         }
-        (Some(size), Some(output_duration), Some(input_duration)) => Ok(ProgressType::Auto {
-            duration: f64::min(output_duration, input_duration),
+        (None, Some(output_duration), Some(input_duration)) => {
+            let (duration, source) = min_duration(output_duration, input_duration);
+            ProgressType::ByDuration {
+                duration,
+                duration_source_input: source,
+            }
+        }
+        (Some(size), None, None) => ProgressType::ByFileSize { size },
+        (Some(size), None, Some((duration, source)))
+        | (Some(size), Some((duration, source)), None) => ProgressType::Auto {
+            duration,
             file_size: size,
-        }),
+            duration_source_input: source,
+        },
+        (Some(size), Some(output_duration), Some(input_duration)) => {
+            let (duration, source) = min_duration(output_duration, input_duration);
+            ProgressType::Auto {
+                duration,
+                file_size: size,
+                duration_source_input: source,
+            }
+        }
+    };
+
+    Ok(sanitize_progress_type(progress_type))
+}
+
+/// Minimum duration in seconds a progress type could have.
+const MIN_DURATION: f64 = 0.001;
+
+/// Sanitizes duration of a progress type, avoiding negative or NaN progress ratios.
+///
+/// - Positive durations smaller than [`MIN_DURATION`] are clamped to [`MIN_DURATION`].
+/// - Non-positive or NaN durations are dropped, which happens when clipping
+/// offsets exceed input durations.
+/// [`ProgressType::ByDuration`] turns into [`ProgressType::Unspecified`]
+/// and [`ProgressType::Auto`] turns into [`ProgressType::ByFileSize`].
+fn sanitize_progress_type(progress_type: ProgressType) -> ProgressType {
+    match progress_type {
+        ProgressType::ByDuration {
+            duration,
+            duration_source_input,
+        } => {
+            if duration > 0.0 {
+                ProgressType::ByDuration {
+                    duration: duration.max(MIN_DURATION),
+                    duration_source_input,
+                }
+            } else {
+                warn!("progress type unspecified, duration {duration} is not positive");
+                ProgressType::Unspecified {
+                    reason: UnspecifiedReason::NonPositiveDuration,
+                }
+            }
+        }
+        ProgressType::Auto {
+            duration,
+            file_size,
+            duration_source_input,
+        } => {
+            if duration > 0.0 {
+                ProgressType::Auto {
+                    duration: duration.max(MIN_DURATION),
+                    file_size,
+                    duration_source_input,
+                }
+            } else {
+                warn!("progress type falls back to file size, duration {duration} is not positive");
+                ProgressType::ByFileSize { size: file_size }
+            }
+        }
+        ProgressType::ByFileSize { .. } | ProgressType::Unspecified { .. } => progress_type,
     }
 }
 
-/// Finds progress type from input arguments
-async fn find_input_progress_sources(
-    ffprobe: &str,
+/// Finds progress type from input arguments and probed duration of input.
+///
+/// Input without a probed duration, such as ffprobe reporting `N/A`, is always unspecified,
+/// progress then relies on output clipping args, such as `-t`, if any.
+fn find_input_progress_sources(
     input: &TaskInputArgs,
-) -> Result<InputProgressSource, Error> {
-    let raw = invoke_ffprobe(
-        ffprobe,
-        with_default_args!(
-            "-show_entries",
-            "format=duration",
-            "-of",
-            "csv=p=0",
-            &input.path
-        ),
-    )
-    .await?;
-    let Ok(duration) = String::from_utf8_lossy(&raw.stdout).trim().parse::<f64>() else {
-        return Ok(InputProgressSource::Unspecified);
+    duration: Option<f64>,
+) -> InputProgressSource {
+    let Some(duration) = duration else {
+        return InputProgressSource::Unspecified;
     };
 
     let (ss, sseof, to, t, fs) = find_progress_args(&input.args);
 
     if let Some(_) = fs {
-        Ok(InputProgressSource::Unspecified)
+        InputProgressSource::Unspecified
     } else {
         // applies clipping
         let duration = match (ss, sseof, to, t) {
@@ -231,7 +346,7 @@ async fn find_input_progress_sources(
             (Some(ss), Some(_), None, None) => duration - ss.min(duration),
         };
 
-        Ok(InputProgressSource::Duration(duration))
+        InputProgressSource::Duration(duration)
     }
 }
 
@@ -254,6 +369,47 @@ fn find_output_progress_sources(output: &TaskOutputArgs) -> OutputProgressSource
     }
 }
 
+/// Output `-ss` positions in seconds beyond which seeking is regarded as slow,
+/// since ffmpeg decodes and discards everything before the position.
+pub const SLOW_OUTPUT_SEEK_SECS: f64 = 30.0;
+
+/// Finds advisory hints of `-ss` placements, never failing a task.
+///
+/// - Output `-ss` beyond [`SLOW_OUTPUT_SEEK_SECS`] decodes from start, input side seeking is much faster.
+/// - Input `-ss` combined with stream copy seeks to the keyframe before the position,
+/// output side seeking cuts more accurately.
+pub fn find_seek_hints(args: &TaskArgs) -> Vec<String> {
+    let mut hints = Vec::new();
+
+    let copies = args.outputs.iter().any(|output| {
+        let (video, audio) = copied_streams(output);
+        video || audio
+    });
+    for input in args.inputs.iter() {
+        if let (Some(ss), _, _, _, _) = find_progress_args(&input.args) {
+            if copies {
+                hints.push(format!(
+                    "-ss {ss} before -i {} seeks to the keyframe before position when stream copying, put -ss after -i for an accurate cut",
+                    input.path
+                ));
+            }
+        }
+    }
+
+    for output in args.outputs.iter() {
+        if let (Some(ss), _, _, _, _) = find_progress_args(&output.args) {
+            if ss > SLOW_OUTPUT_SEEK_SECS {
+                hints.push(format!(
+                    "-ss {ss} after -i decodes from start of input for output {}, put -ss before -i for fast seeking",
+                    output.path.as_deref().unwrap_or("-")
+                ));
+            }
+        }
+    }
+
+    hints
+}
+
 /// Finds arguments that used for progressing, in (-ss, -sseof, -to, -t, -fs) order.
 fn find_progress_args<I, S>(
     args: I,
@@ -410,3 +566,188 @@ fn extract_duration<S: AsRef<OsStr>>(value: S) -> Option<f64> {
 
     duration
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::commands::task::{TaskArgs, TaskInputArgs, TaskOutputArgs};
+    #[cfg(unix)]
+    use crate::handlers::test_support::fake_ffprobe;
+
+    #[cfg(unix)]
+    use super::find_progress_type;
+    use super::{
+        find_seek_hints, sanitize_progress_type, ProgressType, UnspecifiedReason, MIN_DURATION,
+    };
+
+    fn by_duration(duration: f64) -> ProgressType {
+        ProgressType::ByDuration {
+            duration,
+            duration_source_input: Some(0),
+        }
+    }
+
+    #[test]
+    fn clamps_tiny_durations() {
+        let ProgressType::ByDuration { duration, .. } = sanitize_progress_type(by_duration(1e-9))
+        else {
+            panic!("expected progress by duration");
+        };
+        assert_eq!(duration, MIN_DURATION);
+    }
+
+    #[test]
+    fn reports_offset_exceeding_duration() {
+        // `-ss 120` on a 60 seconds input
+        for duration in [60.0 - 120.0, 0.0, f64::NAN] {
+            assert!(matches!(
+                sanitize_progress_type(by_duration(duration)),
+                ProgressType::Unspecified {
+                    reason: UnspecifiedReason::NonPositiveDuration
+                }
+            ));
+        }
+    }
+
+    #[test]
+    fn falls_back_to_file_size() {
+        let progress_type = ProgressType::Auto {
+            duration: -1.0,
+            file_size: 1024,
+            duration_source_input: None,
+        };
+        assert!(matches!(
+            sanitize_progress_type(progress_type),
+            ProgressType::ByFileSize { size: 1024 }
+        ));
+    }
+
+    #[test]
+    fn serializes_unspecified_reason() {
+        let progress_type = ProgressType::Unspecified {
+            reason: UnspecifiedReason::NoDurationOrSize,
+        };
+        assert_eq!(
+            serde_json::to_string(&progress_type).unwrap(),
+            r#"{"type":"Unspecified","reason":"NoDurationOrSize"}"#
+        );
+    }
+
+    /// Creates a fake ffprobe printing `duration` of each input by its file name from `durations`.
+    #[cfg(unix)]
+    fn fake_durations_ffprobe(durations: &[(&str, &str)]) -> (std::path::PathBuf, String) {
+        let outputs = durations
+            .iter()
+            .map(|(name, duration)| {
+                (
+                    *name,
+                    serde_json::json!({ "format": { "duration": duration } }),
+                )
+            })
+            .collect::<Vec<_>>();
+        fake_ffprobe(&outputs)
+    }
+
+    #[cfg(unix)]
+    fn inputs_args(inputs: &[(&str, &[&str])]) -> TaskArgs {
+        TaskArgs {
+            inputs: inputs
+                .iter()
+                .map(|(path, input_args)| TaskInputArgs {
+                    path: path.to_string(),
+                    args: input_args.iter().map(|arg| arg.to_string()).collect(),
+                })
+                .collect(),
+            outputs: vec![TaskOutputArgs::default()],
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn probes_each_unique_input_once() {
+        let (dir, ffprobe) = fake_durations_ffprobe(&[("a.mp4", "10.0"), ("b.mp4", "20.0")]);
+        let args = inputs_args(&[("a.mp4", &[]), ("b.mp4", &[]), ("a.mp4", &["-t", "5"])]);
+
+        let progress_type = find_progress_type(&ffprobe, &args).await.unwrap();
+        assert!(matches!(
+            progress_type,
+            ProgressType::ByDuration { duration, .. } if duration == 20.0
+        ));
+        let calls = std::fs::read_to_string(dir.join("calls")).unwrap();
+        assert_eq!(calls.lines().collect::<Vec<_>>(), vec!["a.mp4", "b.mp4"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn seek_args(input_args: &[&str], output_args: &[&str]) -> TaskArgs {
+        let owned = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect();
+        TaskArgs {
+            inputs: vec![TaskInputArgs {
+                path: "in.mkv".to_string(),
+                args: owned(input_args),
+            }],
+            outputs: vec![TaskOutputArgs {
+                path: Some("out.mkv".to_string()),
+                args: owned(output_args),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hints_input_seek_when_copying() {
+        let hints = find_seek_hints(&seek_args(&["-ss", "10"], &["-c", "copy"]));
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].contains("-i in.mkv"), "{}", hints[0]);
+
+        assert!(find_seek_hints(&seek_args(&["-ss", "10"], &["-c:v", "libx264"])).is_empty());
+        assert!(find_seek_hints(&seek_args(&[], &["-c", "copy"])).is_empty());
+    }
+
+    #[test]
+    fn hints_slow_output_seek() {
+        let hints = find_seek_hints(&seek_args(&[], &["-ss", "60"]));
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].contains("output out.mkv"), "{}", hints[0]);
+
+        assert!(find_seek_hints(&seek_args(&[], &["-ss", "10"])).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reports_input_providing_duration() {
+        let (dir, ffprobe) = fake_durations_ffprobe(&[("a.mp4", "10.0"), ("b.mp4", "20.0")]);
+        let mut args = inputs_args(&[("a.mp4", &[]), ("b.mp4", &[])]);
+
+        let progress_type = find_progress_type(&ffprobe, &args).await.unwrap();
+        assert!(matches!(
+            progress_type,
+            ProgressType::ByDuration {
+                duration_source_input: Some(1),
+                ..
+            }
+        ));
+        assert_eq!(
+            serde_json::to_value(&progress_type).unwrap()["duration_source_input"],
+            1
+        );
+
+        // a shorter duration of output args is taken instead
+        args.outputs[0].args = vec!["-t".to_string(), "5".to_string()];
+        let progress_type = find_progress_type(&ffprobe, &args).await.unwrap();
+        assert!(matches!(
+            progress_type,
+            ProgressType::ByDuration {
+                duration_source_input: None,
+                ..
+            }
+        ));
+        assert!(serde_json::to_value(&progress_type)
+            .unwrap()
+            .get("duration_source_input")
+            .is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,148 @@
+use std::time::{Duration, Instant};
+
+/// Minimum interval between two resource samplings.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Resource usage of a process.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResourceUsage {
+    pub cpu_percent: Option<f64>,
+    pub rss_bytes: Option<usize>,
+}
+
+/// Samples CPU and resident memory usage of a child process periodically.
+///
+/// Only Linux is supported currently, by reading `/proc`.
+/// Usage is always `None` on other platforms.
+pub struct ResourceSampler {
+    pid: Option<u32>,
+    /// Last sampled cpu time and sampling time.
+    last_cpu_time: Option<(Duration, Instant)>,
+    last_sampled: Option<Instant>,
+    usage: ResourceUsage,
+}
+
+impl ResourceSampler {
+    pub fn new(pid: Option<u32>) -> Self {
+        Self {
+            pid,
+            last_cpu_time: None,
+            last_sampled: None,
+            usage: ResourceUsage::default(),
+        }
+    }
+
+    /// Returns resource usage, samples again if [`SAMPLE_INTERVAL`] elapsed since last sampling.
+    /// Usage turns into `None` if process exited between samplings.
+    pub fn sample(&mut self) -> ResourceUsage {
+        let Some(pid) = self.pid else {
+            return self.usage;
+        };
+
+        let now = Instant::now();
+        if let Some(last_sampled) = self.last_sampled {
+            if now.duration_since(last_sampled) < SAMPLE_INTERVAL {
+                return self.usage;
+            }
+        }
+        self.last_sampled = Some(now);
+
+        let cpu_time = cpu_time(pid);
+        self.usage.cpu_percent = match (cpu_time, self.last_cpu_time) {
+            (Some(cpu_time), Some((last_cpu_time, last_instant))) => {
+                let elapsed = now.duration_since(last_instant).as_secs_f64();
+                if elapsed > 0.0 {
+                    Some(cpu_time.saturating_sub(last_cpu_time).as_secs_f64() / elapsed * 100.0)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        self.last_cpu_time = cpu_time.map(|cpu_time| (cpu_time, now));
+        self.usage.rss_bytes = rss_bytes(pid);
+
+        self.usage
+    }
+}
+
+/// Fallback clock ticks per second of `/proc/<pid>/stat`, which is 100 on almost all Linux systems.
+#[cfg(target_os = "linux")]
+const DEFAULT_USER_HZ: f64 = 100.0;
+
+/// Gets clock ticks per second of `/proc/<pid>/stat` from `sysconf(_SC_CLK_TCK)`,
+/// falls back to [`DEFAULT_USER_HZ`] if unavailable.
+#[cfg(target_os = "linux")]
+fn user_hz() -> f64 {
+    use std::sync::OnceLock;
+
+    use nix::libc;
+
+    static USER_HZ: OnceLock<f64> = OnceLock::new();
+    *USER_HZ.get_or_init(|| {
+        // safely calling, sysconf touches no memory
+        let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        if ticks > 0 {
+            ticks as f64
+        } else {
+            DEFAULT_USER_HZ
+        }
+    })
+}
+
+/// Gets total cpu time (user and system) consumed by a process.
+#[cfg(target_os = "linux")]
+fn cpu_time(pid: u32) -> Option<Duration> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // process name may contain spaces, fields are counted after the closing parenthesis.
+    // utime and stime are the 14th and 15th fields, which are the 12th and 13th after name.
+    let (_, fields) = stat.rsplit_once(')')?;
+    let mut fields = fields.split_whitespace().skip(11);
+    let utime = fields.next()?.parse::<u64>().ok()?;
+    let stime = fields.next()?.parse::<u64>().ok()?;
+
+    Some(Duration::from_secs_f64((utime + stime) as f64 / user_hz()))
+}
+
+/// Gets resident memory size in bytes of a process.
+#[cfg(target_os = "linux")]
+fn rss_bytes(pid: u32) -> Option<usize> {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<usize>()
+        .ok()?;
+
+    Some(kb * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cpu_time(_pid: u32) -> Option<Duration> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rss_bytes(_pid: u32) -> Option<usize> {
+    None
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::{cpu_time, rss_bytes, user_hz};
+
+    #[test]
+    fn gets_clock_ticks_from_sysconf() {
+        let expected = unsafe { nix::libc::sysconf(nix::libc::_SC_CLK_TCK) };
+        assert_eq!(user_hz(), expected as f64);
+    }
+
+    #[test]
+    fn samples_current_process() {
+        assert!(cpu_time(std::process::id()).is_some());
+        assert!(rss_bytes(std::process::id()).unwrap() > 0);
+    }
+}
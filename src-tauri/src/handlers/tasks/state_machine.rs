@@ -1,11 +1,25 @@
-use std::{path::PathBuf, process::Stdio, sync::Arc, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    net::Ipv4Addr,
+    path::PathBuf,
+    pin::Pin,
+    process::{ExitStatus, Stdio},
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
 
 use async_trait::async_trait;
-use log::{info, trace, warn};
+use log::{error, info, trace, warn};
 use tauri::Manager;
 use tokio::{
     fs,
-    io::{AsyncBufReadExt, BufReader},
+    io::{
+        AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader,
+        BufWriter, ReadBuf,
+    },
+    net::{TcpListener, TcpStream},
     process::{Child, ChildStderr, ChildStdout},
     sync::Mutex,
     task::JoinHandle,
@@ -13,18 +27,34 @@ use tokio::{
 use tokio_util::sync::CancellationToken;
 
 use crate::handlers::{
-    commands::process::create_process,
+    commands::{
+        process::{create_process, quote_command, Shell},
+        task::TaskArgs,
+    },
     error::Error,
     tasks::{
-        message::{TaskMessage, TaskRunningMessage, TASK_MESSAGE_EVENT},
-        progress::{find_progress_type, ProgressType},
+        dry_run::{is_dry_run, DryRunning},
+        lifetime::{bind_child_to_app, bind_command_to_app},
+        loudnorm::{phased_args, Measuring, APPLY_PHASE},
+        message::{
+            FinalStats, TaskLifecycleEvent, TaskMessage, TaskRunningMessage, TASK_MESSAGE_EVENT,
+        },
+        priority::{apply_priority, is_background_mode},
+        progress::{find_progress_type, Phase, ProgressType},
+        resource::ResourceSampler,
+        validation::{find_empty_output, has_protocol_scheme, reads_stdin},
     },
 };
 
 use super::task::Task;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum TaskStateCode {
+    /// Created but waiting for a slot to start.
+    Queued,
+    /// Created but held until started or resumed explicitly.
+    Held,
+    /// About to run, probing inputs and spawning ffmpeg.
     Idle,
     Running,
     Pausing,
@@ -39,6 +69,26 @@ pub trait TaskState: Send {
 
     fn message(&self) -> Option<&str>;
 
+    /// Returns `true` if state owns a watchdog which has exited already.
+    fn watchdog_exited(&self) -> bool {
+        false
+    }
+
+    /// Returns pid of ffmpeg process if state owns a process.
+    fn pid(&self) -> Option<u32> {
+        None
+    }
+
+    /// Returns exit status of ffmpeg process if state owns a process which has exited already.
+    fn exit_status(&self) -> Option<ExitStatus> {
+        None
+    }
+
+    /// Returns `true` if state simulates a task without ffmpeg, see [`DryRunning`].
+    fn is_simulated(&self) -> bool {
+        false
+    }
+
     async fn start(self: Box<Self>, task: Task) -> Box<dyn TaskState>;
 
     async fn pause(self: Box<Self>, task: Task) -> Box<dyn TaskState>;
@@ -50,6 +100,139 @@ pub trait TaskState: Send {
     async fn finish(self: Box<Self>, task: Task) -> Box<dyn TaskState>;
 
     async fn error(self: Box<Self>, task: Task, reason: String) -> Box<dyn TaskState>;
+
+    /// Moves an ended task, which is stopped, finished or errored, back to held state,
+    /// so that it could be started again with the same args.
+    async fn reset(self: Box<Self>, task: Task) -> Box<dyn TaskState>;
+}
+
+pub struct Queued;
+
+#[async_trait]
+impl TaskState for Queued {
+    fn code(&self) -> TaskStateCode {
+        TaskStateCode::Queued
+    }
+
+    fn message(&self) -> Option<&str> {
+        None
+    }
+
+    async fn start(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        Box::new(Idle).start(task).await
+    }
+
+    async fn pause(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to pause a queued task", task.data.id);
+        self
+    }
+
+    async fn resume(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to resume a queued task", task.data.id);
+        self
+    }
+
+    async fn stop(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+        Box::new(Stopped)
+    }
+
+    async fn finish(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to finish a queued task", task.data.id);
+        self
+    }
+
+    async fn error(self: Box<Self>, _task: Task, reason: String) -> Box<dyn TaskState> {
+        Box::new(Errored::from_string(reason))
+    }
+
+    async fn reset(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to reset a queued task", task.data.id);
+        self
+    }
+}
+
+/// Task created by `start_paused`, never starts until started or resumed explicitly.
+pub struct Held;
+
+#[async_trait]
+impl TaskState for Held {
+    fn code(&self) -> TaskStateCode {
+        TaskStateCode::Held
+    }
+
+    fn message(&self) -> Option<&str> {
+        None
+    }
+
+    /// Queues a held task, it is started by scheduler then, see [`Task::resume`].
+    async fn start(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+        Box::new(Queued)
+    }
+
+    async fn pause(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to pause a held task", task.data.id);
+        self
+    }
+
+    /// Queues a held task, it is started by scheduler then, see [`Task::resume`].
+    async fn resume(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+        Box::new(Queued)
+    }
+
+    async fn stop(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+        Box::new(Stopped)
+    }
+
+    async fn finish(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to finish a held task", task.data.id);
+        self
+    }
+
+    async fn error(self: Box<Self>, _task: Task, reason: String) -> Box<dyn TaskState> {
+        Box::new(Errored::from_string(reason))
+    }
+
+    async fn reset(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to reset a held task", task.data.id);
+        self
+    }
+}
+
+/// Kills ffmpeg process and waits for it exiting in at most `kill_timeout_ms` of task args.
+///
+/// A process never exiting in time, such as an uninterruptible one stuck on a network file system,
+/// is left behind with an error logged as a last resort, rather than blocking stopping forever.
+pub(super) async fn kill_process(process: &mut Child, task: &Task) -> Result<(), std::io::Error> {
+    process.start_kill()?;
+    wait_killed(process.wait(), &task.data.id, task.data.args.kill_timeout()).await
+}
+
+/// Waits for a killed process exiting by `wait` in at most `timeout`,
+/// a process not exited in time is left behind with an error logged.
+async fn wait_killed<F>(wait: F, id: &str, timeout: Duration) -> Result<(), std::io::Error>
+where
+    F: std::future::Future<Output = Result<ExitStatus, std::io::Error>>,
+{
+    match tokio::time::timeout(timeout, wait).await {
+        Ok(status) => status.map(|_| ()),
+        Err(_) => {
+            error!(
+                "[{}] ffmpeg not exited in {}ms after killed, left behind",
+                id,
+                timeout.as_millis()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Converts an error spawning ffmpeg process into a task error.
+pub(super) fn spawn_error(ffmpeg: &str, err: std::io::Error) -> Error {
+    match err.kind() {
+        std::io::ErrorKind::NotFound => Error::ffmpeg_not_found(ffmpeg),
+        std::io::ErrorKind::PermissionDenied => Error::binary_not_executable(ffmpeg),
+        _ => Error::ffmpeg_unavailable_with_raw_error(ffmpeg, err),
+    }
 }
 
 pub struct Idle;
@@ -75,8 +258,77 @@ impl Idle {
 
         Ok(())
     }
+
+    /// Binds a listening socket on localhost for ffmpeg sending progress to, if `progress_over_tcp` enabled.
+    async fn bind_progress_listener(task: &Task) -> Result<Option<TcpListener>, std::io::Error> {
+        if !task.data.args.progress_over_tcp {
+            return Ok(None);
+        }
+
+        TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.map(Some)
+    }
+
+    /// Accepts the connection from ffmpeg sending progress.
+    /// Listener is dropped once accepted, so no other connection is accepted ever.
+    ///
+    /// Errors printed to stderr are reported if ffmpeg exits before connecting, such as an invalid option.
+    /// Otherwise, process is killed and reaped if accepting fails.
+    async fn accept_progress_socket(
+        listener: TcpListener,
+        process: &mut Child,
+        task: &Task,
+    ) -> Result<TcpStream, Error> {
+        let accepted = tokio::select! {
+            accepted = tokio::time::timeout(PROGRESS_ACCEPT_TIMEOUT, listener.accept()) => Some(accepted),
+            _ = process.wait() => None,
+        };
+
+        let err = match accepted {
+            Some(Ok(Ok((socket, _)))) => return Ok(socket),
+            Some(Ok(Err(err))) => Error::internal(err),
+            Some(Err(err)) => Error::internal(err),
+            None => {
+                let mut stderr = String::new();
+                if let Some(mut reader) = process.stderr.take() {
+                    let read = reader.read_to_string(&mut stderr);
+                    let _ = tokio::time::timeout(EXITED_CAPTURE_TIMEOUT, read).await;
+                }
+                return Err(match exited_reason(&stderr) {
+                    Some(reason) => Error::ffmpeg_runtime_error(reason),
+                    None => Error::ffmpeg_unexpected_killed(),
+                });
+            }
+        };
+
+        if let Err(err) = kill_process(process, task).await {
+            warn!("[{}] failed to kill ffmpeg: {}", task.data.id, err);
+        }
+        Err(err)
+    }
+}
+
+/// Extracts reason of an ffmpeg exiting before starting from all of its stderr,
+/// which are the last lines not logged as warning or below.
+fn exited_reason(stderr: &str) -> Option<String> {
+    let lines = stderr
+        .lines()
+        .filter_map(|line| match split_log_level(line.trim()) {
+            (Some("warning") | Some("info") | Some("verbose") | Some("debug"), _) => None,
+            (_, line) if line.is_empty() => None,
+            (_, line) => Some(line),
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return None;
+    }
+
+    let skip = lines.len().saturating_sub(MAX_ERROR_CONTEXT_LINES + 1);
+    Some(lines[skip..].join("\n"))
 }
 
+/// Maximum duration waiting for ffmpeg connecting to progress listener.
+const PROGRESS_ACCEPT_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[async_trait]
 impl TaskState for Idle {
     fn code(&self) -> TaskStateCode {
@@ -88,63 +340,130 @@ impl TaskState for Idle {
     }
 
     async fn start(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
-        // find maximum duration from all inputs
-        let progress_type = match find_progress_type(&task).await {
-            Ok(total_duration) => total_duration,
-            Err(err) => return Box::new(Errored::from_err(err)),
+        if is_dry_run() {
+            return Box::new(DryRunning::start(task));
+        }
+
+        // warnings of previous runs, such as before retrying, are never sent
+        task.data.pending_warnings.lock().await.clear();
+
+        // loudness normalizing task measures loudness first, as another phase
+        let (args, phase) = match task.data.args.loudnorm_target {
+            None => (Cow::Borrowed(&task.data.args), Phase::single()),
+            Some(target_i) => match task.data.loudnorm_filter.get() {
+                None => return Box::new(Measuring::start(task, target_i)),
+                Some(apply_filter) => (
+                    Cow::Owned(phased_args(&task.data.args, apply_filter)),
+                    APPLY_PHASE,
+                ),
+            },
         };
 
+        // find maximum duration from all inputs
+        let progress_type =
+            match find_progress_type(&task.data.ffprobe_program, &task.data.args).await {
+                Ok(total_duration) => total_duration,
+                Err(err) => return Box::new(Errored::from_err(err)),
+            };
+
         // create directories if not exist
         if let Err(err) = Idle::mkdirs(&task).await {
             return Box::new(Errored::from_err(err));
         };
 
+        // binds progress listener before ffmpeg starting
+        let progress_listener = match Idle::bind_progress_listener(&task).await {
+            Ok(listener) => listener,
+            Err(err) => return Box::new(Errored::from_err(err)),
+        };
+        let progress_url = match progress_listener.as_ref().map(|l| l.local_addr()) {
+            Some(Ok(addr)) => format!("tcp://{addr}"),
+            Some(Err(err)) => return Box::new(Errored::from_err(err)),
+            None => "-".to_string(),
+        };
+
         // startup ffmpeg subprocess
-        let args = task.data.args.to_cli_args();
+        let args = args.to_cli_args_with_progress(&progress_url);
         let mut command = create_process(&task.data.ffmpeg_program, &args);
+        let stdout = if progress_listener.is_some() {
+            Stdio::null()
+        } else {
+            Stdio::piped()
+        };
+        // stdin is only piped for pausing by keystrokes on Windows, signals are used on Unix.
+        // Tasks reading from stdin inherit it from app instead, never waiting on an idle pipe.
+        let reads_stdin = reads_stdin(&task.data.args);
+        let stdin = if reads_stdin {
+            Stdio::inherit()
+        } else if cfg!(windows) {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        };
+        #[cfg(all(unix, feature = "fd-output"))]
+        crate::handlers::tasks::fd_output::inherit_output_fds(&mut command, &task.data.args);
+        bind_command_to_app(&mut command, reads_stdin);
         let process = command
-            .stdin(Stdio::piped())
+            .stdin(stdin)
             .stderr(Stdio::piped())
-            .stdout(Stdio::piped())
+            .stdout(stdout)
             .spawn()
-            .map_err(|err| match err.kind() {
-                std::io::ErrorKind::NotFound => Error::ffmpeg_not_found(&task.data.ffmpeg_program),
-                _ => Error::ffmpeg_unavailable_with_raw_error(&task.data.ffmpeg_program, err),
-            });
-        let process = match process {
-            Ok(process) => Arc::new(Mutex::new(process)),
+            .map_err(|err| spawn_error(&task.data.ffmpeg_program, err));
+        let mut process = match process {
+            Ok(process) => process,
             Err(err) => {
                 return Box::new(Errored::from_err(err));
             }
         };
 
+        if let Err(err) = bind_child_to_app(&process) {
+            warn!("[{}] failed to bind process to app: {}", task.data.id, err);
+        }
+
+        let pid = process.id();
+        if let (Some(pid), true) = (pid, is_background_mode()) {
+            if let Err(err) = apply_priority(pid, true) {
+                warn!("[{}] failed to apply low priority: {}", task.data.id, err);
+            }
+        }
+
+        let progress_source = match progress_listener {
+            Some(listener) => {
+                match Idle::accept_progress_socket(listener, &mut process, &task).await {
+                    Ok(socket) => Some(ProgressLines::new(ProgressReader::Tcp(socket))),
+                    Err(err) => return Box::new(Errored::from_err(err)),
+                }
+            }
+            None => None,
+        };
+        let process = Arc::new(Mutex::new(process));
+        let progress_source = Arc::new(Mutex::new(progress_source));
+
         let watchdog_cancellations = (CancellationToken::new(), CancellationToken::new());
         let watchdog_handle = start_watchdog(
             Arc::clone(&process),
+            Arc::clone(&progress_source),
             watchdog_cancellations.clone(),
             task.clone(),
             progress_type,
+            phase,
+            false,
         );
 
         let next_state = Box::new(Running {
             progress_type,
+            phase,
             process,
+            pid,
+            progress_source,
             watchdog_cancellations,
             watchdog_handle,
         });
 
         info!(
-            "[{}] start task with command: {} {}",
+            "[{}] start task with command: {}",
             task.data.id,
-            task.data.ffmpeg_program,
-            args.iter()
-                .map(|arg| if arg.contains(" ") {
-                    format!("\"{arg}\"")
-                } else {
-                    arg.to_string()
-                })
-                .collect::<Vec<_>>()
-                .join(" ")
+            quote_command(&task.data.ffmpeg_program, &args, Shell::native())
         );
 
         next_state
@@ -172,11 +491,26 @@ impl TaskState for Idle {
     async fn error(self: Box<Self>, _task: Task, reason: String) -> Box<dyn TaskState> {
         Box::new(Errored::from_string(reason))
     }
+
+    async fn reset(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to reset a not start task", task.data.id);
+        self
+    }
 }
 
+/// Progress lines from ffmpeg, read from a TCP connection if `progress_over_tcp` enabled,
+/// or from stdout otherwise. Taken out by watchdog when capturing and put back when pausing,
+/// so that a partial line read before pausing is kept.
+/// Stays `None` until the first capturing if progress is read from stdout.
+type ProgressSource = Arc<Mutex<Option<ProgressLines<ProgressReader>>>>;
+
 pub struct Running {
     progress_type: ProgressType,
+    phase: Phase,
     process: Arc<Mutex<Child>>,
+    /// Pid of process, kept out of mutex since watchdog keeps process locked.
+    pid: Option<u32>,
+    progress_source: ProgressSource,
     watchdog_cancellations: (CancellationToken, CancellationToken),
     watchdog_handle: JoinHandle<()>,
 }
@@ -191,12 +525,35 @@ impl TaskState for Running {
         None
     }
 
+    fn watchdog_exited(&self) -> bool {
+        self.watchdog_handle.is_finished()
+    }
+
+    fn exit_status(&self) -> Option<ExitStatus> {
+        // process is kept locked by watchdog until it exits
+        self.process.try_lock().ok()?.try_wait().ok()?
+    }
+
+    fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
     async fn start(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
         warn!("[{}] attempting to start a running task", task.data.id);
         self
     }
 
     async fn pause(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        // keystrokes would be mixed into input of tasks reading from stdin
+        #[cfg(windows)]
+        if reads_stdin(&task.data.args) {
+            warn!(
+                "[{}] pausing a task reading from stdin is unsupported on Windows",
+                task.data.id
+            );
+            return self;
+        }
+
         self.watchdog_cancellations.0.cancel();
         self.watchdog_cancellations.1.cancel();
         if let Err(err) = self.watchdog_handle.await {
@@ -206,7 +563,6 @@ impl TaskState for Running {
         let process = self.process;
         #[cfg(windows)]
         {
-            use tokio::io::AsyncWriteExt;
             if let Err(err) = process
                 .lock()
                 .await
@@ -246,7 +602,10 @@ impl TaskState for Running {
 
         Box::new(Pausing {
             progress_type: self.progress_type,
+            phase: self.phase,
             process,
+            pid: self.pid,
+            progress_source: self.progress_source,
         })
     }
 
@@ -255,7 +614,7 @@ impl TaskState for Running {
         self
     }
 
-    async fn stop(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+    async fn stop(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
         self.watchdog_cancellations.0.cancel();
         self.watchdog_cancellations.1.cancel();
         if let Err(err) = self.watchdog_handle.await {
@@ -263,11 +622,7 @@ impl TaskState for Running {
         }
 
         let mut process = self.process.lock().await;
-        let kill = async {
-            process.start_kill()?;
-            process.wait().await
-        };
-        if let Err(err) = kill.await {
+        if let Err(err) = kill_process(&mut process, &task).await {
             return Box::new(Errored::from_err(err));
         };
         // MUST drop here, or watchdog_handle can NEVER get mutex lock of process
@@ -276,11 +631,13 @@ impl TaskState for Running {
         Box::new(Stopped)
     }
 
-    async fn finish(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+    async fn finish(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
         self.watchdog_cancellations.0.cancel();
         self.watchdog_cancellations.1.cancel();
         if let Err(err) = self.watchdog_handle.await {
             Box::new(Errored::from_err(err))
+        } else if let Some(path) = find_empty_output(&task.data.args) {
+            Box::new(Errored::from_err(Error::empty_output(path)))
         } else {
             Box::new(Finished)
         }
@@ -294,11 +651,19 @@ impl TaskState for Running {
             stopped
         }
     }
+
+    async fn reset(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to reset a running task", task.data.id);
+        self
+    }
 }
 
 pub struct Pausing {
     progress_type: ProgressType,
+    phase: Phase,
     process: Arc<Mutex<Child>>,
+    pid: Option<u32>,
+    progress_source: ProgressSource,
 }
 
 #[async_trait]
@@ -311,6 +676,10 @@ impl TaskState for Pausing {
         None
     }
 
+    fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
     async fn start(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
         warn!("[{}] attempting to start a pausing task", task.data.id);
         self
@@ -326,7 +695,6 @@ impl TaskState for Pausing {
 
         #[cfg(windows)]
         {
-            use tokio::io::AsyncWriteExt;
             if let Err(err) = process
                 .lock()
                 .await
@@ -362,31 +730,49 @@ impl TaskState for Pausing {
             }
         }
 
+        // recomputes progress type if inputs might be changed during pausing
+        let progress_type = if task.data.args.reprobe_on_resume {
+            match find_progress_type(&task.data.ffprobe_program, &task.data.args).await {
+                Ok(progress_type) => progress_type,
+                Err(err) => {
+                    warn!(
+                        "[{}] failed to recompute progress type, keeps the previous one: {}",
+                        task.data.id, err
+                    );
+                    self.progress_type
+                }
+            }
+        } else {
+            self.progress_type
+        };
+
         let watchdog_cancellations = (CancellationToken::new(), CancellationToken::new());
         let watchdog_handle = start_watchdog(
             Arc::clone(&process),
+            Arc::clone(&self.progress_source),
             watchdog_cancellations.clone(),
             task.clone(),
-            self.progress_type,
+            progress_type,
+            self.phase,
+            true,
         );
 
         info!("[{}] task resume", task.data.id);
 
         Box::new(Running {
-            progress_type: self.progress_type,
+            progress_type,
+            phase: self.phase,
             process,
+            pid: self.pid,
+            progress_source: self.progress_source,
             watchdog_cancellations,
             watchdog_handle,
         })
     }
 
-    async fn stop(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+    async fn stop(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
         let mut process = self.process.lock().await;
-        let kill = async {
-            process.start_kill()?;
-            process.wait().await
-        };
-        if let Err(err) = kill.await {
+        if let Err(err) = kill_process(&mut process, &task).await {
             return Box::new(Errored::from_err(err));
         };
 
@@ -406,6 +792,11 @@ impl TaskState for Pausing {
             stopped
         }
     }
+
+    async fn reset(self: Box<Self>, task: Task) -> Box<dyn TaskState> {
+        warn!("[{}] attempting to reset a pausing task", task.data.id);
+        self
+    }
 }
 
 pub struct Stopped;
@@ -451,6 +842,10 @@ impl TaskState for Stopped {
         );
         self
     }
+
+    async fn reset(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+        Box::new(Held)
+    }
 }
 
 pub struct Errored {
@@ -464,7 +859,7 @@ impl Errored {
         }
     }
 
-    fn from_string<S: Into<String>>(reason: S) -> Self {
+    pub(super) fn from_string<S: Into<String>>(reason: S) -> Self {
         Self {
             reason: reason.into(),
         }
@@ -513,6 +908,10 @@ impl TaskState for Errored {
         );
         self
     }
+
+    async fn reset(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+        Box::new(Held)
+    }
 }
 
 pub struct Finished;
@@ -558,25 +957,199 @@ impl TaskState for Finished {
         );
         self
     }
+
+    async fn reset(self: Box<Self>, _task: Task) -> Box<dyn TaskState> {
+        Box::new(Held)
+    }
+}
+
+/// Reader splitting lines terminated by either `\n` or `\r`.
+///
+/// Some ffmpeg variants print progress with carriage returns only,
+/// [`AsyncBufReadExt::read_line`] buffers them until a `\n` appears and stalls progress updating.
+///
+/// Bytes of a partial line are owned by reader until terminated,
+/// hence [`ProgressLines::read_line`] is cancel safe and could be raced in `select!`,
+/// and the partial line survives pausing since reader is put back into task state.
+pub(super) struct ProgressLines<R> {
+    reader: R,
+    pending: Vec<u8>,
+    /// Amount of leading bytes in `pending` known to contain no line terminator.
+    scanned: usize,
+}
+
+impl<R> ProgressLines<R>
+where
+    R: AsyncRead + Unpin,
+{
+    pub(super) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: Vec::new(),
+            scanned: 0,
+        }
+    }
+
+    /// Reads a line and appends it to `line`, terminator included.
+    /// Returns amount of bytes read, `0` if reaching eof.
+    pub(super) async fn read_line(&mut self, line: &mut String) -> std::io::Result<usize> {
+        loop {
+            if let Some(index) = self.pending[self.scanned..]
+                .iter()
+                .position(|b| *b == b'\n' || *b == b'\r')
+            {
+                let bytes = self
+                    .pending
+                    .drain(..=self.scanned + index)
+                    .collect::<Vec<_>>();
+                self.scanned = 0;
+                line.push_str(&String::from_utf8_lossy(&bytes));
+                return Ok(bytes.len());
+            }
+            self.scanned = self.pending.len();
+
+            // `read_buf` is cancel safe, bytes are either appended or not read at all
+            if self.reader.read_buf(&mut self.pending).await? == 0 {
+                // eof, takes rest of bytes as the last line
+                let bytes = std::mem::take(&mut self.pending);
+                self.scanned = 0;
+                line.push_str(&String::from_utf8_lossy(&bytes));
+                return Ok(bytes.len());
+            }
+        }
+    }
+}
+
+/// Creates the interim message sent before the first progress line, without any progress.
+/// Returns `None` for a resumed task, whose progress is told already.
+fn interim_message(message: &TaskRunningMessage) -> Option<TaskMessage<'_>> {
+    (!message.resumed).then(|| TaskMessage::running(message))
+}
+
+/// Clears `line` and reads the next progress line into it.
+///
+/// Previous line is always cleared, even if it is not in key=value form,
+/// otherwise it is merged into the next one.
+async fn read_progress_line<R>(
+    reader: &mut ProgressLines<R>,
+    line: &mut String,
+) -> std::io::Result<usize>
+where
+    R: AsyncRead + Unpin,
+{
+    line.clear();
+    reader.read_line(line).await
+}
+
+/// Splits a progress line into trimmed key and value, `None` if not in key=value form.
+fn progress_field(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once('=')?;
+    Some((key.trim(), value.trim()))
+}
+
+/// Opens progress log file of a task in append mode, if specified.
+async fn open_progress_log(task: &Task) -> Option<BufWriter<fs::File>> {
+    let path = task.data.args.progress_log.as_ref()?;
+    match fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+    {
+        Ok(file) => Some(BufWriter::new(file)),
+        Err(err) => {
+            warn!(
+                "[{}] failed to open progress log {}: {}",
+                task.data.id, path, err
+            );
+            None
+        }
+    }
+}
+
+/// Appends a message to progress log as a JSON line and flushes immediately,
+/// so that external monitors could tail it in time.
+/// Progress log is disabled for the rest of capturing if writing fails.
+async fn write_progress_log(
+    progress_log: &mut Option<BufWriter<fs::File>>,
+    message: &TaskMessage<'_>,
+    id: &str,
+) {
+    let Some(writer) = progress_log.as_mut() else {
+        return;
+    };
+
+    let write = async {
+        let mut line = serde_json::to_string(message)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await?;
+        writer.flush().await
+    };
+    if let Err(err) = write.await {
+        warn!("[{}] failed to write progress log, disabled: {}", id, err);
+        *progress_log = None;
+    }
+}
+
+/// Source of progress, either stdout of ffmpeg or a TCP connection from ffmpeg.
+enum ProgressReader {
+    Stdout(ChildStdout),
+    Tcp(TcpStream),
+}
+
+impl AsyncRead for ProgressReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            ProgressReader::Stdout(stdout) => Pin::new(stdout).poll_read(cx, buf),
+            ProgressReader::Tcp(socket) => Pin::new(socket).poll_read(cx, buf),
+        }
+    }
 }
 
 fn start_capture(
-    stdout: ChildStdout,
+    stdout: ProgressLines<ProgressReader>,
     stderr: ChildStderr,
     watchdog_cancellations: (CancellationToken, CancellationToken),
     task: Task,
     progress_type: ProgressType,
+    phase: Phase,
+    resumed: bool,
+    pid: Option<u32>,
 ) -> (
-    JoinHandle<(ChildStdout, Result<bool, Error>)>,
-    JoinHandle<(ChildStderr, Result<(), Error>)>,
+    JoinHandle<(ProgressLines<ProgressReader>, Result<bool, Error>)>,
+    JoinHandle<(ChildStderr, Result<StderrOutput, Error>)>,
 ) {
+    let collect_warnings = task.data.args.collect_warnings;
+    let output_paths = file_output_paths(&task.data.args);
+
     // spawn a thread to capture stdout
     let state_cloned = Arc::clone(&task.state);
     let stdout_cancellation_cloned = watchdog_cancellations.0.clone();
     let stdout_handle = tokio::spawn(async move {
         let mut line = String::new();
-        let mut reader = BufReader::new(stdout);
-        let mut message = TaskRunningMessage::new(task.data.id.to_string(), progress_type);
+        let mut reader = stdout;
+        let mut message = TaskRunningMessage::new(task.data.id.to_string(), progress_type, phase);
+        message.resumed = resumed;
+        let mut progress_log = open_progress_log(&task).await;
+        let mut resource_sampler = ResourceSampler::new(pid);
+        let mut last_frame = 0;
+        let mut last_output_time_ms = 0;
+        let mut last_percent: Option<f64> = None;
+
+        // ffmpeg may take seconds analyzing inputs and building filtergraphs before the first progress,
+        // an interim message without any progress tells frontend that task is initializing.
+        // `has_activity` stays `false`, so a hung startup is still told apart from a working one.
+        if let Some(msg) = interim_message(&message) {
+            if let Err(err) = task.data.app_handle.emit_all(TASK_MESSAGE_EVENT, &msg) {
+                warn!("[{}] failed to send interim message: {}", task.data.id, err);
+            }
+        }
+
         let result = loop {
             // check state
             if state_cloned.lock().await.as_ref().unwrap().code() != TaskStateCode::Running {
@@ -588,7 +1161,7 @@ fn start_capture(
                 _ = stdout_cancellation_cloned.cancelled() => {
                     break Ok(false);
                 }
-                len = reader.read_line(&mut line) => {
+                len = read_progress_line(&mut reader, &mut line) => {
                     match len {
                         Ok(len) => len,
                         Err(err) => {
@@ -607,19 +1180,20 @@ fn start_capture(
             }
 
             let trimmed_line = line.trim();
+            if trimmed_line.is_empty() {
+                // an empty line produced by a "\r\n" line ending
+                continue;
+            }
             trace!("[{}] capture stdout output: {}", task.data.id, trimmed_line);
 
             // store raw message
-            message.raw.push(trimmed_line.to_string());
+            message.push_raw(trimmed_line.to_string());
 
-            // extract key value
-            let mut splitted = trimmed_line.split("=");
-            if let (Some(key), Some(value)) = (splitted.next(), splitted.next()) {
-                let key = key.trim();
-                let value = value.trim();
+            // extract key value, lines not in key=value form are kept in raw only
+            if let Some((key, value)) = progress_field(trimmed_line) {
                 match key {
                     "frame" => {
-                        message.frame = value.parse::<usize>().ok();
+                        message.frame = message.parse_int_field("frame", value);
                     }
                     "fps" => {
                         message.fps = value.parse::<f64>().ok();
@@ -632,16 +1206,16 @@ fn start_capture(
                         }
                     }
                     "total_size" => {
-                        message.total_size = value.parse::<usize>().ok();
+                        message.total_size = message.parse_int_field("total_size", value);
                     }
                     "out_time_ms" => {
-                        message.output_time_ms = value.parse::<usize>().ok();
+                        message.output_time_ms = message.parse_int_field("out_time_ms", value);
                     }
                     "dup_frames" => {
-                        message.dup_frames = value.parse::<usize>().ok();
+                        message.dup_frames = message.parse_int_field("dup_frames", value);
                     }
                     "drop_frames" => {
-                        message.drop_frames = value.parse::<usize>().ok();
+                        message.drop_frames = message.parse_int_field("drop_frames", value);
                     }
                     "speed" => {
                         if value == "N/A" {
@@ -651,6 +1225,28 @@ fn start_capture(
                         }
                     }
                     "progress" => {
+                        let usage = resource_sampler.sample();
+                        message.cpu_percent = usage.cpu_percent;
+                        message.rss_bytes = usage.rss_bytes;
+
+                        // activity is kept once any monotonic counter advances,
+                        // since message is cleared after each frame
+                        let frame = message.frame.unwrap_or(0);
+                        let output_time_ms = message.output_time_ms.unwrap_or(0);
+                        if frame > last_frame || output_time_ms > last_output_time_ms {
+                            message.has_activity = true;
+                        }
+                        last_frame = last_frame.max(frame);
+                        last_output_time_ms = last_output_time_ms.max(output_time_ms);
+
+                        // percent never decreases, even if a counter jumps back
+                        message.percent = match (message.compute_percent(), last_percent) {
+                            (Some(percent), Some(last)) => Some(percent.max(last)),
+                            (percent, _) => percent,
+                        };
+                        last_percent = message.percent;
+                        message.eta_ms = message.compute_eta_ms();
+
                         let (finished, msg) = match value {
                             "continue" => (false, Some(TaskMessage::running(&message))),
                             "end" => (true, Some(TaskMessage::running(&message))),
@@ -663,6 +1259,11 @@ fn start_capture(
                                 Ok(_) => trace!("[{}] send message to frontend", task.data.id),
                                 Err(err) => break Err(Error::internal(err)),
                             }
+                            write_progress_log(&mut progress_log, &msg, &task.data.id).await;
+                            task.send_lifecycle(TaskLifecycleEvent::progress(
+                                &task.data.id,
+                                message.percent,
+                            ));
 
                             message.clear();
                         }
@@ -671,87 +1272,293 @@ fn start_capture(
                             break Ok(true);
                         }
                     }
-                    _ => {}
+                    _ => {
+                        message.insert_extra(key, value);
+                    }
                 }
-
-                line.clear();
+            } else {
+                trace!(
+                    "[{}] skip stdout output not in key=value form: {}",
+                    task.data.id,
+                    trimmed_line
+                );
             };
         };
 
-        (reader.into_inner(), result)
+        if let Some(mut progress_log) = progress_log {
+            if let Err(err) = progress_log.shutdown().await {
+                warn!("[{}] failed to close progress log: {}", task.data.id, err);
+            }
+        }
+
+        (reader, result)
     });
 
     // spawn a thread to capture stderr
     // stderr capturing should not report any process error, only ffmpeg runtime error should be thrown
     let stderr_cancellation_cloned = watchdog_cancellations.1.clone();
     let stderr_handle = tokio::spawn(async move {
-        let mut line = String::new();
         let mut reader = BufReader::new(stderr);
+        let result = capture_stderr(
+            &mut reader,
+            &stderr_cancellation_cloned,
+            collect_warnings,
+            &output_paths,
+        )
+        .await;
+        (reader.into_inner(), result)
+    });
+
+    (stdout_handle, stderr_handle)
+}
 
-        // read from stdout
+/// Captures stderr until eof or cancelled,
+/// returning the first error classified by [`classify_stderr_error`] if any.
+///
+/// Reading continues after an error, since lines printed before and after it help explaining it.
+async fn capture_stderr<R>(
+    reader: &mut R,
+    cancellation: &CancellationToken,
+    collect_warnings: bool,
+    output_paths: &[String],
+) -> Result<StderrOutput, Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = String::new();
+    let mut output = StderrOutput::default();
+    let mut recent = VecDeque::with_capacity(MAX_STDERR_LINES);
+    let mut first_error: Option<(Vec<String>, String)> = None;
+
+    loop {
+        line.clear();
+
+        // read from stderr
         let len = tokio::select! {
-            _ = stderr_cancellation_cloned.cancelled() => {
-                return (reader.into_inner(), Ok(()));
+            _ = cancellation.cancelled() => {
+                break;
             }
             len = reader.read_line(&mut line) => {
                 match len {
                     Ok(len) => len,
-                    Err(err) => {
-                        match err.kind() {
-                            std::io::ErrorKind::UnexpectedEof => return (reader.into_inner(), Ok(())),
-                            _ => return (reader.into_inner(), Ok(())),
-                        }
-                    },
+                    Err(_) => break,
                 }
             }
         };
 
-        // stop if capturing any error output or reach eof
+        // stop if reach eof
         if len == 0 {
-            (reader.into_inner(), Ok(()))
-        } else {
-            let line = line.trim();
+            break;
+        }
 
-            // checks ignore list
-            // any stderr starts with text in ignore list does not regard as error
-            static STARTS_WITH_IGNORES: [&'static str; 2] = ["x264", "x265"];
-            if STARTS_WITH_IGNORES.iter().any(|str| line.starts_with(str)) {
-                (reader.into_inner(), Ok(()))
-            } else {
-                (reader.into_inner(), Err(Error::ffmpeg_runtime_error(line)))
+        let (level, line) = split_log_level(line.trim());
+        if line.is_empty() {
+            continue;
+        }
+
+        match level {
+            // warnings never fail a task, only deprecation ones are kept
+            Some("warning") => {
+                if collect_warnings
+                    && line.to_lowercase().contains("deprecated")
+                    && output.warnings.len() < MAX_WARNINGS
+                {
+                    output.warnings.push(line.clone());
+                }
+                push_recent(&mut recent, line);
+                continue;
             }
+            Some("info") | Some("verbose") | Some("debug") => {
+                if let Some(stats) = FinalStats::from_line(&line) {
+                    output.final_stats.push(stats);
+                }
+                continue;
+            }
+            _ => {}
         }
-    });
 
-    (stdout_handle, stderr_handle)
+        // any stderr starts with text in ignore list does not regard as error
+        static STARTS_WITH_IGNORES: [&'static str; 2] = ["x264", "x265"];
+        if first_error.is_none() && !STARTS_WITH_IGNORES.iter().any(|str| line.starts_with(str)) {
+            let skip = recent.len().saturating_sub(MAX_ERROR_CONTEXT_LINES);
+            let context = recent.iter().skip(skip).cloned().collect();
+            first_error = Some((context, line.clone()));
+        }
+        push_recent(&mut recent, line);
+    }
+
+    match first_error {
+        Some((context, error_line)) => Err(classify_stderr_error(
+            context,
+            error_line,
+            &recent,
+            output_paths,
+        )),
+        None => {
+            let skip = recent.len().saturating_sub(MAX_ERROR_CONTEXT_LINES);
+            output.last_lines = recent.into_iter().skip(skip).collect();
+            Ok(output)
+        }
+    }
+}
+
+/// Returns paths of file outputs, excluding null, stdout and protocol outputs.
+fn file_output_paths(args: &TaskArgs) -> Vec<String> {
+    args.outputs
+        .iter()
+        .filter_map(|output| output.path.as_ref())
+        .filter(|path| path.as_str() != "-" && !has_protocol_scheme(path))
+        .cloned()
+        .collect()
+}
+
+/// Returns the output whose disk is full if a stderr line reports `ENOSPC`,
+/// such as `av_interleaved_write_frame(): No space left on device`.
+///
+/// The output mentioned by the line is preferred,
+/// otherwise the first file output is taken since ffmpeg usually does not print paths.
+/// Returns an empty path if there is no file output.
+fn disk_full_output(line: &str, output_paths: &[String]) -> Option<String> {
+    let lowercased = line.to_lowercase();
+    if !lowercased.contains("no space left on device") && !lowercased.contains("enospc") {
+        return None;
+    }
+
+    let path = output_paths
+        .iter()
+        .find(|path| line.contains(path.as_str()))
+        .or(output_paths.first())
+        .cloned()
+        .unwrap_or_default();
+    Some(path)
+}
+
+/// Maximum amount of recent stderr lines kept for classifying errors.
+const MAX_STDERR_LINES: usize = 64;
+
+/// Maximum amount of lines printed before the first error kept in error reason.
+const MAX_ERROR_CONTEXT_LINES: usize = 8;
+
+/// Pushes a stderr line into rolling buffer, dropping the oldest one if full.
+fn push_recent(recent: &mut VecDeque<String>, line: String) {
+    if recent.len() >= MAX_STDERR_LINES {
+        recent.pop_front();
+    }
+    recent.push_back(line);
+}
+
+/// Classifies error of a task from the first error line and all recent stderr lines.
+///
+/// Disk full is reported if any recent line reports it, even if it is not the first error.
+/// Otherwise, reason is the first error line preceded by lines printed before it.
+fn classify_stderr_error(
+    context: Vec<String>,
+    error_line: String,
+    recent: &VecDeque<String>,
+    output_paths: &[String],
+) -> Error {
+    if let Some(path) = recent
+        .iter()
+        .chain([&error_line])
+        .find_map(|line| disk_full_output(line, output_paths))
+    {
+        return Error::disk_full(path);
+    }
+
+    if context.is_empty() {
+        Error::ffmpeg_runtime_error(error_line)
+    } else {
+        let mut lines = context;
+        lines.push(error_line);
+        Error::ffmpeg_runtime_error(lines.join("\n"))
+    }
+}
+
+/// Outputs collected from stderr besides errors.
+#[derive(Default)]
+struct StderrOutput {
+    warnings: Vec<String>,
+    final_stats: Vec<FinalStats>,
+    /// Last lines printed to stderr, including warnings, explaining a failed exit without errors.
+    last_lines: Vec<String>,
+}
+
+/// Returns reason of ffmpeg exiting with failure while printing no error,
+/// which is the last lines printed to stderr, or exit status if nothing printed.
+fn exit_failure_reason(status: ExitStatus, last_lines: &[String]) -> String {
+    if last_lines.is_empty() {
+        format!("ffmpeg exited with failure: {status}")
+    } else {
+        format!(
+            "ffmpeg exited with failure: {status}\n{}",
+            last_lines.join("\n")
+        )
+    }
+}
+
+/// Maximum amount of deprecation warnings collected from stderr.
+const MAX_WARNINGS: usize = 32;
+
+/// Splits log level out of a stderr line printed with `-loglevel level+<level>`,
+/// such as `[libx264 @ 0x5581] [warning] some message`.
+/// Returns `None` level for lines without a level tag.
+fn split_log_level(line: &str) -> (Option<&'static str>, String) {
+    static LEVELS: [&'static str; 8] = [
+        "quiet", "panic", "fatal", "error", "warning", "info", "verbose", "debug",
+    ];
+
+    for level in LEVELS {
+        let tag = format!("[{level}] ");
+        if let Some(index) = line.find(&tag) {
+            let message = format!("{}{}", &line[..index], &line[index + tag.len()..]);
+            return (Some(level), message);
+        }
+    }
+
+    (None, line.to_string())
 }
 
+/// Timeout waiting for stdout and stderr capturing reaching eof, once ffmpeg exited.
+const EXITED_CAPTURE_TIMEOUT: Duration = Duration::from_secs(1);
+
 enum ProcessStatus {
+    /// Capturing stopped, with exit status if ffmpeg exited with failure meanwhile.
     PauseOrFinish(
-        Result<(ChildStdout, Result<bool, Error>), tokio::task::JoinError>,
-        Result<(ChildStderr, Result<(), Error>), tokio::task::JoinError>,
+        Result<(ProgressLines<ProgressReader>, Result<bool, Error>), tokio::task::JoinError>,
+        Result<(ChildStderr, Result<StderrOutput, Error>), tokio::task::JoinError>,
+        Option<ExitStatus>,
     ),
+    /// ffmpeg exited successfully but capturing never reached eof.
     Exit,
     Killed(Error),
 }
 
 fn start_watchdog(
     process: Arc<Mutex<Child>>,
+    progress_source: ProgressSource,
     watchdog_cancellations: (CancellationToken, CancellationToken),
     task: Task,
     progress_type: ProgressType,
+    phase: Phase,
+    resumed: bool,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
         info!("[{}] start subprocess output capturing", task.data.id);
 
         let mut process = process.lock().await;
 
-        let stdout = process.stdout.take().unwrap(); // safely unwrap
+        // progress is read from socket instead of stdout if socket presents,
+        // stdout is only taken out from subprocess on the first capturing
+        let stdout = match progress_source.lock().await.take() {
+            Some(lines) => lines,
+            None => ProgressLines::new(ProgressReader::Stdout(process.stdout.take().unwrap())), // safely unwrap
+        };
         let stderr = process.stderr.take().unwrap(); // safely unwrap
 
         // strange bug, stdin becomes None when trying to pause a running job.
         // only takes it out here and puts it back when watchdog stopping could make it works.
-        let stdin = process.stdin.take().unwrap(); // safely unwrap
+        // stdin is `None` if it is not piped.
+        let stdin = process.stdin.take();
 
         // spawns threads to capture log from stdout and stderr.
         // stdout and stdin are taken out from subprocess
@@ -761,25 +1568,40 @@ fn start_watchdog(
             watchdog_cancellations,
             task.clone(),
             progress_type,
+            phase,
+            resumed,
+            process.id(),
         );
 
         // waits for watchdog finished or process killed
+        let mut handles = tokio::spawn(async move { tokio::join!(stdout_handle, stderr_handle) });
         let status = tokio::select! {
-            handles = tokio::spawn(async move { tokio::join!(stdout_handle, stderr_handle) }) => {
+            handles = &mut handles => {
                 match handles {
-                    Ok((h1, h2)) => ProcessStatus::PauseOrFinish(h1, h2),
+                    Ok((h1, h2)) => ProcessStatus::PauseOrFinish(h1, h2, None),
                     Err(err) => ProcessStatus::Killed(Error::internal(err))
                 }
             },
             status = process.wait() => {
-                // waits for 50 milliseconds to ensure stderr finish first
-                tokio::time::sleep(Duration::from_millis(50)).await;
                 match status {
                     Ok(status) => {
                         if status.success() {
-                            ProcessStatus::Exit
+                            // capturing is racing with exiting, waits for it reaching eof,
+                            // so that task finishes with final stats the same as capturing stopped first
+                            match tokio::time::timeout(EXITED_CAPTURE_TIMEOUT, &mut handles).await {
+                                Ok(Ok((h1, h2))) => ProcessStatus::PauseOrFinish(h1, h2, None),
+                                Ok(Err(err)) => ProcessStatus::Killed(Error::internal(err)),
+                                Err(_) => ProcessStatus::Exit,
+                            }
                         } else {
-                            ProcessStatus::Killed(Error::ffmpeg_unexpected_killed())
+                            // ffmpeg may exit with failure before printing any progress, such as an unrecognized option.
+                            // waits for capturing reaching eof, so that the error printed to stderr is reported
+                            // instead of an unexpected killed one.
+                            match tokio::time::timeout(EXITED_CAPTURE_TIMEOUT, &mut handles).await {
+                                Ok(Ok((h1, h2))) => ProcessStatus::PauseOrFinish(h1, h2, Some(status)),
+                                Ok(Err(err)) => ProcessStatus::Killed(Error::internal(err)),
+                                Err(_) => ProcessStatus::Killed(Error::ffmpeg_unexpected_killed()),
+                            }
                         }
                     },
                     Err(err) => ProcessStatus::Killed(Error::internal(err))
@@ -788,7 +1610,7 @@ fn start_watchdog(
         };
 
         match status {
-            ProcessStatus::PauseOrFinish(stdout_handle_result, stderr_handle_result) => {
+            ProcessStatus::PauseOrFinish(stdout_handle_result, stderr_handle_result, failure) => {
                 let ((stdout, stdout_result), (stderr, stderr_result)) = match (
                     stdout_handle_result,
                     stderr_handle_result,
@@ -815,17 +1637,37 @@ fn start_watchdog(
                     }
                 };
 
-                process.stdout = Some(stdout);
+                *progress_source.lock().await = Some(stdout);
                 process.stderr = Some(stderr);
-                process.stdin = Some(stdin);
+                process.stdin = stdin;
 
                 match (stdout_result, stderr_result) {
-                    (Ok(finished), Ok(_)) => {
-                        if finished {
-                            tokio::spawn(async move { task.finish().await });
-                        } else {
+                    // ffmpeg exited with failure but printed no error, never finishes even if progress ended
+                    (_, Ok(output)) if failure.is_some() => {
+                        let reason = exit_failure_reason(failure.unwrap(), &output.last_lines); // safely unwrap
+                        tokio::spawn(async move { task.error(reason).await });
+                    }
+                    (Ok(finished), Ok(output)) => {
+                        // warnings are kept across pauses and only sent once finished
+                        let mut pending_warnings = task.data.pending_warnings.lock().await;
+                        let vacancy = MAX_WARNINGS.saturating_sub(pending_warnings.len());
+                        pending_warnings.extend(output.warnings.into_iter().take(vacancy));
+                        if !finished {
                             // pause, do nothing
+                            return;
+                        }
+
+                        let warnings = std::mem::take(&mut *pending_warnings);
+                        drop(pending_warnings);
+                        if !warnings.is_empty() {
+                            let message = TaskMessage::warning(task.data.id.clone(), warnings);
+                            if let Err(err) =
+                                task.data.app_handle.emit_all(TASK_MESSAGE_EVENT, message)
+                            {
+                                warn!("[{}] failed to send warnings: {}", task.data.id, err);
+                            }
                         }
+                        tokio::spawn(async move { task.finish(output.final_stats).await });
                     }
                     (Err(err), Ok(_)) => {
                         let reason = err.to_string();
@@ -839,7 +1681,11 @@ fn start_watchdog(
                 }
             }
             ProcessStatus::Exit => {
-                // do nothing, waits for watchdog stops and send finish event there
+                warn!(
+                    "[{}] ffmpeg exited but capturing never stopped",
+                    task.data.id
+                );
+                tokio::spawn(async move { task.finish(Vec::new()).await });
             }
             ProcessStatus::Killed(err) => {
                 // unexpected killed
@@ -849,3 +1695,381 @@ fn start_watchdog(
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, time::Duration};
+
+    use tokio::io::AsyncWriteExt;
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::handlers::error::Error;
+
+    use super::{
+        capture_stderr, classify_stderr_error, disk_full_output, exit_failure_reason,
+        exited_reason, interim_message, progress_field, read_progress_line, split_log_level,
+        wait_killed, write_progress_log, ProgressLines, MAX_ERROR_CONTEXT_LINES, MAX_WARNINGS,
+    };
+
+    async fn read_lines(bytes: &[u8]) -> Vec<String> {
+        let mut reader = ProgressLines::new(bytes);
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    #[test]
+    fn reports_exited_reason_without_warnings() {
+        let stderr = "[info] ffmpeg version 6.1\n\
+            [warning] something deprecated\n\
+            [error] Unrecognized option 'foo'.\n\
+            [fatal] Error splitting the argument list: Option not found\n";
+        assert_eq!(
+            exited_reason(stderr).as_deref(),
+            Some("Unrecognized option 'foo'.\nError splitting the argument list: Option not found")
+        );
+    }
+
+    #[test]
+    fn reports_no_exited_reason_for_empty_stderr() {
+        assert_eq!(exited_reason(""), None);
+        assert_eq!(exited_reason("[info] ffmpeg version 6.1\n\n"), None);
+    }
+
+    #[tokio::test]
+    async fn splits_lines_by_lf() {
+        assert_eq!(
+            read_lines(b"frame=1\nprogress=continue\n").await,
+            vec!["frame=1\n", "progress=continue\n"]
+        );
+    }
+
+    #[tokio::test]
+    async fn splits_lines_by_cr() {
+        assert_eq!(
+            read_lines(b"frame=1\rframe=2\rprogress=end").await,
+            vec!["frame=1\r", "frame=2\r", "progress=end"]
+        );
+    }
+
+    #[tokio::test]
+    async fn splits_crlf_into_an_extra_empty_line() {
+        assert_eq!(
+            read_lines(b"frame=1\r\nframe=2\r\n").await,
+            vec!["frame=1\r", "\n", "frame=2\r", "\n"]
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_partial_line_when_cancelled() {
+        let (mut writer, reader) = tokio::io::duplex(64);
+        let mut reader = ProgressLines::new(reader);
+
+        writer.write_all(b"out_time_ms=").await.unwrap();
+        let mut line = String::new();
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(20), reader.read_line(&mut line)).await;
+        assert!(cancelled.is_err());
+
+        writer.write_all(b"1000\rprogress").await.unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "out_time_ms=1000\r");
+
+        drop(writer);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "progress");
+    }
+
+    /// Runs a fake ffmpeg by shell and captures its stderr, returning exit status along with result.
+    #[cfg(unix)]
+    async fn run_fake_ffmpeg(
+        script: &str,
+    ) -> (std::process::ExitStatus, Result<super::StderrOutput, Error>) {
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", script])
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut reader = tokio::io::BufReader::new(child.stderr.take().unwrap());
+        let result = capture_stderr(&mut reader, &CancellationToken::new(), true, &[]).await;
+        (child.wait().await.unwrap(), result)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reports_stderr_of_invalid_flag() {
+        let (status, result) = run_fake_ffmpeg(
+            "echo \"[error] Unrecognized option 'invalid-flag'.\" >&2; \
+            echo '[fatal] Error splitting the argument list: Option not found' >&2; \
+            exit 8",
+        )
+        .await;
+
+        assert!(!status.success());
+        let Err(Error::FFmpegRuntimeError { reason }) = result else {
+            panic!("expected ffmpeg runtime error, got {:?}", result.err());
+        };
+        assert_eq!(reason, "Unrecognized option 'invalid-flag'.");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn keeps_last_lines_of_failure_without_errors() {
+        let (status, result) = run_fake_ffmpeg(
+            "echo '[info] frame=1 fps=0.0 q=0.0 size=0kB time=00:00:00.00' >&2; \
+            echo '[warning] stream 1 is deprecated' >&2; \
+            exit 1",
+        )
+        .await;
+
+        let output = result.unwrap();
+        assert_eq!(output.last_lines, vec!["stream 1 is deprecated"]);
+        assert_eq!(output.warnings, vec!["stream 1 is deprecated"]);
+        assert_eq!(
+            exit_failure_reason(status, &output.last_lines),
+            "ffmpeg exited with failure: exit status: 1\nstream 1 is deprecated"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_exit_status_of_silent_failure() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let status = std::process::ExitStatus::from_raw(1 << 8);
+        assert_eq!(
+            exit_failure_reason(status, &[]),
+            "ffmpeg exited with failure: exit status: 1"
+        );
+    }
+
+    /// Captures stderr from bytes, as if ffmpeg printed them with `-loglevel level+<level>`.
+    async fn capture_bytes(
+        stderr: &str,
+        output_paths: &[String],
+    ) -> Result<super::StderrOutput, Error> {
+        let mut reader = stderr.as_bytes();
+        capture_stderr(&mut reader, &CancellationToken::new(), false, output_paths).await
+    }
+
+    #[tokio::test]
+    async fn keeps_lines_printed_before_error() {
+        let stderr = "[info] Input #0, matroska,webm, from 'in.mkv':\n\
+            [mp4 @ 0x55d1] [warning] track 1: codec frame size is not set\n\
+            [mp4 @ 0x55d1] [warning] Could not find tag for codec pcm_s16le\n\
+            [error] Could not write header for output file #0\n\
+            [error] Conversion failed!\n";
+        let Err(Error::FFmpegRuntimeError { reason }) = capture_bytes(stderr, &[]).await else {
+            panic!("expected ffmpeg runtime error");
+        };
+        assert_eq!(
+            reason,
+            "[mp4 @ 0x55d1] track 1: codec frame size is not set\n\
+            [mp4 @ 0x55d1] Could not find tag for codec pcm_s16le\n\
+            Could not write header for output file #0"
+        );
+    }
+
+    #[tokio::test]
+    async fn limits_lines_printed_before_error() {
+        let mut stderr = (0..MAX_ERROR_CONTEXT_LINES + 4)
+            .map(|i| format!("[warning] warning {i}\n"))
+            .collect::<String>();
+        stderr.push_str("[error] Conversion failed!\n");
+
+        let Err(Error::FFmpegRuntimeError { reason }) = capture_bytes(&stderr, &[]).await else {
+            panic!("expected ffmpeg runtime error");
+        };
+        let lines = reason.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), MAX_ERROR_CONTEXT_LINES + 1);
+        assert_eq!(lines[0], "warning 4");
+        assert_eq!(lines[MAX_ERROR_CONTEXT_LINES], "Conversion failed!");
+    }
+
+    #[tokio::test]
+    async fn reports_disk_full_printed_after_first_error() {
+        let stderr = "[out#0/mp4 @ 0x55d1] [error] Error muxing a packet\n\
+            [error] av_interleaved_write_frame(): No space left on device\n\
+            [error] Conversion failed!\n";
+        let result = capture_bytes(stderr, &["/videos/out.mp4".to_string()]).await;
+        let Err(Error::DiskFull { path }) = result else {
+            panic!("expected disk full");
+        };
+        assert_eq!(path, "/videos/out.mp4");
+    }
+
+    #[test]
+    fn classifies_error_without_context() {
+        let error = classify_stderr_error(
+            vec![],
+            "Unknown encoder 'libx265'".to_string(),
+            &VecDeque::new(),
+            &[],
+        );
+        assert!(matches!(
+            error,
+            Error::FFmpegRuntimeError { reason } if reason == "Unknown encoder 'libx265'"
+        ));
+    }
+
+    #[tokio::test]
+    async fn appends_progress_messages_as_json_lines() {
+        use crate::handlers::tasks::message::TaskMessage;
+
+        let path = std::env::temp_dir().join(format!("progress-{}.jsonl", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "{\"state\":\"Held\",\"id\":\"previous\"}\n").unwrap();
+
+        let file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .await
+            .unwrap();
+        let mut progress_log = Some(tokio::io::BufWriter::new(file));
+        for id in ["first", "second"] {
+            let message = TaskMessage::held(id.to_string());
+            write_progress_log(&mut progress_log, &message, id).await;
+        }
+        assert!(progress_log.is_some());
+
+        let ids = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["id"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["previous", "first", "second"]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn splits_log_levels_out_of_lines() {
+        assert_eq!(
+            split_log_level("[libx264 @ 0x5581] [warning] -vsync is deprecated"),
+            (
+                Some("warning"),
+                "[libx264 @ 0x5581] -vsync is deprecated".to_string()
+            )
+        );
+        assert_eq!(
+            split_log_level("[error] Invalid argument"),
+            (Some("error"), "Invalid argument".to_string())
+        );
+        assert_eq!(
+            split_log_level("frame=1 fps=0.0"),
+            (None, "frame=1 fps=0.0".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn collects_deprecation_warnings_only_if_enabled() {
+        let stderr = "[warning] -vsync is deprecated, use -fps_mode\n\
+            [warning] Guessed Channel Layout for Input Stream #0.1 : stereo\n"
+            .repeat(MAX_WARNINGS + 1);
+
+        let mut reader = stderr.as_bytes();
+        let output = capture_stderr(&mut reader, &CancellationToken::new(), true, &[])
+            .await
+            .unwrap();
+        assert_eq!(output.warnings.len(), MAX_WARNINGS);
+        assert!(output
+            .warnings
+            .iter()
+            .all(|warning| warning == "-vsync is deprecated, use -fps_mode"));
+
+        let mut reader = stderr.as_bytes();
+        let output = capture_stderr(&mut reader, &CancellationToken::new(), false, &[])
+            .await
+            .unwrap();
+        assert!(output.warnings.is_empty());
+    }
+
+    #[test]
+    fn finds_output_of_full_disk() {
+        let outputs = vec!["/mnt/a/out.mp4".to_string(), "/mnt/b/out.mkv".to_string()];
+        assert_eq!(
+            disk_full_output("/mnt/b/out.mkv: No space left on device", &outputs),
+            Some("/mnt/b/out.mkv".to_string())
+        );
+        assert_eq!(
+            disk_full_output(
+                "av_interleaved_write_frame(): No space left on device",
+                &outputs
+            ),
+            Some("/mnt/a/out.mp4".to_string())
+        );
+        assert_eq!(
+            disk_full_output("Error writing trailer: ENOSPC", &[]),
+            Some(String::new())
+        );
+        assert_eq!(disk_full_output("Invalid data found", &outputs), None);
+    }
+
+    #[tokio::test]
+    async fn never_merges_lines_not_in_key_value_form() {
+        let mut reader = ProgressLines::new(&b"Press [q] to stop\nframe=10\n"[..]);
+        let mut line = String::new();
+
+        read_progress_line(&mut reader, &mut line).await.unwrap();
+        assert_eq!(progress_field(line.trim()), None);
+
+        read_progress_line(&mut reader, &mut line).await.unwrap();
+        assert_eq!(line, "frame=10\n");
+        assert_eq!(progress_field(line.trim()), Some(("frame", "10")));
+    }
+
+    #[test]
+    fn sends_interim_message_before_first_progress() {
+        use crate::handlers::tasks::{
+            message::{TaskMessage, TaskRunningMessage},
+            progress::{Phase, ProgressType},
+        };
+
+        let progress_type = ProgressType::ByDuration {
+            duration: 10.0,
+            duration_source_input: Some(0),
+        };
+        let mut message =
+            TaskRunningMessage::new("task".to_string(), progress_type, Phase::single());
+        let Some(TaskMessage::Running(interim)) = interim_message(&message) else {
+            panic!("expected an interim running message");
+        };
+        assert!(!interim.has_activity);
+        assert_eq!(interim.frame, None);
+        assert_eq!(interim.percent, None);
+
+        message.resumed = true;
+        assert!(interim_message(&message).is_none());
+    }
+
+    #[tokio::test]
+    async fn leaves_behind_child_never_exiting() {
+        let timeout = Duration::from_millis(20);
+        let started = std::time::Instant::now();
+        let never_exits = std::future::pending::<std::io::Result<std::process::ExitStatus>>();
+        assert!(wait_killed(never_exits, "task", timeout).await.is_ok());
+        assert!(started.elapsed() >= timeout);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn waits_killed_child_exiting() {
+        let mut child = tokio::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        child.start_kill().unwrap();
+        assert!(wait_killed(child.wait(), "task", Duration::from_secs(5))
+            .await
+            .is_ok());
+        assert!(child.try_wait().unwrap().is_some());
+    }
+}
@@ -1,24 +1,302 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock, Weak,
+    },
+    time::Duration,
+};
 
+use log::{info, warn};
+use tauri::Manager;
 use tokio::sync::Mutex;
 
 use crate::handlers::{commands::task::TaskArgs, error::Error};
 
-use super::task::Task;
+use super::{
+    message::{StopReason, TaskLifecycleEvent, TaskLifecycleKind, TaskMessage, TASK_MESSAGE_EVENT},
+    priority::apply_priority,
+    state_machine::TaskStateCode,
+    task::{Task, TaskData},
+    validation::resolved_output_paths,
+};
+
+/// Default maximum amount of tasks a [`TaskStore`] holds.
+pub const DEFAULT_MAX_TASKS: usize = 1024;
+
+/// Maximum amount of ended tasks a [`TaskStore`] keeps for resetting, oldest ones are dropped first.
+///
+/// A dropped task could neither be reset nor restarted, which fail as task not found,
+/// it has to be started again with its args then.
+pub const MAX_ENDED_TASKS: usize = 256;
+
+/// Interval of sweeping inconsistent tasks from a [`TaskStore`].
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Result of clearing all tasks.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct ClearResult {
+    /// Amount of idle, running or pausing tasks stopped.
+    pub stopped: usize,
+    /// Amount of not yet started tasks discarded, which are queued or held.
+    pub dequeued: usize,
+}
+
+/// Result of a pause or resume operation.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct TransitionResult {
+    /// `false` if task is already in target state or not able to transition.
+    pub changed: bool,
+    /// State of task after operation.
+    pub state: TaskStateCode,
+}
+
+/// Limits amount of running tasks, tasks beyond limit wait in queued state.
+///
+/// Tasks which are idle, running or pausing occupy slots of running tasks.
+/// Once a task removed from store, next waiting task starts.
+pub struct Scheduler {
+    /// Maximum amount of running tasks, no limitation if `0`.
+    max_concurrent: AtomicUsize,
+    /// Ids of tasks waiting for running, in starting order.
+    /// Only locked for pushing or popping, never across starting a task.
+    pending: Mutex<VecDeque<String>>,
+    /// Serializes starting of waiting tasks, so that concurrent dispatching never exceeds limit.
+    dispatching: Mutex<()>,
+    store: Weak<Mutex<HashMap<String, Task>>>,
+}
+
+impl Scheduler {
+    fn new(store: Weak<Mutex<HashMap<String, Task>>>) -> Self {
+        Self {
+            max_concurrent: AtomicUsize::new(0),
+            pending: Mutex::new(VecDeque::new()),
+            dispatching: Mutex::new(()),
+            store,
+        }
+    }
+
+    /// Puts tasks to the back of pending queue and starts waiting tasks until limit reached.
+    ///
+    /// Every queued task is started through here, so that limit is always honored.
+    pub async fn start_all(&self, ids: Vec<String>) {
+        self.pending.lock().await.extend(ids);
+        self.start_next().await;
+    }
+
+    /// Starts waiting tasks in order until limit reached.
+    ///
+    /// Waiting tasks removed from store, or not queued anymore, such as stopped or held, are skipped.
+    pub async fn start_next(&self) {
+        let _dispatching = self.dispatching.lock().await;
+        while self.has_slot().await {
+            let Some(id) = self.pending.lock().await.pop_front() else {
+                break;
+            };
+            let Some(store) = self.store.upgrade() else {
+                break;
+            };
+            let Some(task) = store.lock().await.get(&id).cloned() else {
+                continue;
+            };
+
+            let code = task.state.lock().await.as_ref().map(|state| state.code());
+            if is_startable(code) {
+                task.start().await;
+            }
+        }
+
+        let waiting = self.pending.lock().await.len();
+        if waiting != 0 {
+            info!("{} tasks waiting for running slots", waiting);
+        }
+    }
+
+    /// Returns `true` if amount of running tasks is below limit.
+    async fn has_slot(&self) -> bool {
+        let max = self.max_concurrent.load(Ordering::SeqCst);
+        if max == 0 {
+            return true;
+        }
+        let Some(store) = self.store.upgrade() else {
+            return false;
+        };
+
+        let tasks = store.lock().await.values().cloned().collect::<Vec<_>>();
+        let mut running = 0;
+        for task in tasks {
+            let code = task.state.lock().await.as_ref().map(|state| state.code());
+            if takes_slot(code) {
+                running += 1;
+                if running >= max {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// Returns `true` if a waiting task in `code` state is started by scheduler.
+///
+/// Only queued tasks are started, held tasks wait for resuming, which queues them.
+fn is_startable(code: Option<TaskStateCode>) -> bool {
+    code == Some(TaskStateCode::Queued)
+}
+
+/// Returns `true` if a task in `code` state takes a running slot.
+fn takes_slot(code: Option<TaskStateCode>) -> bool {
+    matches!(
+        code,
+        Some(TaskStateCode::Idle) | Some(TaskStateCode::Running) | Some(TaskStateCode::Pausing)
+    )
+}
 
 /// Task managing store center.
+///
+/// A task state is never locked while holding store, since an erroring task locks store
+/// while holding its state. States of tasks are read after store released then,
+/// and store is only locked after a state, both here and in [`Scheduler`].
 pub struct TaskStore {
     store: Arc<Mutex<HashMap<String, Task>>>,
+    /// Ended tasks removed from store, kept for resetting and restarting, at most [`MAX_ENDED_TASKS`].
+    ///
+    /// Tasks here are not "in the store", they are never counted, stopped or swept,
+    /// and a new task may take the id of an ended one, replacing it once the new one ends as well.
+    /// Ended tasks are kept out of store, since store only holds active tasks,
+    /// which limit adding tasks by `max_tasks` and are all stopped by [`TaskStore::clear_all`].
+    ended: Arc<Mutex<HashMap<String, Task>>>,
+    /// Maximum amount of tasks, no limitation if `None`.
+    max_tasks: Option<usize>,
+    scheduler: Arc<Scheduler>,
+}
+
+/// Keeps an ended task, dropping the oldest ones beyond [`MAX_ENDED_TASKS`].
+pub fn keep_ended(ended: &mut HashMap<String, Task>, task: Task) {
+    ended.insert(task.data.id.clone(), task);
+    drop_oldest(ended, MAX_ENDED_TASKS, |task| task.data.created_at);
+}
+
+/// Drops entries created earliest by `created_at`, until at most `max` entries are left.
+fn drop_oldest<T, K: Ord>(
+    entries: &mut HashMap<String, T>,
+    max: usize,
+    created_at: impl Fn(&T) -> K,
+) {
+    while entries.len() > max {
+        let Some(oldest) = entries
+            .iter()
+            .min_by_key(|(_, entry)| created_at(entry))
+            .map(|(id, _)| id.clone())
+        else {
+            break;
+        };
+        entries.remove(&oldest);
+    }
+}
+
+/// Ways of clearing a task by [`TaskStore::clear_all`], in clearing order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Clearing {
+    /// Not yet started task, which is queued or held, discarded first.
+    Dequeue,
+    /// Started task taking a running slot, which is idle, running or pausing.
+    Stop,
+}
+
+/// Finds how to clear a task in `code` state, `None` if task is ended already.
+fn find_clearing(code: Option<TaskStateCode>) -> Option<Clearing> {
+    match code? {
+        TaskStateCode::Queued | TaskStateCode::Held => Some(Clearing::Dequeue),
+        TaskStateCode::Idle | TaskStateCode::Running | TaskStateCode::Pausing => {
+            Some(Clearing::Stop)
+        }
+        TaskStateCode::Stopped | TaskStateCode::Finished | TaskStateCode::Errored => None,
+    }
+}
+
+/// Ways of reaping an inconsistent task by sweeper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reaping {
+    /// Running task whose ffmpeg exited successfully, only finishing is lost,
+    /// such as watchdog panicked when finishing.
+    Finish,
+    /// Running task whose watchdog exited while ffmpeg failed or never exited.
+    Error,
+    /// Ended task still in store.
+    Remove,
+}
+
+/// Finds how to reap a task in `code` state, `None` if task is consistent.
+fn find_reaping(
+    code: TaskStateCode,
+    watchdog_exited: bool,
+    exit_status: Option<ExitStatus>,
+) -> Option<Reaping> {
+    match code {
+        TaskStateCode::Stopped | TaskStateCode::Finished | TaskStateCode::Errored => {
+            Some(Reaping::Remove)
+        }
+        TaskStateCode::Running if watchdog_exited => {
+            if exit_status.map(|status| status.success()).unwrap_or(false) {
+                Some(Reaping::Finish)
+            } else {
+                Some(Reaping::Error)
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Checks whether one more task could be added to a store holding `len` tasks,
+/// no limitation if `max_tasks` is `None`.
+fn check_capacity(len: usize, max_tasks: Option<usize>) -> Result<(), Error> {
+    match max_tasks {
+        Some(max) if len >= max => Err(Error::queue_full(max)),
+        _ => Ok(()),
+    }
+}
+
+/// Finds the first of resolved `outputs` which is an output of any `existing` task as well.
+fn find_output_in_use<'a, 'b>(
+    outputs: &'a [PathBuf],
+    existing: impl IntoIterator<Item = &'b [PathBuf]>,
+) -> Option<&'a PathBuf> {
+    if outputs.is_empty() {
+        return None;
+    }
+
+    existing
+        .into_iter()
+        .find_map(|existing| outputs.iter().find(|path| existing.contains(path)))
+}
+
+/// Returns `true` if args of a task in `code` state could be replaced.
+///
+/// A queued task with `retries` is delaying for retrying, it has run already
+/// and its pending retry holds the old args, hence it is not updatable.
+fn is_args_updatable(code: Option<TaskStateCode>, retries: usize) -> bool {
+    match code {
+        Some(TaskStateCode::Queued) => retries == 0,
+        Some(TaskStateCode::Held) | Some(TaskStateCode::Idle) => true,
+        _ => false,
+    }
 }
 
 macro_rules! operations {
     ($((
         $(#[$meta:meta])*
-        $name:ident
+        $name:ident,
+        $target:expr
     )),+) => {
         $(
             $(#[$meta])*
-            pub async fn $name(&self, id: &str) -> Result<(), Error> {
+            ///
+            /// Returns without dispatching to state machine if task is already in target state.
+            pub async fn $name(&self, id: &str) -> Result<TransitionResult, Error> {
                 let store = self.store.lock().await;
                 let Some(task) = store.get(id) else {
                     return Err(Error::task_not_found(id));
@@ -27,23 +305,59 @@ macro_rules! operations {
                 let task = task.clone();
                 drop(store);
 
+                let before = task.state.lock().await.as_ref().map(|state| state.code());
+                if before == Some($target) {
+                    return Ok(TransitionResult {
+                        changed: false,
+                        state: $target,
+                    });
+                }
+
                 task.$name().await;
-                Ok(())
+
+                let after = task.state.lock().await.as_ref().map(|state| state.code());
+                let state = after.or(before).unwrap(); // safely unwrap, state is only taken out during transitions
+                Ok(TransitionResult {
+                    changed: after != before,
+                    state,
+                })
             }
         )+
     };
 }
 
 impl TaskStore {
-    /// Creates a new transcode store.
+    /// Creates a new transcode store, holding at most [`DEFAULT_MAX_TASKS`] tasks.
     pub fn new() -> Self {
+        Self::with_max_tasks(Some(DEFAULT_MAX_TASKS))
+    }
+
+    /// Creates a new transcode store with a specified maximum amount of tasks.
+    pub fn with_max_tasks(max_tasks: Option<usize>) -> Self {
+        let store = Arc::new(Mutex::new(HashMap::new()));
+        let scheduler = Arc::new(Scheduler::new(Arc::downgrade(&store)));
         Self {
-            store: Arc::new(Mutex::new(HashMap::new())),
+            store,
+            ended: Arc::new(Mutex::new(HashMap::new())),
+            max_tasks,
+            scheduler,
         }
     }
 
+    /// Sets maximum amount of running tasks, no limitation if `0`.
+    ///
+    /// Running tasks beyond a lowered limit keep running,
+    /// while waiting tasks start if limit raised.
+    pub async fn set_max_concurrent(&self, max_concurrent: usize) {
+        self.scheduler
+            .max_concurrent
+            .store(max_concurrent, Ordering::SeqCst);
+        self.scheduler.start_next().await;
+    }
+
     /// Adds and starts a new task.
-    /// Returns an identifier which points to the task.
+    /// Task waits in queued state if amount of running tasks reaches limit,
+    /// see [`TaskStore::set_max_concurrent`].
     pub async fn start(
         &self,
         id: String,
@@ -52,40 +366,703 @@ impl TaskStore {
         ffmpeg_program: String,
         ffprobe_program: String,
     ) -> Result<(), Error> {
+        let task = self
+            .insert(id, args, app_handle, ffmpeg_program, ffprobe_program)
+            .await?;
+        self.scheduler.start_all(vec![task.data.id.clone()]).await;
+        Ok(())
+    }
+
+    /// Adds a new task in held state without starting it,
+    /// it starts once resumed by [`TaskStore::resume`].
+    pub async fn start_paused(
+        &self,
+        id: String,
+        args: TaskArgs,
+        app_handle: tauri::AppHandle,
+        ffmpeg_program: String,
+        ffprobe_program: String,
+    ) -> Result<(), Error> {
+        let task = self
+            .insert(id, args, app_handle, ffmpeg_program, ffprobe_program)
+            .await?;
+        task.hold().await;
+        Ok(())
+    }
+
+    /// Adds and starts many tasks under a single store lock acquisition,
+    /// returning result of each task in order.
+    ///
+    /// Tasks failing to be added never fail others, such as tasks with duplicated ids.
+    /// Tasks added are started in order in background, until amount of running tasks reaches limit.
+    pub async fn start_many(
+        &self,
+        tasks: Vec<(String, TaskArgs)>,
+        app_handle: tauri::AppHandle,
+        ffmpeg_program: String,
+        ffprobe_program: String,
+    ) -> Vec<Result<(), Error>> {
+        // tasks are created before locking store, since resolving output paths touches file system
+        let tasks = tasks
+            .into_iter()
+            .map(|(id, args)| {
+                self.new_task(
+                    id,
+                    args,
+                    app_handle.clone(),
+                    ffmpeg_program.clone(),
+                    ffprobe_program.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
         let mut store = self.store.lock().await;
-        if store.contains_key(&id) {
-            return Err(Error::task_existing(id));
-        }
+        let inserted = tasks
+            .into_iter()
+            .map(|task| self.insert_into(&mut store, task))
+            .collect::<Vec<_>>();
+        drop(store);
+
+        let ids = inserted
+            .iter()
+            .filter_map(|result| result.as_ref().ok())
+            .map(|task| task.data.id.clone())
+            .collect::<Vec<_>>();
+        let scheduler = Arc::clone(&self.scheduler);
+        tokio::spawn(async move { scheduler.start_all(ids).await });
+
+        inserted
+            .into_iter()
+            .map(|result| result.map(|_| ()))
+            .collect()
+    }
+
+    /// Creates a new queued task and adds it to store.
+    async fn insert(
+        &self,
+        id: String,
+        args: TaskArgs,
+        app_handle: tauri::AppHandle,
+        ffmpeg_program: String,
+        ffprobe_program: String,
+    ) -> Result<Task, Error> {
+        let task = self.new_task(id, args, app_handle, ffmpeg_program, ffprobe_program);
+        let mut store = self.store.lock().await;
+        self.insert_into(&mut store, task)
+    }
 
-        let task = Task::new(
-            id.clone(),
+    /// Creates a new queued task belonging to this store, without adding it.
+    fn new_task(
+        &self,
+        id: String,
+        args: TaskArgs,
+        app_handle: tauri::AppHandle,
+        ffmpeg_program: String,
+        ffprobe_program: String,
+    ) -> Task {
+        Task::new(
+            id,
             app_handle,
             ffmpeg_program,
             ffprobe_program,
             args,
             Arc::downgrade(&self.store),
-        );
-        store.insert(id, task.clone());
+            Arc::downgrade(&self.ended),
+            Arc::downgrade(&self.scheduler),
+        )
+    }
+
+    /// Adds a new queued task to a locked store.
+    fn insert_into(&self, store: &mut HashMap<String, Task>, task: Task) -> Result<Task, Error> {
+        self.check_insertable(store, &task.data.id, &task.data.output_paths)?;
+
+        store.insert(task.data.id.clone(), task.clone());
+        task.send_lifecycle(TaskLifecycleEvent::new(
+            TaskLifecycleKind::Created,
+            &task.data.id,
+        ));
+
+        Ok(task)
+    }
+
+    /// Checks whether a task with `id` and resolved `outputs` could be added to a locked store.
+    fn check_insertable(
+        &self,
+        store: &HashMap<String, Task>,
+        id: &str,
+        outputs: &[PathBuf],
+    ) -> Result<(), Error> {
+        if store.contains_key(id) {
+            return Err(Error::task_existing(id));
+        }
+        check_capacity(store.len(), self.max_tasks)?;
+
+        // two tasks writing to a same output corrupt each other
+        let existing = store.values().map(|task| task.data.output_paths.as_slice());
+        if let Some(path) = find_output_in_use(outputs, existing) {
+            return Err(Error::output_in_use(path.to_string_lossy()));
+        }
+
+        Ok(())
+    }
+
+    /// Resets an ended task by id, which is stopped, finished or errored,
+    /// and adds it back to store in held state, see [`TaskState::reset`](super::state_machine::TaskState::reset).
+    ///
+    /// Task starts again with the same args once resumed by [`TaskStore::resume`].
+    /// It is held rather than idle, since an idle task takes a running slot and is about to spawn ffmpeg,
+    /// see [`takes_slot`], while a held one waits the same as a task added by [`TaskStore::start_paused`].
+    /// A held message is sent then, so that frontend is told the state pausing and resuming report.
+    ///
+    /// Only tasks kept as ended could be reset, see [`MAX_ENDED_TASKS`].
+    pub async fn reset(&self, id: &str) -> Result<(), Error> {
+        let mut ended = self.ended.lock().await;
+        let Some(task) = ended.get(id) else {
+            return Err(Error::task_not_found(id));
+        };
+        // file descriptors are closed already, they can't be written again
+        if task.data.args.writes_to_fds() {
+            return Err(Error::output_fd_not_reusable(id));
+        }
+        let task = ended.remove(id).unwrap(); // safely unwrap
+        drop(ended);
+
+        task.reset().await;
 
-        // drops store immediately
+        let mut store = self.store.lock().await;
+        if let Err(err) = self.check_insertable(&store, id, &task.data.output_paths) {
+            drop(store);
+            keep_ended(&mut *self.ended.lock().await, task);
+            return Err(err);
+        }
+        store.insert(id.to_string(), task.clone());
         drop(store);
 
-        task.start().await;
+        task.send_lifecycle(TaskLifecycleEvent::new(
+            TaskLifecycleKind::Created,
+            &task.data.id,
+        ));
+        if let Err(err) = task
+            .data
+            .app_handle
+            .emit_all(TASK_MESSAGE_EVENT, TaskMessage::held(id.to_string()))
+        {
+            warn!("[{}] failed to send message to frontend: {}", id, err);
+        }
+
+        Ok(())
+    }
+
+    /// Starts sweeping inconsistent tasks every [`SWEEP_INTERVAL`], until store dropped.
+    ///
+    /// Tasks are inconsistent if they are ended but still in store,
+    /// or running while their watchdogs have exited, such as a watchdog panicked,
+    /// see [`find_reaping`].
+    /// Since tasks are inconsistent transiently during state transitions,
+    /// they are only reaped if found inconsistent in two sweeps in a row.
+    pub fn start_sweeper(&self) {
+        let store = Arc::downgrade(&self.store);
+        tauri::async_runtime::spawn(async move {
+            let mut suspects = HashSet::new();
+            loop {
+                tokio::time::sleep(SWEEP_INTERVAL).await;
+                let Some(store) = store.upgrade() else {
+                    break;
+                };
+                suspects = Self::sweep(&store, &suspects).await;
+            }
+        });
+    }
+
+    /// Reaps inconsistent tasks found in previous sweep as well and returns ids of inconsistent tasks.
+    async fn sweep(
+        store: &Mutex<HashMap<String, Task>>,
+        suspects: &HashSet<String>,
+    ) -> HashSet<String> {
+        let tasks = store.lock().await.values().cloned().collect::<Vec<_>>();
+
+        let mut inconsistents = HashSet::new();
+        for task in tasks {
+            let (code, watchdog_exited, exit_status) = match task.state.lock().await.as_ref() {
+                Some(state) => (state.code(), state.watchdog_exited(), state.exit_status()),
+                None => continue,
+            };
+            let Some(reaping) = find_reaping(code, watchdog_exited, exit_status) else {
+                continue;
+            };
+
+            let id = &task.data.id;
+            if !suspects.contains(id) {
+                inconsistents.insert(id.clone());
+                continue;
+            }
+
+            match reaping {
+                Reaping::Finish => {
+                    warn!(
+                        "[{}] watchdog exited after ffmpeg exited successfully, finished",
+                        id
+                    );
+                    task.finish(Vec::new()).await;
+                }
+                Reaping::Error => {
+                    warn!("[{}] watchdog exited but task is still running, reaped", id);
+                    task.error("watchdog exited unexpectedly".to_string()).await;
+                }
+                Reaping::Remove => {
+                    warn!("[{}] task ended but still in store, reaped", id);
+                    let mut store = store.lock().await;
+                    // task with the same id may be restarted already
+                    if store
+                        .get(id)
+                        .map(|existing| Arc::ptr_eq(&existing.data, &task.data))
+                        .unwrap_or(false)
+                    {
+                        store.remove(id);
+                    }
+                }
+            }
+        }
+
+        inconsistents
+    }
+
+    /// Applies low priority to processes of all running and pausing tasks if `background` is `true`,
+    /// or normal priority otherwise.
+    ///
+    /// Failures are sent to frontend as warnings of tasks, since restoring normal priority
+    /// usually fails without privileges, leaving processes running in low priority.
+    pub async fn apply_priority(&self, background: bool) {
+        let tasks = self
+            .store
+            .lock()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for task in tasks {
+            let Some(pid) = task
+                .state
+                .lock()
+                .await
+                .as_ref()
+                .and_then(|state| state.pid())
+            else {
+                continue;
+            };
+
+            if let Err(err) = apply_priority(pid, background) {
+                let reason = if background {
+                    format!("failed to apply low priority: {err}")
+                } else {
+                    format!(
+                        "failed to restore normal priority, still running in low priority: {err}"
+                    )
+                };
+                warn!("[{}] {}", task.data.id, reason);
+                if let Err(err) = task.data.app_handle.emit_all(
+                    TASK_MESSAGE_EVENT,
+                    TaskMessage::warning(task.data.id.to_string(), vec![reason]),
+                ) {
+                    warn!(
+                        "[{}] failed to send message to frontend: {}",
+                        task.data.id, err
+                    );
+                }
+            }
+        }
+    }
+
+    /// Returns amount of active tasks, which are queued, idle, running or pausing.
+    pub async fn count(&self) -> usize {
+        self.store.lock().await.len()
+    }
+
+    /// Stops all tasks and discards all not yet started tasks.
+    ///
+    /// Each task is removed the same as stopped one by one, emitting `task.removed` and kept as ended.
+    /// Not yet started tasks are discarded before any started one is stopped, see [`Clearing`],
+    /// so that no task in store when clearing is started by a released running slot.
+    pub async fn clear_all(&self) -> ClearResult {
+        let tasks = self
+            .store
+            .lock()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut clearings = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            let code = task.state.lock().await.as_ref().map(|state| state.code());
+            if let Some(clearing) = find_clearing(code) {
+                clearings.push((clearing, task));
+            }
+        }
+        clearings.sort_by_key(|(clearing, _)| *clearing);
+
+        let mut result = ClearResult {
+            stopped: 0,
+            dequeued: 0,
+        };
+        for (clearing, task) in clearings {
+            match clearing {
+                Clearing::Dequeue => result.dequeued += 1,
+                Clearing::Stop => result.stopped += 1,
+            }
+            task.stop(StopReason::BatchAborted).await;
+        }
+
+        result
+    }
+
+    /// Stops a task by id.
+    pub async fn stop(&self, id: &str, reason: StopReason) -> Result<(), Error> {
+        let store = self.store.lock().await;
+        let Some(task) = store.get(id) else {
+            return Err(Error::task_not_found(id));
+        };
+
+        let task = task.clone();
+        drop(store);
+
+        task.stop(reason).await;
+        Ok(())
+    }
+
+    /// Stops all tasks currently in a state, returning ids of tasks stopped.
+    ///
+    /// Only tasks in store, which are queued, held, idle, running or pausing, could be stopped.
+    pub async fn stop_where(
+        &self,
+        state: TaskStateCode,
+        reason: StopReason,
+    ) -> Result<Vec<String>, Error> {
+        match state {
+            TaskStateCode::Queued
+            | TaskStateCode::Held
+            | TaskStateCode::Idle
+            | TaskStateCode::Running
+            | TaskStateCode::Pausing => {}
+            TaskStateCode::Stopped | TaskStateCode::Finished | TaskStateCode::Errored => {
+                return Err(Error::task_state_not_actionable(format!("{:?}", state)))
+            }
+        };
+
+        let tasks = self
+            .store
+            .lock()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut stopped = Vec::new();
+        for task in tasks {
+            let code = task.state.lock().await.as_ref().map(|state| state.code());
+            if code != Some(state) {
+                continue;
+            }
+
+            task.stop(reason).await;
+            stopped.push(task.data.id.clone());
+        }
+
+        Ok(stopped)
+    }
+
+    /// Replaces args of a task not yet running by id, which is queued, held or idle.
+    /// Task delaying for retrying is rejected, since its pending retry holds the old args.
+    ///
+    /// Task entry in store is replaced by a new one with the new args, sharing state with the old one.
+    pub async fn update_args(&self, id: &str, args: TaskArgs) -> Result<(), Error> {
+        // resolves before locking anything, since resolving touches file system
+        let output_paths = resolved_output_paths(&args);
+
+        let store = self.store.lock().await;
+        let Some(task) = store.get(id) else {
+            return Err(Error::task_not_found(id));
+        };
+
+        let task = task.clone();
+        drop(store);
+
+        // state is held during replacing, so that task never starts with the old args
+        let state = task.state.lock().await;
+        let code = state.as_ref().map(|state| state.code());
+        if !is_args_updatable(code, task.data.retries.load(Ordering::SeqCst)) {
+            return Err(Error::task_already_running(id));
+        }
+
+        let mut store = self.store.lock().await;
+        // task may be removed or replaced already
+        if !store
+            .get(id)
+            .map(|existing| Arc::ptr_eq(&existing.data, &task.data))
+            .unwrap_or(false)
+        {
+            return Err(Error::task_not_found(id));
+        }
+
+        // two tasks writing to a same output corrupt each other
+        let others = store
+            .values()
+            .filter(|existing| existing.data.id != id)
+            .map(|existing| existing.data.output_paths.as_slice());
+        if let Some(path) = find_output_in_use(&output_paths, others) {
+            return Err(Error::output_in_use(path.to_string_lossy()));
+        }
+
+        // task owns output file descriptors, closes old ones no longer used by the new args
+        #[cfg(all(unix, feature = "fd-output"))]
+        super::fd_output::close_replaced_output_fds(id, &task.data.args, &args);
+
+        let data = &task.data;
+        let updated = Task {
+            data: Arc::new(TaskData {
+                id: data.id.clone(),
+                ffmpeg_program: data.ffmpeg_program.clone(),
+                ffprobe_program: data.ffprobe_program.clone(),
+                output_paths,
+                args,
+                app_handle: data.app_handle.clone(),
+                retries: AtomicUsize::new(0),
+                created_at: data.created_at,
+                loudnorm_filter: OnceLock::new(),
+                pending_warnings: Mutex::new(Vec::new()),
+            }),
+            state: Arc::clone(&task.state),
+            store: task.store.clone(),
+            ended: task.ended.clone(),
+            scheduler: task.scheduler.clone(),
+        };
+        store.insert(id.to_string(), updated);
+        drop(store);
+        drop(state);
+
+        Ok(())
+    }
+
+    /// Restarts a task by id, either in store or ended.
+    /// Task in store is stopped first and then a new task with the same id and args starts,
+    /// replacing the ended one.
+    pub async fn restart(&self, id: &str) -> Result<(), Error> {
+        let running = self.store.lock().await.get(id).cloned();
+        let (task, in_store) = match running {
+            Some(task) => (task, true),
+            None => match self.ended.lock().await.get(id).cloned() {
+                Some(task) => (task, false),
+                None => return Err(Error::task_not_found(id)),
+            },
+        };
+
+        // file descriptors are closed once stopped, they can't be written again
+        if task.data.args.writes_to_fds() {
+            return Err(Error::output_fd_not_reusable(id));
+        }
+
+        // stopping reaps child process and removes task from store.
+        // if another task with the same id is added during stopping, starting fails as existing.
+        if in_store {
+            task.stop(StopReason::Restarting).await;
+        }
+
+        let data = &task.data;
+        self.start(
+            data.id.clone(),
+            data.args.clone(),
+            data.app_handle.clone(),
+            data.ffmpeg_program.clone(),
+            data.ffprobe_program.clone(),
+        )
+        .await?;
+
+        // ended task is replaced by the restarted one, so that resetting never revives it.
+        // it is kept if starting fails, so that it could still be restarted or reset later
+        let mut ended = self.ended.lock().await;
+        if ended
+            .get(id)
+            .map(|existing| Arc::ptr_eq(&existing.data, &task.data))
+            .unwrap_or(false)
+        {
+            ended.remove(id);
+        }
+
         Ok(())
     }
 
     operations! {
-        (
-            /// Stops a task by id.
-            stop
-        ),
         (
             /// Pauses a task by id.
-            pause
+            pause,
+            TaskStateCode::Pausing
         ),
         (
             /// Resumes a task by id.
-            resume
+            resume,
+            TaskStateCode::Running
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::{error::Error, tasks::state_machine::TaskStateCode};
+
+    use std::path::PathBuf;
+
+    use super::{
+        check_capacity, drop_oldest, find_clearing, find_output_in_use, find_reaping,
+        is_args_updatable, is_startable, takes_slot, Clearing, Reaping, TaskStore, MAX_ENDED_TASKS,
+    };
+
+    #[test]
+    fn updates_args_of_not_started_tasks() {
+        assert!(is_args_updatable(Some(TaskStateCode::Queued), 0));
+        assert!(is_args_updatable(Some(TaskStateCode::Held), 0));
+        assert!(is_args_updatable(Some(TaskStateCode::Idle), 0));
+    }
+
+    #[test]
+    fn rejects_updating_args_of_retrying_tasks() {
+        assert!(!is_args_updatable(Some(TaskStateCode::Queued), 1));
+    }
+
+    #[test]
+    fn rejects_updating_args_of_started_tasks() {
+        for code in [
+            TaskStateCode::Running,
+            TaskStateCode::Pausing,
+            TaskStateCode::Stopped,
+            TaskStateCode::Finished,
+            TaskStateCode::Errored,
+        ] {
+            assert!(!is_args_updatable(Some(code), 0));
+        }
+        assert!(!is_args_updatable(None, 0));
+    }
+
+    #[test]
+    fn finds_output_in_use() {
+        let outputs = vec![PathBuf::from("/out/a.mp4"), PathBuf::from("/out/b.mp4")];
+        let first = vec![PathBuf::from("/out/c.mp4")];
+        let second = vec![PathBuf::from("/out/b.mp4")];
+        assert_eq!(
+            find_output_in_use(&outputs, [first.as_slice(), second.as_slice()]),
+            Some(&outputs[1])
+        );
+        assert_eq!(find_output_in_use(&outputs, [first.as_slice()]), None);
+        assert_eq!(find_output_in_use(&[], [second.as_slice()]), None);
+    }
+
+    #[test]
+    fn rejects_tasks_beyond_capacity() {
+        assert!(check_capacity(0, Some(2)).is_ok());
+        assert!(check_capacity(1, Some(2)).is_ok());
+        assert!(matches!(
+            check_capacity(2, Some(2)),
+            Err(Error::QueueFull { max: 2 })
+        ));
+        assert!(check_capacity(usize::MAX, None).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finishes_running_tasks_exited_successfully() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let succeeded = std::process::ExitStatus::from_raw(0);
+        let failed = std::process::ExitStatus::from_raw(1 << 8);
+        let running = TaskStateCode::Running;
+        assert_eq!(
+            find_reaping(running, true, Some(succeeded)),
+            Some(Reaping::Finish)
+        );
+        assert_eq!(
+            find_reaping(running, true, Some(failed)),
+            Some(Reaping::Error)
+        );
+        assert_eq!(find_reaping(running, true, None), Some(Reaping::Error));
+        assert_eq!(find_reaping(running, false, Some(succeeded)), None);
+    }
+
+    #[test]
+    fn removes_ended_tasks() {
+        for code in [
+            TaskStateCode::Stopped,
+            TaskStateCode::Finished,
+            TaskStateCode::Errored,
+        ] {
+            assert_eq!(find_reaping(code, true, None), Some(Reaping::Remove));
+        }
+        for code in [
+            TaskStateCode::Queued,
+            TaskStateCode::Held,
+            TaskStateCode::Idle,
+            TaskStateCode::Pausing,
+        ] {
+            assert_eq!(find_reaping(code, true, None), None);
+        }
+    }
+
+    #[test]
+    fn never_starts_held_tasks() {
+        assert!(!is_startable(Some(TaskStateCode::Held)));
+        assert!(!takes_slot(Some(TaskStateCode::Held)));
+
+        // resuming a held task queues it, and it is started then
+        assert!(is_startable(Some(TaskStateCode::Queued)));
+        assert!(!is_startable(Some(TaskStateCode::Stopped)));
+        assert!(!is_startable(None));
+        assert!(takes_slot(Some(TaskStateCode::Idle)));
+        assert!(!takes_slot(Some(TaskStateCode::Queued)));
+    }
+
+    #[test]
+    fn clears_not_started_tasks_first() {
+        use TaskStateCode::*;
+
+        assert_eq!(find_clearing(Some(Queued)), Some(Clearing::Dequeue));
+        assert_eq!(find_clearing(Some(Held)), Some(Clearing::Dequeue));
+        // an idle task probing inputs or spawning ffmpeg takes a running slot already
+        for code in [Idle, Running, Pausing] {
+            assert_eq!(find_clearing(Some(code)), Some(Clearing::Stop), "{code:?}");
+        }
+        for code in [Some(Stopped), Some(Finished), Some(Errored), None] {
+            assert_eq!(find_clearing(code), None, "{code:?}");
+        }
+
+        let mut clearings = vec![Clearing::Stop, Clearing::Dequeue, Clearing::Stop];
+        clearings.sort();
+        assert_eq!(
+            clearings,
+            vec![Clearing::Dequeue, Clearing::Stop, Clearing::Stop]
+        );
+    }
+
+    #[test]
+    fn drops_oldest_ended_tasks() {
+        let mut ended = (0..4)
+            .map(|created_at| (created_at.to_string(), created_at))
+            .collect::<std::collections::HashMap<_, _>>();
+        drop_oldest(&mut ended, 2, |created_at| *created_at);
+
+        let mut ids = ended.keys().map(String::as_str).collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, vec!["2", "3"]);
+
+        // nothing is dropped within limit
+        drop_oldest(&mut ended, MAX_ENDED_TASKS, |created_at| *created_at);
+        assert_eq!(ended.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rejects_resetting_task_not_kept_as_ended() {
+        // a task never added, or dropped from ended tasks already
+        let store = TaskStore::new();
+        assert!(matches!(
+            store.reset("dropped").await,
+            Err(Error::TaskNotFound { id }) if id == "dropped"
+        ));
+        assert_eq!(store.count().await, 0);
+    }
+}
@@ -1,18 +1,34 @@
 use std::{
     collections::HashMap,
-    sync::{Arc, Weak},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock, Weak,
+    },
+    time::{Duration, SystemTime},
 };
 
-use log::{error, info};
+use log::{error, info, warn};
 use tauri::Manager;
 use tokio::sync::Mutex;
 
 use crate::handlers::{
     commands::task::TaskArgs,
-    tasks::message::{TaskMessage, TASK_MESSAGE_EVENT},
+    tasks::{
+        history::{unix_millis, TaskHistory, TaskReport, TaskResult},
+        message::{
+            FinalStats, StopReason, TaskLifecycleEvent, TaskLifecycleKind, TaskMessage,
+            TASK_LIFECYCLE_EVENT, TASK_MESSAGE_EVENT,
+        },
+    },
 };
 
-use super::state_machine::{Idle, TaskState};
+use super::{
+    cleanup::{delete_sources, finalize_partial_outputs, remove_partial_outputs},
+    state_machine::{Held, Queued, TaskState, TaskStateCode},
+    store::{keep_ended, Scheduler},
+    validation::resolved_output_paths,
+};
 
 /// Task data.
 pub struct TaskData {
@@ -20,7 +36,18 @@ pub struct TaskData {
     pub ffmpeg_program: String,
     pub ffprobe_program: String,
     pub args: TaskArgs,
+    /// Output paths of args resolved once created, for checking outputs in use without touching file system,
+    /// see [`resolved_output_paths`].
+    pub output_paths: Vec<PathBuf>,
     pub app_handle: tauri::AppHandle,
+    /// Amount of retries attempted.
+    pub retries: AtomicUsize,
+    pub created_at: SystemTime,
+    /// `loudnorm` filter applying measured loudness, set once measured,
+    /// see [`loudnorm`](super::loudnorm).
+    pub loudnorm_filter: OnceLock<String>,
+    /// Deprecation warnings collected from stderr, kept across pauses and sent once task finishes.
+    pub pending_warnings: Mutex<Vec<String>>,
 }
 
 /// Task Item.
@@ -29,10 +56,15 @@ pub struct Task {
     pub data: Arc<TaskData>,
     pub state: Arc<Mutex<Option<Box<dyn TaskState>>>>,
     pub store: Weak<Mutex<HashMap<String, Task>>>,
+    /// Ended tasks kept for resetting, see [`TaskState::reset`].
+    pub ended: Weak<Mutex<HashMap<String, Task>>>,
+    pub scheduler: Weak<Scheduler>,
 }
 
 impl Task {
     /// Creates a new task item.
+    ///
+    /// Output paths are resolved on file system here, hence never creates a task while holding store.
     pub fn new(
         id: String,
         app_handle: tauri::AppHandle,
@@ -40,17 +72,26 @@ impl Task {
         ffprobe_program: String,
         args: TaskArgs,
         store: Weak<Mutex<HashMap<String, Task>>>,
+        ended: Weak<Mutex<HashMap<String, Task>>>,
+        scheduler: Weak<Scheduler>,
     ) -> Self {
         Self {
             data: Arc::new(TaskData {
                 id,
                 ffmpeg_program,
                 ffprobe_program,
+                output_paths: resolved_output_paths(&args),
                 args,
                 app_handle,
+                retries: AtomicUsize::new(0),
+                created_at: SystemTime::now(),
+                loudnorm_filter: OnceLock::new(),
+                pending_warnings: Mutex::new(Vec::new()),
             }),
-            state: Arc::new(Mutex::new(Some(Box::new(Idle)))),
+            state: Arc::new(Mutex::new(Some(Box::new(Queued)))),
             store,
+            ended,
+            scheduler,
         }
     }
 }
@@ -72,15 +113,37 @@ impl Task {
         (to_pause, pause),
         (to_resume, resume),
         (to_stop, stop),
-        (to_finish, finish)
+        (to_finish, finish),
+        (to_reset, reset)
     }
 
     async fn remove(&self) {
+        // task owns output file descriptors, closes them once ended
+        #[cfg(all(unix, feature = "fd-output"))]
+        super::fd_output::close_output_fds(&self.data.id, &self.data.args);
+
         // removes task from store
         let Some(store) = self.store.upgrade() else {
             return;
         };
-        store.lock().await.remove(&self.data.id);
+        let removed = store.lock().await.remove(&self.data.id);
+        if let Some(removed) = removed {
+            self.send_lifecycle(TaskLifecycleEvent::new(
+                TaskLifecycleKind::Removed,
+                &self.data.id,
+            ));
+
+            // keeps ended task, so that it could be reset later
+            if let Some(ended) = self.ended.upgrade() {
+                keep_ended(&mut *ended.lock().await, removed);
+            }
+
+            // a slot of running tasks may be released, starts next waiting task.
+            // spawned since task may still hold its state here
+            if let Some(scheduler) = self.scheduler.upgrade() {
+                tokio::spawn(async move { scheduler.start_next().await });
+            }
+        }
     }
 
     fn send_message(&self, payload: TaskMessage<'_>) {
@@ -93,36 +156,221 @@ impl Task {
         }
     }
 
+    /// Sends a structured lifecycle event to listeners.
+    pub fn send_lifecycle(&self, event: TaskLifecycleEvent<'_>) {
+        if let Err(err) = self.data.app_handle.emit_all(TASK_LIFECYCLE_EVENT, event) {
+            error!(
+                "[{}] failed to send lifecycle event {:?}: {}",
+                self.data.id, event.event, err
+            );
+        }
+    }
+
+    /// Sends a lifecycle event of `kind` if task is in `code` state now.
+    async fn send_lifecycle_if(&self, code: TaskStateCode, kind: TaskLifecycleKind) {
+        let current = self.state.lock().await.as_ref().map(|state| state.code());
+        if current == Some(code) {
+            self.send_lifecycle(TaskLifecycleEvent::new(kind, &self.data.id));
+        }
+    }
+
+    /// Appends a report of this completed task to history, if history is managed.
+    fn record_history(&self, result: TaskResult, final_stats: Vec<FinalStats>) {
+        let Some(history) = self.data.app_handle.try_state::<TaskHistory>() else {
+            return;
+        };
+
+        history.append(TaskReport {
+            id: self.data.id.clone(),
+            args: self.data.args.clone(),
+            result,
+            created_at: unix_millis(self.data.created_at),
+            completed_at: unix_millis(SystemTime::now()),
+            final_stats,
+        });
+    }
+
+    /// Moves an ended task back to held state, resetting its retries.
+    pub async fn reset(&self) {
+        self.to_reset().await;
+        self.data.retries.store(0, Ordering::SeqCst);
+        info!("[{}] task reset", self.data.id);
+    }
+
     pub async fn start(&self) {
         self.to_start().await;
+        self.send_lifecycle_if(TaskStateCode::Running, TaskLifecycleKind::Started)
+            .await;
         info!("[{}] task started", self.data.id);
     }
 
+    /// Starts next phase of a task running ffmpeg several times, such as normalizing loudness.
+    /// Task keeps its running slot and no lifecycle event is sent, since it never stopped running.
+    pub async fn next_phase(&self) {
+        let mut state = self.state.lock().await;
+        // stopped or errored while finishing the previous phase
+        if state.as_ref().map(|state| state.code()) != Some(TaskStateCode::Running) {
+            return;
+        }
+        *state = Some(state.take().unwrap().start(self.clone()).await);
+
+        let reason = state
+            .as_ref()
+            .filter(|state| state.code() == TaskStateCode::Errored)
+            .and_then(|state| state.message())
+            .map(|reason| reason.to_string());
+        drop(state);
+
+        // next phase fails to start, such as ffmpeg not found
+        if let Some(reason) = reason {
+            self.error(reason).await;
+        } else {
+            info!("[{}] task next phase started", self.data.id);
+        }
+    }
+
+    /// Holds a queued task without starting it, until started or resumed explicitly.
+    pub async fn hold(&self) {
+        let mut state = self.state.lock().await;
+        if state.as_ref().map(|state| state.code()) != Some(TaskStateCode::Queued) {
+            warn!("[{}] attempting to hold a not queued task", self.data.id);
+            return;
+        }
+        *state = Some(Box::new(Held));
+        drop(state);
+
+        self.send_message(TaskMessage::held(self.data.id.clone()));
+        info!("[{}] task held", self.data.id);
+    }
+
     pub async fn pause(&self) {
         self.to_pause().await;
+        self.send_lifecycle_if(TaskStateCode::Pausing, TaskLifecycleKind::Paused)
+            .await;
         info!("[{}] task started", self.data.id);
     }
 
     pub async fn resume(&self) {
+        // a held task never started before, it is queued and started by scheduler rather than resumed
+        let held =
+            self.state.lock().await.as_ref().map(|state| state.code()) == Some(TaskStateCode::Held);
         self.to_resume().await;
-        info!("[{}] task started", self.data.id);
+        if held {
+            self.enqueue().await;
+            return;
+        }
+        self.send_lifecycle_if(TaskStateCode::Running, TaskLifecycleKind::Resumed)
+            .await;
+        info!("[{}] task resumed", self.data.id);
     }
 
-    pub async fn stop(&self) {
+    /// Puts a queued task to scheduler, it starts once a running slot is available.
+    async fn enqueue(&self) {
+        match self.scheduler.upgrade() {
+            Some(scheduler) => scheduler.start_all(vec![self.data.id.clone()]).await,
+            None => warn!("[{}] scheduler dropped, task never starts", self.data.id),
+        }
+    }
+
+    pub async fn stop(&self, reason: StopReason) {
         self.to_stop().await;
+        remove_partial_outputs(&self.data.id, &self.data.args).await;
         self.remove().await;
-        info!("[{}] task stopped", self.data.id);
+        self.send_message(TaskMessage::stopped(self.data.id.clone(), reason));
+        info!("[{}] task stopped: {:?}", self.data.id, reason);
     }
 
-    pub async fn finish(&self) {
+    pub async fn finish(&self, final_stats: Vec<FinalStats>) {
+        // checked by state rather than dry run flag, which may be toggled while task is running
+        let simulated = self
+            .state
+            .lock()
+            .await
+            .as_ref()
+            .map(|state| state.is_simulated())
+            .unwrap_or(false);
         self.to_finish().await;
+
+        // task errors instead of finishing if outputs are verified empty
+        let (code, reason) = match self.state.lock().await.as_ref() {
+            Some(state) => (Some(state.code()), state.message().map(|m| m.to_string())),
+            None => (None, None),
+        };
+
+        // sources are deleted only if task truly finished,
+        // simulated tasks never touch any file
+        if !simulated {
+            if code == Some(TaskStateCode::Finished) {
+                finalize_partial_outputs(&self.data.id, &self.data.args).await;
+                delete_sources(&self.data.id, &self.data.args).await;
+            } else {
+                remove_partial_outputs(&self.data.id, &self.data.args).await;
+            }
+        }
+
         self.remove().await;
-        self.send_message(TaskMessage::finished(self.data.id.clone()));
-        info!("[{}] task finished", self.data.id);
+        match (code, reason) {
+            (Some(TaskStateCode::Errored), Some(reason)) => {
+                info!("[{}] task errored: {}", self.data.id, reason);
+                self.record_history(
+                    TaskResult::Errored {
+                        reason: reason.clone(),
+                    },
+                    final_stats,
+                );
+                self.send_lifecycle(TaskLifecycleEvent::errored(&self.data.id, &reason));
+                self.send_message(TaskMessage::errored(self.data.id.clone(), reason));
+            }
+            _ => {
+                self.record_history(TaskResult::Finished, final_stats.clone());
+                self.send_lifecycle(TaskLifecycleEvent::new(
+                    TaskLifecycleKind::Finished,
+                    &self.data.id,
+                ));
+                self.send_message(TaskMessage::finished(self.data.id.clone(), final_stats));
+                info!("[{}] task finished", self.data.id);
+            }
+        }
+    }
+
+    /// Returns delay before next retry and increases retries,
+    /// or `None` if no retry remains.
+    ///
+    /// Tasks writing to output file descriptors are never retried,
+    /// since ffmpeg may have written to them partially already.
+    fn next_retry_delay(&self) -> Option<Duration> {
+        let args = &self.data.args;
+        if args.writes_to_fds() {
+            return None;
+        }
+        let retries = self.data.retries.fetch_add(1, Ordering::SeqCst);
+        if retries >= args.max_retries {
+            self.data.retries.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+
+        Some(args.backoff.delay(args.retry_delay_ms, retries + 1))
+    }
+
+    /// Starts task again once delay elapsed, unless task is stopped during delaying.
+    fn retry(&self, delay: Duration) {
+        let task = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+
+            let queued = task.state.lock().await.as_ref().map(|state| state.code())
+                == Some(TaskStateCode::Queued);
+            if queued {
+                task.enqueue().await;
+            } else {
+                info!("[{}] task retry cancelled", task.data.id);
+            }
+        });
     }
 
     pub async fn error(&self, reason: String) {
         let mut state = self.state.lock().await;
+        let previous = state.as_ref().map(|state| state.code());
         *state = Some(
             state
                 .take()
@@ -133,7 +381,45 @@ impl Task {
 
         info!("[{}] task errored: {}", self.data.id, reason);
 
+        remove_partial_outputs(&self.data.id, &self.data.args).await;
+
+        // only tasks failing during running are retried,
+        // they wait in queued state and stay in store during delaying
+        let failed_running = matches!(
+            previous,
+            Some(TaskStateCode::Running) | Some(TaskStateCode::Pausing)
+        ) && state.as_ref().map(|state| state.code())
+            == Some(TaskStateCode::Errored);
+        if let Some(delay) = failed_running.then(|| self.next_retry_delay()).flatten() {
+            *state = Some(Box::new(Queued));
+            drop(state);
+
+            let warning = format!(
+                "retrying in {}ms, attempt {} of {}: {}",
+                delay.as_millis(),
+                self.data.retries.load(Ordering::SeqCst),
+                self.data.args.max_retries,
+                reason
+            );
+            info!("[{}] {}", self.data.id, warning);
+            self.send_message(TaskMessage::warning(self.data.id.clone(), vec![warning]));
+            self.retry(delay);
+
+            // a delaying task releases its running slot, starts next waiting task
+            if let Some(scheduler) = self.scheduler.upgrade() {
+                tokio::spawn(async move { scheduler.start_next().await });
+            }
+            return;
+        }
+
         self.remove().await;
+        self.record_history(
+            TaskResult::Errored {
+                reason: reason.clone(),
+            },
+            vec![],
+        );
+        self.send_lifecycle(TaskLifecycleEvent::errored(&self.data.id, &reason));
         self.send_message(TaskMessage::errored(self.data.id.clone(), reason));
     }
 }
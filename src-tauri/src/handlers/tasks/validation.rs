@@ -0,0 +1,1206 @@
+use std::{
+    fmt::Display,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::handlers::{
+    commands::{
+        probe::ProbeStream,
+        task::{TaskArgs, TaskInputArgs, TaskOutputArgs},
+    },
+    error::Error,
+};
+
+/// Reasons of a field of [`TaskArgs`] being invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum ValidationReason {
+    /// List requires at least one item, such as `inputs` and `outputs`.
+    Empty,
+    /// Path is empty or blank.
+    EmptyPath,
+}
+
+/// Error of a single field of [`TaskArgs`],
+/// field is pointed by a path such as `outputs[0].path`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ValidationError {
+    pub field: String,
+    pub reason: ValidationReason,
+}
+
+impl ValidationError {
+    fn new<S: Into<String>>(field: S, reason: ValidationReason) -> Self {
+        Self {
+            field: field.into(),
+            reason,
+        }
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.reason {
+            ValidationReason::Empty => {
+                f.write_fmt(format_args!("{} requires at least one item", self.field))
+            }
+            ValidationReason::EmptyPath => {
+                f.write_fmt(format_args!("{} is an empty path", self.field))
+            }
+        }
+    }
+}
+
+/// Validates shape of args, collecting errors of all invalid fields:
+///
+/// - At least one input and at least one output.
+/// - Input paths are not empty.
+/// - Output paths are either non-empty or `None` for null output.
+/// - Progress log path is not empty if specified.
+pub fn validate_shape(args: &TaskArgs) -> Result<(), Vec<ValidationError>> {
+    let is_blank = |path: &str| path.trim().is_empty();
+
+    let mut errors = Vec::new();
+    if args.inputs.is_empty() {
+        errors.push(ValidationError::new("inputs", ValidationReason::Empty));
+    }
+    if args.outputs.is_empty() {
+        errors.push(ValidationError::new("outputs", ValidationReason::Empty));
+    }
+    for (i, input) in args.inputs.iter().enumerate() {
+        if is_blank(&input.path) {
+            errors.push(ValidationError::new(
+                format!("inputs[{i}].path"),
+                ValidationReason::EmptyPath,
+            ));
+        }
+    }
+    for (i, output) in args.outputs.iter().enumerate() {
+        if output.path.as_deref().map(is_blank).unwrap_or(false) {
+            errors.push(ValidationError::new(
+                format!("outputs[{i}].path"),
+                ValidationReason::EmptyPath,
+            ));
+        }
+    }
+    if args.progress_log.as_deref().map(is_blank).unwrap_or(false) {
+        errors.push(ValidationError::new(
+            "progress_log",
+            ValidationReason::EmptyPath,
+        ));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Formats that read from devices or virtual sources rather than files.
+static NON_FILE_INPUT_FORMATS: [&'static str; 12] = [
+    "lavfi",
+    "dshow",
+    "vfwcap",
+    "gdigrab",
+    "avfoundation",
+    "v4l2",
+    "video4linux2",
+    "x11grab",
+    "kmsgrab",
+    "alsa",
+    "pulse",
+    "openal",
+];
+
+/// Returns `true` if input is a file on local disk.
+///
+/// Inputs are not regarded as files if:
+///
+/// - input args specify a device or virtual format by `-f`, such as `lavfi`.
+/// - input path starts with a protocol scheme, such as `http://` or `pipe:`.
+/// - input path is `-`, reading from stdin.
+pub fn is_file_input(input: &TaskInputArgs) -> bool {
+    if input.path == "-" {
+        return false;
+    }
+
+    let mut args = input.args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-f" {
+            if let Some(format) = args.next() {
+                if NON_FILE_INPUT_FORMATS.iter().any(|f| *f == format.as_str()) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    !has_protocol_scheme(&input.path)
+}
+
+/// Returns `true` if any input reads from stdin by `-`, `pipe:` or `pipe:0`.
+///
+/// Stdin of such tasks is inherited from the app rather than piped,
+/// so it conflicts with pausing by keystrokes through stdin on Windows.
+pub fn reads_stdin(args: &TaskArgs) -> bool {
+    args.inputs
+        .iter()
+        .any(|input| ["-", "pipe:", "pipe:0"].contains(&input.path.as_str()))
+}
+
+/// Returns `true` if path starts with a protocol scheme, such as `http://` and `pipe:`.
+pub fn has_protocol_scheme(path: &str) -> bool {
+    protocol_scheme(path).is_some()
+}
+
+/// Extracts protocol scheme from path, such as `http` from `http://` and `pipe` from `pipe:`.
+///
+/// Single letter schemes are never regarded as protocol,
+/// since they are drive letters on Windows.
+fn protocol_scheme(path: &str) -> Option<&str> {
+    let (scheme, _) = path.split_once(':')?;
+
+    let is_scheme = scheme.len() > 1
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+    if is_scheme {
+        Some(scheme)
+    } else {
+        None
+    }
+}
+
+/// Particulars of ffmpeg used for validating tasks.
+/// Validations relying on a particular are skipped if it is `None`.
+#[derive(Debug, Default)]
+pub struct ValidationContext {
+    /// Protocols supported by ffmpeg for inputs.
+    pub input_protocols: Option<Vec<String>>,
+    /// Hardware acceleration methods supported by ffmpeg.
+    pub hwaccels: Option<Vec<String>>,
+}
+
+/// Validates all file inputs exist and are not zero-byte files.
+///
+/// If `input_protocols` provided, protocol schemes of non-file inputs are validated as well.
+pub fn validate_inputs(args: &TaskArgs, input_protocols: Option<&[String]>) -> Result<(), Error> {
+    for input in args.inputs.iter() {
+        if is_file_input(input) {
+            let Ok(path) = PathBuf::from(&input.path).canonicalize() else {
+                return Err(Error::input_not_found(&input.path));
+            };
+            // devices and fifos always report zero length
+            if path
+                .metadata()
+                .map(|metadata| metadata.is_file() && metadata.len() == 0)
+                .unwrap_or(false)
+            {
+                return Err(Error::empty_or_invalid_input(&input.path));
+            }
+        } else if let (Some(scheme), Some(protocols)) =
+            (protocol_scheme(&input.path), input_protocols)
+        {
+            let scheme = scheme.to_lowercase();
+            if !protocols.iter().any(|protocol| *protocol == scheme) {
+                return Err(Error::unsupported_protocol(scheme));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Output container formats implied by file extensions.
+static EXTENSION_FORMATS: [(&'static str, &'static str); 15] = [
+    ("mp4", "mp4"),
+    ("m4v", "mp4"),
+    ("m4a", "mp4"),
+    ("mov", "mov"),
+    ("mkv", "matroska"),
+    ("mka", "matroska"),
+    ("webm", "webm"),
+    ("avi", "avi"),
+    ("ts", "mpegts"),
+    ("m2ts", "mpegts"),
+    ("mp3", "mp3"),
+    ("flac", "flac"),
+    ("wav", "wav"),
+    ("ogg", "ogg"),
+    ("gif", "gif"),
+];
+
+/// Formats specified by `-f` regarded as agreeing with a format implied by file extension,
+/// since they produce files of the same family, such as `ipod` muxing `.m4a`.
+static AGREEING_FORMATS: [(&'static str, &'static [&'static str]); 5] = [
+    ("mp4", &["mov", "ipod", "ismv", "f4v", "3gp"]),
+    ("mov", &["mp4"]),
+    ("matroska", &["webm"]),
+    ("webm", &["matroska"]),
+    ("ogg", &["opus", "oga", "ogv", "spx"]),
+];
+
+/// Formats specified by `-f` never checking file extension of output,
+/// since they write files of other formats or paths not being files, such as `segment` and `tee`.
+static META_FORMATS: [&'static str; 10] = [
+    "segment",
+    "ssegment",
+    "stream_segment",
+    "tee",
+    "hls",
+    "dash",
+    "image2",
+    "fifo",
+    "null",
+    "rtp_mpegts",
+];
+
+/// Codecs known to be incompatible with output container formats.
+/// Combinations not listed here are regarded as compatible.
+static INCOMPATIBLE_CODECS: [(&'static str, &'static [&'static str]); 6] = [
+    (
+        "mp4",
+        &[
+            "flac",
+            "pcm_s16le",
+            "pcm_s24le",
+            "pcm_s32le",
+            "subrip",
+            "srt",
+            "ass",
+            "ssa",
+            "dvdsub",
+            "dvd_subtitle",
+            "hdmv_pgs_subtitle",
+        ],
+    ),
+    (
+        "mov",
+        &["subrip", "srt", "ass", "ssa", "webvtt", "hdmv_pgs_subtitle"],
+    ),
+    (
+        "webm",
+        &[
+            "libx264",
+            "libx265",
+            "h264_nvenc",
+            "hevc_nvenc",
+            "h264_qsv",
+            "hevc_qsv",
+            "aac",
+            "libmp3lame",
+            "ac3",
+            "subrip",
+            "srt",
+            "ass",
+            "ssa",
+            "mov_text",
+        ],
+    ),
+    ("matroska", &["mov_text"]),
+    (
+        "avi",
+        &[
+            "libopus", "subrip", "srt", "ass", "ssa", "webvtt", "mov_text",
+        ],
+    ),
+    (
+        "mpegts",
+        &["subrip", "srt", "ass", "ssa", "webvtt", "mov_text"],
+    ),
+];
+
+/// Finds container format of an output,
+/// from `-f` argument first and then from file extension.
+pub fn output_format(output: &TaskOutputArgs) -> Option<String> {
+    explicit_format(output)
+        .map(|format| format.to_string())
+        .or_else(|| extension_format(output).map(|format| format.to_string()))
+}
+
+/// Finds container format of an output specified by `-f` argument.
+fn explicit_format(output: &TaskOutputArgs) -> Option<&str> {
+    let mut args = output.args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-f" {
+            if let Some(format) = args.next() {
+                return Some(format.as_str());
+            }
+        }
+    }
+
+    None
+}
+
+/// Finds container format of an output implied by file extension.
+fn extension_format(output: &TaskOutputArgs) -> Option<&'static str> {
+    let extension = output.path.as_ref().and_then(|path| {
+        PathBuf::from(path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+    })?;
+    EXTENSION_FORMATS
+        .iter()
+        .find(|(e, _)| *e == extension)
+        .map(|(_, format)| *format)
+}
+
+/// Validates format specified by `-f` of each output agrees with format implied by its file extension.
+///
+/// Outputs without `-f` or with extensions not listed in [`EXTENSION_FORMATS`] are never checked,
+/// nor are outputs of formats listed in [`META_FORMATS`].
+/// Formats listed in [`AGREEING_FORMATS`] are regarded as agreeing as well.
+pub fn validate_output_formats(args: &TaskArgs) -> Result<(), Error> {
+    for output in args.outputs.iter() {
+        let (Some(format), Some(implied)) = (explicit_format(output), extension_format(output))
+        else {
+            continue;
+        };
+        if format == implied || META_FORMATS.contains(&format) {
+            continue;
+        }
+
+        let agreeing = AGREEING_FORMATS
+            .iter()
+            .find(|(f, _)| *f == implied)
+            .map(|(_, agreeings)| agreeings.contains(&format))
+            .unwrap_or(false);
+        if !agreeing {
+            let path = output.path.as_deref().unwrap_or_default();
+            return Err(Error::output_format_mismatch(path, format, implied));
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds all codecs specified by `-c`, `-codec`, `-vcodec`, `-acodec` and `-scodec`
+/// (including stream specifiers, such as `-c:v:0`) of an output.
+/// Stream copy is ignored.
+pub fn output_codecs(output: &TaskOutputArgs) -> Vec<&str> {
+    codec_args(&output.args)
+}
+
+/// Finds all codecs specified by `-c`, `-codec`, `-vcodec`, `-acodec` and `-scodec`
+/// (including stream specifiers, such as `-c:v:0`) of args,
+/// which are encoders in output args and decoders in input args.
+/// Stream copy is ignored.
+pub fn codec_args(args: &[String]) -> Vec<&str> {
+    let mut codecs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let is_codec_arg = match arg.split_once(':') {
+            Some((name, _)) => name == "-c" || name == "-codec",
+            None => ["-c", "-codec", "-vcodec", "-acodec", "-scodec"].contains(&arg.as_str()),
+        };
+        if !is_codec_arg {
+            continue;
+        }
+
+        if let Some(codec) = args.next() {
+            if codec != "copy" {
+                codecs.push(codec.as_str());
+            }
+        }
+    }
+
+    codecs
+}
+
+/// Validates codecs of each output are compatible with the output container format.
+pub fn validate_outputs(args: &TaskArgs) -> Result<(), Error> {
+    for output in args.outputs.iter() {
+        let Some(format) = output_format(output) else {
+            continue;
+        };
+        let Some((_, incompatibles)) = INCOMPATIBLE_CODECS.iter().find(|(f, _)| *f == format)
+        else {
+            continue;
+        };
+
+        if let Some(codec) = output_codecs(output).into_iter().find(|codec| {
+            incompatibles
+                .iter()
+                .any(|incompatible| incompatible == codec)
+        }) {
+            return Err(Error::incompatible_output(codec, format));
+        }
+    }
+
+    Ok(())
+}
+
+/// Probed codecs and codec types of input streams that output container formats can't hold,
+/// as `(container, codec types, codecs)`.
+///
+/// Unlike [`INCOMPATIBLE_CODECS`] listing encoders, codecs here are codecs of input streams,
+/// which fail either copied or encoded by default encoder of container,
+/// such as bitmap subtitles never converted to text subtitles.
+static UNSUPPORTED_STREAMS: [(
+    &'static str,
+    &'static [&'static str],
+    &'static [&'static str],
+); 4] = [
+    (
+        "mp4",
+        &["attachment"],
+        &[
+            "hdmv_pgs_subtitle",
+            "dvb_subtitle",
+            "dvd_subtitle",
+            "dvb_teletext",
+            "xsub",
+            "bin_data",
+        ],
+    ),
+    (
+        "mov",
+        &["attachment"],
+        &[
+            "hdmv_pgs_subtitle",
+            "dvb_subtitle",
+            "dvd_subtitle",
+            "dvb_teletext",
+            "xsub",
+            "bin_data",
+        ],
+    ),
+    (
+        "webm",
+        &["attachment", "data"],
+        &[
+            "hdmv_pgs_subtitle",
+            "dvb_subtitle",
+            "dvd_subtitle",
+            "dvb_teletext",
+            "xsub",
+        ],
+    ),
+    (
+        "avi",
+        &["attachment", "data"],
+        &[
+            "hdmv_pgs_subtitle",
+            "dvb_subtitle",
+            "dvd_subtitle",
+            "dvb_teletext",
+        ],
+    ),
+];
+
+/// Streams selected by a `-map` arg, such as `0`, `-0:s`, `0:s:1`, `0:3` or `1:a?`.
+struct MapSpecifier {
+    /// Negative maps, prefixed by `-`, deselect streams selected by previous maps.
+    negative: bool,
+    input: usize,
+    codec_type: Option<&'static str>,
+    /// Index among streams of `codec_type` if specified, otherwise absolute stream index.
+    index: Option<usize>,
+}
+
+impl MapSpecifier {
+    /// Parses value of a `-map` arg.
+    ///
+    /// Filter graph labels and stream specifiers of other forms,
+    /// such as program, metadata or stream id specifiers, are never parsed and return `None`.
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.strip_suffix('?').unwrap_or(value);
+        let (negative, value) = match value.strip_prefix('-') {
+            Some(value) => (true, value),
+            None => (false, value),
+        };
+
+        let mut parts = value.split(':');
+        let input = parts.next()?.parse::<usize>().ok()?;
+        let (codec_type, index) = match (parts.next(), parts.next(), parts.next()) {
+            (None, _, _) => (None, None),
+            (Some(index), None, _) if index.parse::<usize>().is_ok() => {
+                (None, Some(index.parse::<usize>().ok()?))
+            }
+            (Some(codec_type), index, None) => {
+                let codec_type = match codec_type {
+                    "v" | "V" => "video",
+                    "a" => "audio",
+                    "s" => "subtitle",
+                    "d" => "data",
+                    "t" => "attachment",
+                    _ => return None,
+                };
+                let index = match index {
+                    Some(index) => Some(index.parse::<usize>().ok()?),
+                    None => None,
+                };
+                (Some(codec_type), index)
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            negative,
+            input,
+            codec_type,
+            index,
+        })
+    }
+
+    /// Returns streams of input matching this map.
+    fn select<'a>(&self, streams: &'a [ProbeStream]) -> Vec<&'a ProbeStream> {
+        let candidates = streams.iter().filter(|stream| match self.codec_type {
+            Some(codec_type) => stream.codec_type.as_deref() == Some(codec_type),
+            None => true,
+        });
+        match (self.codec_type, self.index) {
+            (_, None) => candidates.collect(),
+            (None, Some(index)) => candidates.filter(|stream| stream.index == index).collect(),
+            (Some(_), Some(index)) => candidates.skip(index).take(1).collect(),
+        }
+    }
+}
+
+/// Returns `true` if any output maps streams explicitly by `-map`
+/// into a container format listed in [`UNSUPPORTED_STREAMS`],
+/// in which case inputs should be probed by [`validate_mapped_streams`].
+pub fn has_mapped_streams_to_check(args: &TaskArgs) -> bool {
+    args.outputs.iter().any(|output| {
+        output.args.iter().any(|arg| arg == "-map")
+            && output_format(output)
+                .map(|format| UNSUPPORTED_STREAMS.iter().any(|(f, _, _)| *f == format))
+                .unwrap_or(false)
+    })
+}
+
+/// Validates streams mapped explicitly by `-map` of each output can be held by the output container,
+/// cross-referencing probed streams of inputs, indexed by input, against [`UNSUPPORTED_STREAMS`].
+///
+/// Inputs never probed, such as device and protocol inputs, are skipped,
+/// as well as outputs without `-map`, since default stream selection of ffmpeg
+/// skips streams the container can't hold.
+pub fn validate_mapped_streams(
+    args: &TaskArgs,
+    input_streams: &[Option<Vec<ProbeStream>>],
+) -> Result<(), Error> {
+    for output in args.outputs.iter() {
+        let Some(format) = output_format(output) else {
+            continue;
+        };
+        let Some((_, codec_types, codecs)) =
+            UNSUPPORTED_STREAMS.iter().find(|(f, _, _)| *f == format)
+        else {
+            continue;
+        };
+
+        let mut selected: Vec<(usize, &ProbeStream)> = Vec::new();
+        let mut args = output.args.iter();
+        while let Some(arg) = args.next() {
+            if arg != "-map" {
+                continue;
+            }
+
+            let Some(map) = args.next().and_then(|value| MapSpecifier::parse(value)) else {
+                continue;
+            };
+            let Some(Some(streams)) = input_streams.get(map.input) else {
+                continue;
+            };
+
+            let matched = map.select(streams);
+            if map.negative {
+                selected.retain(|(input, stream)| {
+                    *input != map.input || !matched.iter().any(|m| m.index == stream.index)
+                });
+            } else {
+                selected.extend(matched.into_iter().map(|stream| (map.input, stream)));
+            }
+        }
+
+        for (input, stream) in selected {
+            let codec_type = stream.codec_type.as_deref().unwrap_or_default();
+            let codec = stream.codec_name.as_deref().unwrap_or(codec_type);
+            if codec_types.contains(&codec_type) || codecs.contains(&codec) {
+                return Err(Error::unsupported_stream_in_container(
+                    codec,
+                    format,
+                    format!("{}:{}", input, stream.index),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns streams, as `(video, audio)`, that an output stream copies by
+/// `-c copy`, `-codec copy`, `-vcodec copy`, `-acodec copy` or codec args with stream specifiers.
+pub fn copied_streams(output: &TaskOutputArgs) -> (bool, bool) {
+    let (mut video, mut audio) = (false, false);
+    let mut args = output.args.iter();
+    while let Some(arg) = args.next() {
+        let (name, specifier) = match arg.split_once(':') {
+            Some((name, specifier)) => (name, Some(specifier)),
+            None => (arg.as_str(), None),
+        };
+        let (copies_video, copies_audio) = match (name, specifier) {
+            ("-c" | "-codec", None) => (true, true),
+            ("-c" | "-codec", Some(specifier)) => {
+                (specifier.starts_with('v'), specifier.starts_with('a'))
+            }
+            ("-vcodec", _) => (true, false),
+            ("-acodec", _) => (false, true),
+            _ => continue,
+        };
+
+        if args.next().map(|codec| codec == "copy").unwrap_or(false) {
+            video |= copies_video;
+            audio |= copies_audio;
+        }
+    }
+
+    (video, audio)
+}
+
+/// Finds first filter arg of an output, such as `-vf`, `-af` or `-filter:v`,
+/// applying to a stream which is stream copied at the same time.
+///
+/// Stream copy bypasses decoding, so ffmpeg either fails or ignores the filter then.
+/// `-filter_complex` is not checked since it maps streams explicitly.
+pub fn find_copy_with_filter(args: &TaskArgs) -> Option<&str> {
+    for output in args.outputs.iter() {
+        let (copies_video, copies_audio) = copied_streams(output);
+        if !copies_video && !copies_audio {
+            continue;
+        }
+
+        let filter = output.args.iter().find(|arg| {
+            let (filters_video, filters_audio) = match arg.split_once(':') {
+                Some(("-filter", specifier)) => {
+                    (specifier.starts_with('v'), specifier.starts_with('a'))
+                }
+                Some(_) => (false, false),
+                None => match arg.as_str() {
+                    "-vf" => (true, false),
+                    "-af" => (false, true),
+                    "-filter" => (true, true),
+                    _ => (false, false),
+                },
+            };
+            (copies_video && filters_video) || (copies_audio && filters_audio)
+        });
+        if let Some(filter) = filter {
+            return Some(filter.as_str());
+        }
+    }
+
+    None
+}
+
+/// Validates hardware acceleration methods specified by `-hwaccel` in input args
+/// are supported by ffmpeg. `auto` and `none` are always accepted.
+pub fn validate_hwaccels(args: &TaskArgs, hwaccels: &[String]) -> Result<(), Error> {
+    for input in args.inputs.iter() {
+        let mut input_args = input.args.iter();
+        while let Some(arg) = input_args.next() {
+            if arg != "-hwaccel" {
+                continue;
+            }
+
+            let Some(name) = input_args.next() else {
+                break;
+            };
+            if name != "auto" && name != "none" && !hwaccels.contains(name) {
+                return Err(Error::hwaccel_unavailable(name));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates a task before launching.
+pub fn validate_task(args: &TaskArgs, context: &ValidationContext) -> Result<(), Error> {
+    validate_inputs(args, context.input_protocols.as_deref())?;
+    if let Some(hwaccels) = &context.hwaccels {
+        validate_hwaccels(args, hwaccels)?;
+    }
+    validate_outputs(args)?;
+    Ok(())
+}
+
+/// Finds first file output of a finished task which is zero bytes.
+///
+/// Partial paths are checked instead if `atomic_output` enabled, since outputs are not renamed yet.
+/// Outputs not existing are skipped, such as outputs of `segment` muxer using path patterns.
+pub fn find_empty_output(args: &TaskArgs) -> Option<&str> {
+    let partial_outputs = args.partial_outputs();
+    args.outputs
+        .iter()
+        .filter_map(|output| output.path.as_ref())
+        .filter(|path| path.as_str() != "-" && !has_protocol_scheme(path))
+        .find(|path| {
+            let path = PathBuf::from(path);
+            let path = partial_outputs
+                .iter()
+                .find(|(_, final_path)| *final_path == path)
+                .map(|(partial_path, _)| partial_path)
+                .unwrap_or(&path);
+            path.metadata()
+                .map(|metadata| metadata.is_file() && metadata.len() == 0)
+                .unwrap_or(false)
+        })
+        .map(|path| path.as_str())
+}
+
+/// Normalizes a path lexically into an absolute path, without requiring it to exist.
+///
+/// Relative paths are resolved against current directory,
+/// `.` components are dropped and `..` components pop the previous component.
+/// Symlinks are never resolved, unlike [`Path::canonicalize`],
+/// hence `..` following a symlink may point to another directory than the file system does.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            // popping never goes above root or prefix
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// Resolves output paths of a task to absolute paths for comparison.
+///
+/// Null outputs, stdout and protocol outputs are excluded.
+/// Since outputs may not exist yet, only parent directories are canonicalized then,
+/// and paths are only normalized by [`normalize_path`] if parent directories don't exist either.
+pub fn resolved_output_paths(args: &TaskArgs) -> Vec<PathBuf> {
+    args.outputs
+        .iter()
+        .filter_map(|output| output.path.as_ref())
+        .filter(|path| path.as_str() != "-" && !has_protocol_scheme(path))
+        .map(|path| {
+            let path = normalize_path(Path::new(path));
+            if let Ok(canonicalized) = path.canonicalize() {
+                return canonicalized;
+            }
+
+            match (path.parent(), path.file_name()) {
+                (Some(parent), Some(file_name)) => parent
+                    .canonicalize()
+                    .map(|parent| parent.join(file_name))
+                    .unwrap_or(path.clone()),
+                _ => path,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::handlers::{
+        commands::{
+            probe::ProbeStream,
+            task::{TaskArgs, TaskInputArgs, TaskOutputArgs},
+        },
+        error::Error,
+    };
+
+    use super::{
+        find_copy_with_filter, find_empty_output, has_mapped_streams_to_check, is_file_input,
+        normalize_path, reads_stdin, resolved_output_paths, validate_hwaccels, validate_inputs,
+        validate_mapped_streams, validate_output_formats, validate_outputs, validate_shape,
+        ValidationReason,
+    };
+
+    fn input_args(path: &str) -> TaskArgs {
+        TaskArgs {
+            inputs: vec![TaskInputArgs {
+                path: path.to_string(),
+                args: vec![],
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rejects_zero_byte_file_inputs() {
+        let path = std::env::temp_dir().join(format!("empty-{}.mp4", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"").unwrap();
+
+        let result = validate_inputs(&input_args(&path.to_string_lossy()), None);
+        assert!(matches!(result, Err(Error::EmptyOrInvalidInput { .. })));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn accepts_char_device_inputs() {
+        assert!(validate_inputs(&input_args("/dev/null"), None).is_ok());
+    }
+
+    fn stream(index: usize, codec_type: &str, codec_name: &str) -> ProbeStream {
+        serde_json::from_value(serde_json::json!({
+            "index": index,
+            "codec_type": codec_type,
+            "codec_name": codec_name,
+        }))
+        .unwrap()
+    }
+
+    /// Streams of a blu-ray remux, with a PGS subtitle at index `2`.
+    fn bluray_streams() -> Vec<Option<Vec<ProbeStream>>> {
+        vec![Some(vec![
+            stream(0, "video", "h264"),
+            stream(1, "audio", "ac3"),
+            stream(2, "subtitle", "hdmv_pgs_subtitle"),
+        ])]
+    }
+
+    fn mapped_args(path: &str, maps: &[&str]) -> TaskArgs {
+        TaskArgs {
+            inputs: vec![TaskInputArgs {
+                path: "in.mkv".to_string(),
+                args: vec![],
+            }],
+            outputs: vec![TaskOutputArgs {
+                path: Some(path.to_string()),
+                args: maps
+                    .iter()
+                    .flat_map(|map| ["-map".to_string(), map.to_string()])
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rejects_pgs_mapped_into_mp4() {
+        for maps in [&["0"][..], &["0:s"], &["0:s:0"], &["0:2"], &["0:v", "0:s?"]] {
+            let args = mapped_args("out.mp4", maps);
+            assert!(has_mapped_streams_to_check(&args));
+            assert!(
+                matches!(
+                    validate_mapped_streams(&args, &bluray_streams()),
+                    Err(Error::UnsupportedStreamInContainer { .. })
+                ),
+                "maps {maps:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_pgs_deselected_or_not_mapped_into_mp4() {
+        for maps in [&["0", "-0:s"][..], &["0:v", "0:a"], &["0:1"]] {
+            let args = mapped_args("out.mp4", maps);
+            assert!(
+                validate_mapped_streams(&args, &bluray_streams()).is_ok(),
+                "maps {maps:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_pgs_mapped_into_mkv() {
+        let args = mapped_args("out.mkv", &["0"]);
+        assert!(!has_mapped_streams_to_check(&args));
+        assert!(validate_mapped_streams(&args, &bluray_streams()).is_ok());
+    }
+
+    #[test]
+    fn skips_inputs_never_probed() {
+        let args = mapped_args("out.mp4", &["0"]);
+        assert!(validate_mapped_streams(&args, &[None]).is_ok());
+    }
+
+    fn output_args(path: &str, format: &str) -> TaskArgs {
+        TaskArgs {
+            outputs: vec![TaskOutputArgs {
+                path: Some(path.to_string()),
+                args: vec!["-f".to_string(), format.to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_agreeing_output_formats() {
+        for (path, format) in [
+            ("out.mp4", "mp4"),
+            ("out.m4a", "ipod"),
+            ("out.mkv", "webm"),
+            ("out.ogg", "opus"),
+            ("out.ogg", "oga"),
+            ("out.wav", "segment"),
+            ("out.mp4", "tee"),
+            ("out.ts", "hls"),
+            ("out.gif", "image2"),
+            ("out.xyz", "mp4"),
+        ] {
+            assert!(
+                validate_output_formats(&output_args(path, format)).is_ok(),
+                "-f {format} {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_disagreeing_output_formats() {
+        for (path, format) in [
+            ("out.mp4", "matroska"),
+            ("out.mkv", "mp4"),
+            ("out.ogg", "mp3"),
+            ("out.flac", "opus"),
+        ] {
+            assert!(
+                matches!(
+                    validate_output_formats(&output_args(path, format)),
+                    Err(Error::OutputFormatMismatch { .. })
+                ),
+                "-f {format} {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_missing_file_inputs() {
+        let path = std::env::temp_dir().join(format!("missing-{}.mp4", uuid::Uuid::new_v4()));
+        let result = validate_inputs(&input_args(&path.to_string_lossy()), None);
+        assert!(matches!(result, Err(Error::InputNotFound { .. })));
+    }
+
+    #[test]
+    fn never_regards_devices_protocols_or_stdin_as_files() {
+        let input = |path: &str, args: &[&str]| TaskInputArgs {
+            path: path.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        };
+        assert!(!is_file_input(&input("testsrc", &["-f", "lavfi"])));
+        assert!(!is_file_input(&input("video=Camera", &["-f", "dshow"])));
+        assert!(!is_file_input(&input("http://example.com/a.mp4", &[])));
+        assert!(!is_file_input(&input("pipe:0", &[])));
+        assert!(!is_file_input(&input("-", &[])));
+
+        assert!(is_file_input(&input("a.mp4", &["-f", "mp4"])));
+        // single letter schemes are drive letters
+        assert!(is_file_input(&input("C:\\media\\a.mp4", &[])));
+    }
+
+    fn encoded_args(path: &str, args: &[&str]) -> TaskArgs {
+        TaskArgs {
+            outputs: vec![TaskOutputArgs {
+                path: Some(path.to_string()),
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn validates_codecs_against_containers() {
+        // (path, args, compatible)
+        let cases: [(&str, &[&str], bool); 10] = [
+            ("out.mp4", &["-c:v", "libx264", "-c:a", "aac"], true),
+            ("out.mp4", &["-c:a", "flac"], false),
+            ("out.mp4", &["-c:s", "subrip"], false),
+            ("out.mp4", &["-c", "copy"], true),
+            ("out.webm", &["-c:v", "libvpx-vp9", "-c:a", "libopus"], true),
+            ("out.webm", &["-vcodec", "libx264"], false),
+            ("out.mkv", &["-c:s", "mov_text"], false),
+            ("out.mkv", &["-c:s", "subrip"], true),
+            ("out.ts", &["-scodec", "webvtt"], false),
+            ("out.bin", &["-f", "avi", "-c:a:0", "libopus"], false),
+        ];
+        for (path, args, compatible) in cases {
+            let result = validate_outputs(&encoded_args(path, args));
+            if compatible {
+                assert!(result.is_ok(), "{path} {args:?}");
+            } else {
+                assert!(
+                    matches!(result, Err(Error::IncompatibleOutput { .. })),
+                    "{path} {args:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn validates_input_schemes_against_protocols() {
+        let protocols = vec!["file".to_string(), "http".to_string(), "pipe".to_string()];
+        assert!(validate_inputs(&input_args("HTTP://example.com/a.mp4"), Some(&protocols)).is_ok());
+        assert!(validate_inputs(&input_args("pipe:0"), Some(&protocols)).is_ok());
+        assert!(matches!(
+            validate_inputs(&input_args("srt://example.com:9000"), Some(&protocols)),
+            Err(Error::UnsupportedProtocol { protocol }) if protocol == "srt"
+        ));
+        // never validated without protocols detected
+        assert!(validate_inputs(&input_args("srt://example.com:9000"), None).is_ok());
+    }
+
+    #[test]
+    fn validates_input_hwaccels_against_detected() {
+        let hwaccel_args = |method: &str| TaskArgs {
+            inputs: vec![TaskInputArgs {
+                path: "in.mp4".to_string(),
+                args: vec!["-hwaccel".to_string(), method.to_string()],
+            }],
+            ..Default::default()
+        };
+        let hwaccels = vec!["vaapi".to_string(), "cuda".to_string()];
+        for method in ["cuda", "auto", "none"] {
+            assert!(
+                validate_hwaccels(&hwaccel_args(method), &hwaccels).is_ok(),
+                "{method}"
+            );
+        }
+        assert!(matches!(
+            validate_hwaccels(&hwaccel_args("qsv"), &hwaccels),
+            Err(Error::HwaccelUnavailable { name }) if name == "qsv"
+        ));
+    }
+
+    #[test]
+    fn finds_filters_of_copied_streams() {
+        let cases: [(&[&str], Option<&str>); 7] = [
+            (&["-c", "copy", "-vf", "scale=640:-2"], Some("-vf")),
+            (&["-c:v", "copy", "-filter:v", "yadif"], Some("-filter:v")),
+            (&["-acodec", "copy", "-af", "volume=2"], Some("-af")),
+            (&["-c:v", "copy", "-af", "volume=2"], None),
+            (&["-c:a", "copy", "-c:v", "libx264", "-vf", "yadif"], None),
+            (&["-c", "libx264", "-vf", "yadif"], None),
+            (&["-c", "copy", "-filter_complex", "[0:v]yadif"], None),
+        ];
+        for (output_args, expected) in cases {
+            let args = encoded_args("out.mkv", output_args);
+            assert_eq!(find_copy_with_filter(&args), expected, "{output_args:?}");
+        }
+    }
+
+    #[test]
+    fn finds_zero_byte_outputs() {
+        let dir = std::env::temp_dir().join(format!("outputs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let (filled, empty) = (dir.join("filled.mp4"), dir.join("empty.mp4"));
+        std::fs::write(&filled, b"filled").unwrap();
+        std::fs::write(&empty, b"").unwrap();
+
+        let mut args = TaskArgs {
+            outputs: [&filled, &empty]
+                .into_iter()
+                .map(|path| TaskOutputArgs {
+                    path: Some(path.to_string_lossy().to_string()),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let expected = empty.to_string_lossy();
+        assert_eq!(find_empty_output(&args), Some(expected.as_ref()));
+
+        // partial outputs are checked instead of final paths if encoded atomically
+        args.atomic_output = true;
+        assert_eq!(find_empty_output(&args), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn collects_errors_of_all_invalid_fields() {
+        let args = TaskArgs {
+            inputs: vec![TaskInputArgs {
+                path: "  ".to_string(),
+                args: vec![],
+            }],
+            progress_log: Some(String::new()),
+            ..Default::default()
+        };
+        let errors = validate_shape(&args)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| (error.field, error.reason))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                ("outputs".to_string(), ValidationReason::Empty),
+                ("inputs[0].path".to_string(), ValidationReason::EmptyPath),
+                ("progress_log".to_string(), ValidationReason::EmptyPath),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_null_outputs() {
+        let mut args = input_args("in.mp4");
+        args.outputs = vec![TaskOutputArgs::default()];
+        assert!(validate_shape(&args).is_ok());
+
+        args.outputs[0].path = Some(String::new());
+        let errors = validate_shape(&args).unwrap_err();
+        assert_eq!(errors[0].field, "outputs[0].path");
+    }
+
+    #[test]
+    fn detects_stdin_inputs() {
+        for path in ["-", "pipe:", "pipe:0"] {
+            assert!(reads_stdin(&input_args(path)), "{path}");
+        }
+        for path in ["pipe:3", "in.mkv", "file:-"] {
+            assert!(!reads_stdin(&input_args(path)), "{path}");
+        }
+    }
+
+    #[test]
+    fn normalizes_paths_lexically() {
+        let root = std::env::temp_dir().join(format!("missing-{}", uuid::Uuid::new_v4()));
+        assert_eq!(
+            normalize_path(&root.join("a/./b/../out.mp4")),
+            root.join("a/out.mp4")
+        );
+        assert!(normalize_path(Path::new("out.mp4")).is_absolute());
+    }
+
+    #[test]
+    fn resolves_nonexistent_output_paths() {
+        let dir = std::env::temp_dir().join(format!("outputs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let canonical_dir = dir.canonicalize().unwrap();
+
+        // parent exists but output doesn't yet
+        let path = dir.join("sub/../out.mp4");
+        assert_eq!(
+            resolved_output_paths(&encoded_args(&path.to_string_lossy(), &[])),
+            vec![canonical_dir.join("out.mp4")]
+        );
+
+        // neither parent nor output exists
+        let path = dir.join("missing/./out.mp4");
+        assert_eq!(
+            resolved_output_paths(&encoded_args(&path.to_string_lossy(), &[])),
+            vec![dir.join("missing/out.mp4")]
+        );
+
+        // stdout and protocol outputs are excluded
+        for path in ["-", "udp://127.0.0.1:1234"] {
+            assert!(resolved_output_paths(&encoded_args(path, &[])).is_empty());
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
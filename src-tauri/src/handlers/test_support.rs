@@ -0,0 +1,22 @@
+//! Helpers shared by tests of handlers.
+
+/// Creates a fake ffprobe printing the json of each input by its file name from `outputs`,
+/// returning its directory and program.
+///
+/// Each probed path, which is the last argument, is recorded into `calls` of the directory.
+#[cfg(unix)]
+pub fn fake_ffprobe(outputs: &[(&str, serde_json::Value)]) -> (std::path::PathBuf, String) {
+    let dir = std::env::temp_dir().join(format!("ffprobe-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir(&dir).unwrap();
+    for (name, json) in outputs {
+        std::fs::write(dir.join(format!("{name}.json")), json.to_string()).unwrap();
+    }
+    let script = format!(
+        "for last; do :; done\necho \"$last\" >> {0}/calls\ncat \"{0}/$last.json\"\n",
+        dir.display()
+    );
+    std::fs::write(dir.join("ffprobe.sh"), script).unwrap();
+
+    let ffprobe = format!("sh {}", dir.join("ffprobe.sh").display());
+    (dir, ffprobe)
+}
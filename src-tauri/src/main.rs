@@ -3,38 +3,50 @@
 
 use std::{path::PathBuf, sync::Arc};
 
-use handlers::{config::Config, tasks::store::TaskStore};
+use handlers::{
+    config::Config,
+    tasks::{
+        history::{TaskHistory, HISTORY_FILE},
+        store::TaskStore,
+    },
+};
 use log::{error, LevelFilter};
 use safe_exit::prevent_main_window_close;
+use single_instance::single_instance;
 use system_tray::{system_tray, system_tray_event};
 use tauri::Manager;
 use tauri_plugin_log::{LogTarget, RotationStrategy};
 use tokio::sync::Mutex;
 
 use crate::handlers::commands::{
-    fs::{search_directory, write_text_file},
-    system::{load_configuration, verify_directory, verify_ffmpeg, verify_ffprobe},
-    task::{media_metadata, pause_task, resume_task, start_task, stop_task},
+    fs::{expand_directory, file_hash, is_stable, search_directory, write_text_file},
+    probe::{
+        ffprobe_query, filmstrip, media_summary, probe_chapters, probe_color, probe_streams,
+        validate_filtergraph,
+    },
+    system::{
+        effective_config, load_configuration, supported_protocols, validate_presets,
+        verify_directory, verify_ffmpeg, verify_ffprobe,
+    },
+    task::{
+        active_task_count, capture_output, clear_all, clear_metadata_cache,
+        clear_metadata_cache_entry, encode_preview, estimate_batch, media_metadata,
+        normalize_loudness, pause_task, preview_command, probe_many, reset_task, restart_task,
+        resume_task, set_max_concurrent, split_by_time, start_batch, start_task, stop_task,
+        stop_tasks_where, task_history, update_task_args, MetadataCache,
+    },
 };
 
 pub mod handlers;
 pub mod safe_exit;
+pub mod single_instance;
 pub mod system_tray;
 
-#[derive(Clone, serde::Serialize)]
-struct Payload {
-    args: Vec<String>,
-    cwd: String,
-}
-
 /// Starts application.
 fn start_app() -> Result<(), tauri::Error> {
     tauri::Builder::default()
         .plugin(tauri_plugin_window_state::Builder::default().build())
-        .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
-            app.emit_all("single-instance", Payload { args: argv, cwd })
-                .unwrap();
-        }))
+        .plugin(tauri_plugin_single_instance::init(single_instance))
         .plugin(
             tauri_plugin_log::Builder::default()
                 .timezone_strategy(tauri_plugin_log::TimezoneStrategy::UseUtc)
@@ -50,7 +62,20 @@ fn start_app() -> Result<(), tauri::Error> {
                 .build(),
         )
         .manage(Arc::new(Mutex::new(None as Option<Config>)))
-        .manage(TaskStore::new())
+        .manage(MetadataCache::default())
+        .manage({
+            let task_store = TaskStore::new();
+            task_store.start_sweeper();
+            task_store
+        })
+        .setup(|app| {
+            let history_path = app
+                .path_resolver()
+                .app_data_dir()
+                .map(|dir| dir.join(HISTORY_FILE));
+            app.manage(TaskHistory::new(history_path));
+            Ok(())
+        })
         .system_tray(system_tray())
         .on_system_tray_event(system_tray_event)
         .on_window_event(prevent_main_window_close)
@@ -59,13 +84,44 @@ fn start_app() -> Result<(), tauri::Error> {
             verify_ffprobe,
             verify_directory,
             load_configuration,
+            supported_protocols,
             search_directory,
             write_text_file,
+            is_stable,
             media_metadata,
+            probe_streams,
+            probe_chapters,
             start_task,
             stop_task,
             pause_task,
             resume_task,
+            estimate_batch,
+            encode_preview,
+            normalize_loudness,
+            clear_all,
+            preview_command,
+            restart_task,
+            probe_many,
+            ffprobe_query,
+            stop_tasks_where,
+            active_task_count,
+            effective_config,
+            file_hash,
+            expand_directory,
+            filmstrip,
+            task_history,
+            probe_color,
+            media_summary,
+            split_by_time,
+            clear_metadata_cache,
+            clear_metadata_cache_entry,
+            capture_output,
+            validate_filtergraph,
+            validate_presets,
+            start_batch,
+            update_task_args,
+            set_max_concurrent,
+            reset_task,
         ])
         .run(tauri::generate_context!())
 }
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use log::{error, info};
+use tauri::{AppHandle, Manager, Runtime};
+
+pub static SINGLE_INSTANCE_EVENT: &'static str = "single-instance";
+pub static ENQUEUE_FILES_EVENT: &'static str = "enqueue_files";
+
+/// Extensions of files regarded as media files, lowercased.
+static MEDIA_EXTENSIONS: [&'static str; 30] = [
+    "mp4", "m4v", "mkv", "webm", "mov", "avi", "wmv", "flv", "ts", "m2ts", "mts", "mpg", "mpeg",
+    "3gp", "ogv", "mp3", "m4a", "aac", "flac", "wav", "ogg", "opus", "wma", "ac3", "dts", "ape",
+    "alac", "aiff", "mka", "vob",
+];
+
+#[derive(Clone, serde::Serialize)]
+struct Payload {
+    args: Vec<String>,
+    cwd: String,
+}
+
+/// Finds media files from command line arguments of a second instance.
+/// Relative paths are resolved against `cwd`.
+fn find_media_files(argv: &[String], cwd: &str) -> Vec<String> {
+    argv.iter()
+        // first argument is the program itself
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .map(|arg| {
+            let path = PathBuf::from(arg);
+            if path.is_relative() {
+                PathBuf::from(cwd).join(path)
+            } else {
+                path
+            }
+        })
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .map(|extension| MEDIA_EXTENSIONS.iter().any(|e| *e == extension))
+                .unwrap_or(false)
+        })
+        .filter_map(|path| path.canonicalize().ok())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+/// Handles a second instance launching.
+///
+/// Media files in arguments are sent to main window by [`ENQUEUE_FILES_EVENT`],
+/// and then frontend appends them to the queue.
+pub fn single_instance<R: Runtime>(app: &AppHandle<R>, argv: Vec<String>, cwd: String) {
+    let files = find_media_files(&argv, &cwd);
+    if !files.is_empty() {
+        info!(
+            target: "single_instance",
+            "enqueue {} files from second instance",
+            files.len()
+        );
+        if let Err(err) = app.emit_to("main", ENQUEUE_FILES_EVENT, files) {
+            error!(target: "single_instance", "failed to emit event \"{ENQUEUE_FILES_EVENT}\" to main window: {err}");
+        }
+    }
+
+    if let Err(err) = app.emit_all(SINGLE_INSTANCE_EVENT, Payload { args: argv, cwd }) {
+        error!(target: "single_instance", "failed to emit event \"{SINGLE_INSTANCE_EVENT}\": {err}");
+    }
+}
@@ -4,7 +4,15 @@ use tauri::{
     SystemTrayMenuItem,
 };
 
-use crate::safe_exit::EXIT_REQUEST_EVENT;
+use crate::{
+    handlers::tasks::{
+        priority::{is_background_mode, set_background_mode},
+        store::TaskStore,
+    },
+    safe_exit::EXIT_REQUEST_EVENT,
+};
+
+static BACKGROUND_MODE_ITEM: &'static str = "background_mode";
 
 fn menu() -> SystemTrayMenu {
     let exit = CustomMenuItem::new("exit", "Exit");
@@ -12,11 +20,14 @@ fn menu() -> SystemTrayMenu {
     let start = CustomMenuItem::new("start_all", "Start All Tasks");
     let pause = CustomMenuItem::new("pause_all", "Pause All Tasks");
     let stop = CustomMenuItem::new("stop_all", "Stop All Tasks");
+    let background_mode = CustomMenuItem::new(BACKGROUND_MODE_ITEM, "Background Mode");
     SystemTrayMenu::new()
         .add_item(start)
         .add_item(pause)
         .add_item(stop)
         .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(background_mode)
+        .add_native_item(SystemTrayMenuItem::Separator)
         .add_item(show)
         .add_item(exit)
 }
@@ -24,6 +35,8 @@ fn menu() -> SystemTrayMenu {
 pub static START_ALL_TASKS_EVENT: &'static str = "start_all_tasks";
 pub static PAUSE_ALL_TASKS_EVENT: &'static str = "pause_all_tasks";
 pub static STOP_ALL_TASKS_EVENT: &'static str = "stop_all_tasks";
+/// Event telling frontend background mode is toggled, with a `bool` payload.
+pub static BACKGROUND_MODE_EVENT: &'static str = "background_mode";
 
 fn display<R: Runtime>(app: &AppHandle<R>) {
     let Some(window) = app.get_window("main") else {
@@ -46,6 +59,37 @@ fn emit<R: Runtime>(app: &AppHandle<R>, event: &str) {
     }
 }
 
+/// Checks or unchecks background mode item in system tray.
+pub fn select_background_mode<R: Runtime>(app: &AppHandle<R>, enabled: bool) {
+    if let Err(err) = app
+        .tray_handle()
+        .get_item(BACKGROUND_MODE_ITEM)
+        .set_selected(enabled)
+    {
+        warn!(target: "system_tray", "failed to select background mode item: {err}");
+    }
+}
+
+/// Toggles background mode, which applies to all running and future tasks.
+/// Frontend is informed for persisting background mode into configuration.
+fn toggle_background_mode<R: Runtime>(app: &AppHandle<R>) {
+    let enabled = !is_background_mode();
+    set_background_mode(enabled);
+    select_background_mode(app, enabled);
+
+    let app_cloned = app.clone();
+    tauri::async_runtime::spawn(async move {
+        app_cloned
+            .state::<TaskStore>()
+            .apply_priority(enabled)
+            .await;
+    });
+
+    if let Err(err) = app.emit_to("main", BACKGROUND_MODE_EVENT, enabled) {
+        error!(target: "system_tray", "failed to emit event \"{BACKGROUND_MODE_EVENT}\" to main window: {err}");
+    }
+}
+
 pub fn system_tray_event<R: Runtime>(app: &AppHandle<R>, event: SystemTrayEvent) {
     match event {
         SystemTrayEvent::MenuItemClick { id, .. } => match id.as_str() {
@@ -54,6 +98,7 @@ pub fn system_tray_event<R: Runtime>(app: &AppHandle<R>, event: SystemTrayEvent)
             "start_all" => emit(app, START_ALL_TASKS_EVENT),
             "pause_all" => emit(app, PAUSE_ALL_TASKS_EVENT),
             "stop_all" => emit(app, STOP_ALL_TASKS_EVENT),
+            "background_mode" => toggle_background_mode(app),
             _ => {}
         },
         SystemTrayEvent::LeftClick { .. } => display(app),
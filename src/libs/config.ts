@@ -62,6 +62,26 @@ export type Configuration = {
    * FFprobe binary program
    */
   ffprobe: string;
+  /**
+   * Args placed before inputs of every task
+   */
+  defaultGlobalArgs?: string[];
+  /**
+   * Args applied to every file output of every task
+   */
+  defaultOutputArgs?: string[];
+  /**
+   * Runs ffmpeg processes in low priority, toggled by system tray
+   */
+  backgroundMode?: boolean;
+  /**
+   * Keeps ffmpeg processes running if app is killed without shutting down
+   */
+  detachChildren?: boolean;
+  /**
+   * Simulates tasks without spawning ffmpeg, for development only
+   */
+  dryRun?: boolean;
   /**
    * Hardware acceleration for {@link TaskArgsSource.Auto} codec
    */
@@ -1,5 +1,6 @@
 export type SystemParticulars = {
   path_separator: string;
+  logical_cores: number;
   ffmpeg: FFmpegParticulars;
 };
 
@@ -7,6 +8,7 @@ export type FFmpegParticulars = {
   banner: FFmpegBanner;
   codecs: FFmpegCodec[];
   hwaccels: string[];
+  formats: FFmpegFormat[];
 };
 
 export type FFmpegBanner = {
@@ -17,6 +19,13 @@ export type FFmpegBanner = {
   libraries: Record<string, number[]>;
 };
 
+export type FFmpegFormat = {
+  name: string;
+  description: string;
+  demuxing: boolean;
+  muxing: boolean;
+};
+
 export enum FFmpegCodecType {
   Video = 0,
   Audio = 1,
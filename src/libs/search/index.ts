@@ -6,6 +6,7 @@ export type SearchDirectory = {
   relative: string;
   name?: string;
   children: SearchEntry[];
+  has_children: boolean;
 };
 
 export type SearchFile = {
@@ -1,9 +1,11 @@
 import { listen } from "@tauri-apps/api/event";
+import { useAppStore } from "../store/app";
 import { useTaskStore } from "../store/task";
 
 export const START_ALL_TASKS_EVENT = "start_all_tasks";
 export const PAUSE_ALL_TASKS_EVENT = "pause_all_tasks";
 export const STOP_ALL_TASKS_EVENT = "stop_all_tasks";
+export const BACKGROUND_MODE_EVENT = "background_mode";
 
 /**
  * Starts listening system tray event from backend
@@ -17,3 +19,6 @@ listen<void>(PAUSE_ALL_TASKS_EVENT, async () => {
 listen<void>(STOP_ALL_TASKS_EVENT, async () => {
   await useTaskStore.getState().stopAllTasks();
 });
+listen<boolean>(BACKGROUND_MODE_EVENT, (event) => {
+  useAppStore.getState().updateConfiguration({ backgroundMode: event.payload });
+});
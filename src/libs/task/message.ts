@@ -4,13 +4,30 @@ import { notifyError, notifyFinish } from "../notification";
 import { Running } from "./state_machine";
 
 export const TASK_MESSAGE_EVENT = "transcoding";
+export const TASK_LIFECYCLE_EVENT = "task-lifecycle";
 
-export type TaskMessage = TaskMessageRunning | TaskMessageFinished | TaskMessageErrored;
+export type TaskMessage =
+  | TaskMessageHeld
+  | TaskMessageRunning
+  | TaskMessageStopped
+  | TaskMessageFinished
+  | TaskMessageErrored
+  | TaskMessageWarning;
+
+export type TaskMessageHeld = {
+  state: "Held";
+  id: string;
+};
 
 export type TaskMessageRunning = {
   state: "Running";
   id: string;
   progress_type: TaskProgressType;
+  /**
+   * Zero based index of current phase, progress of all phases make up the task progress.
+   */
+  phase: number;
+  phases_total: number;
   raw: string[];
   frame?: number;
   fps?: number;
@@ -20,11 +37,56 @@ export type TaskMessageRunning = {
   dup_frames?: number;
   drop_frames?: number;
   speed?: number;
+  cpu_percent?: number;
+  rss_bytes?: number;
+  /**
+   * Overall progress of all phases in [0, 100], computed by backend.
+   * Absent if progress type is unspecified.
+   */
+  percent?: number;
+  eta_ms?: number;
+  /**
+   * Whether frame or output time has ever advanced since task started.
+   */
+  has_activity: boolean;
+  /**
+   * Whether this is the first message after resuming.
+   */
+  resumed: boolean;
+  /**
+   * Unrecognized `key=value` progress fields.
+   */
+  extra: Record<string, string>;
+  unavailable: string[];
+  malformed: string[];
+};
+
+export type TaskStopReason =
+  | "UserRequested"
+  | "BatchAborted"
+  | "Shutdown"
+  | "SizeExceeded"
+  | "Timeout"
+  | "Restarting";
+
+export type TaskMessageStopped = {
+  state: "Stopped";
+  id: string;
+  reason: TaskStopReason;
+};
+
+export type TaskFinalStats = {
+  video_kb: number;
+  audio_kb: number;
+  subtitle_kb: number;
+  other_kb: number;
+  muxing_overhead_percent?: number;
 };
 
 export type TaskMessageFinished = {
   state: "Finished";
   id: string;
+  final_stats: TaskFinalStats[];
 };
 
 export type TaskMessageErrored = {
@@ -33,6 +95,12 @@ export type TaskMessageErrored = {
   reason: string;
 };
 
+export type TaskMessageWarning = {
+  state: "Warning";
+  id: string;
+  warnings: string[];
+};
+
 export type TaskProgressType =
   | TaskProgressTypeUnspecified
   | TaskProgressTypeByDuration
@@ -42,6 +110,7 @@ export type TaskProgressType =
 export type TaskProgressTypeByDuration = {
   type: "ByDuration";
   duration: number;
+  duration_source_input?: number;
 };
 
 export type TaskProgressTypeByFileSize = {
@@ -53,10 +122,32 @@ export type TaskProgressTypeAuto = {
   type: "Auto";
   duration: number;
   file_size: number;
+  duration_source_input?: number;
 };
 
+export type TaskProgressUnspecifiedReason = "NoDurationOrSize" | "NonPositiveDuration";
+
 export type TaskProgressTypeUnspecified = {
   type: "Unspecified";
+  reason: TaskProgressUnspecifiedReason;
+};
+
+export type TaskLifecycleKind =
+  | "task.created"
+  | "task.started"
+  | "task.progress"
+  | "task.paused"
+  | "task.resumed"
+  | "task.finished"
+  | "task.errored"
+  | "task.removed";
+
+export type TaskLifecycleEvent = {
+  event: TaskLifecycleKind;
+  id: string;
+  timestamp: number;
+  percent: number | null;
+  reason: string | null;
 };
 
 /**
@@ -34,7 +34,7 @@ const ProgressBar = ({
     case "ByDuration": {
       const duration = message.progress_type.duration;
       const output_duration = (message.output_time_ms ?? 0) / 1000000;
-      const percent = duration === 0 ? 0 : (output_duration / duration) * 100;
+      const percent = message.percent ?? 0;
 
       // prints eta and speed
       let etaHint = "";
@@ -64,9 +64,7 @@ const ProgressBar = ({
       );
     }
     case "ByFileSize": {
-      const size = message.progress_type.size;
-      const output_size = message.total_size ?? 0;
-      const percent = size === 0 ? 0 : (output_size / size) * 100;
+      const percent = message.percent ?? 0;
 
       // prints speed
       const speedHint = message.speed ? `${message.speed.toFixed(2)}x` : "";
@@ -90,16 +88,8 @@ const ProgressBar = ({
       );
     }
     case "Auto": {
-      // for auto progress, prints the largest percentage
-      const file_size = message.progress_type.file_size;
-      const output_file_size = message.total_size ?? 0;
-      const file_percent = file_size === 0 ? 0 : (output_file_size / file_size) * 100;
-
-      const duration = message.progress_type.duration;
-      const output_duration = (message.output_time_ms ?? 0) / 1000000;
-      const duration_percent = duration === 0 ? 0 : (output_duration / duration) * 100;
-
-      const percent = Math.max(file_percent, duration_percent);
+      // for auto progress, backend takes the largest percentage
+      const percent = message.percent ?? 0;
 
       // prints speed
       const speedHint = message.speed ? `${message.speed.toFixed(2)}x` : "";
@@ -12,7 +12,37 @@ export type TauriError =
   | TaskNotFoundError
   | TaskExistingError
   | ConfigurationNotLoadedError
-  | ConfigurationUnavailableError;
+  | ConfigurationUnavailableError
+  | InputNotFoundError
+  | PreviewDurationOutOfRangeError
+  | IncompatibleOutputError
+  | LoudnessMeasurementFailedError
+  | LoudnessTargetOutOfRangeError
+  | QueueFullError
+  | UnsupportedProtocolError
+  | OutputInUseError
+  | HwaccelUnavailableError
+  | EmptyProgramPathError
+  | BinaryMismatchError
+  | InvalidFFprobeQueryError
+  | CopyWithFilterError
+  | TaskStateNotActionableError
+  | EmptyOrInvalidInputError
+  | EmptyOutputError
+  | BinaryNotExecutableError
+  | InvalidTaskArgsError
+  | MalformedProgramError
+  | FileNotFoundError
+  | UnsupportedHashAlgorithmError
+  | DiskFullError
+  | InvalidOutputFdError
+  | InvalidSegmentArgsError
+  | UnsupportedStreamInContainerError
+  | InvalidCaptureArgsError
+  | CaptureTooLargeError
+  | TaskAlreadyRunningError
+  | OutputFormatMismatchError
+  | OutputFdNotReusableError;
 
 export type InternalError = {
   type: "Internal";
@@ -77,6 +107,168 @@ export type ConfigurationUnavailableError = {
   reasons: TauriError[];
 };
 
+export type InputNotFoundError = {
+  type: "InputNotFound";
+  path: string;
+};
+
+export type PreviewDurationOutOfRangeError = {
+  type: "PreviewDurationOutOfRange";
+  max: number;
+};
+
+export type IncompatibleOutputError = {
+  type: "IncompatibleOutput";
+  codec: string;
+  container: string;
+};
+
+export type LoudnessMeasurementFailedError = {
+  type: "LoudnessMeasurementFailed";
+  reason: string;
+};
+
+export type LoudnessTargetOutOfRangeError = {
+  type: "LoudnessTargetOutOfRange";
+  min: number;
+  max: number;
+};
+
+export type QueueFullError = {
+  type: "QueueFull";
+  max: number;
+};
+
+export type UnsupportedProtocolError = {
+  type: "UnsupportedProtocol";
+  protocol: string;
+};
+
+export type OutputInUseError = {
+  type: "OutputInUse";
+  path: string;
+};
+
+export type HwaccelUnavailableError = {
+  type: "HwaccelUnavailable";
+  name: string;
+};
+
+export type EmptyProgramPathError = {
+  type: "EmptyProgramPath";
+  name: string;
+};
+
+export type BinaryMismatchError = {
+  type: "BinaryMismatch";
+  expected: string;
+  found: string;
+};
+
+export type InvalidFFprobeQueryError = {
+  type: "InvalidFFprobeQuery";
+  reason: string;
+};
+
+export type CopyWithFilterError = {
+  type: "CopyWithFilter";
+  filter: string;
+};
+
+export type TaskStateNotActionableError = {
+  type: "TaskStateNotActionable";
+  state: string;
+};
+
+export type EmptyOrInvalidInputError = {
+  type: "EmptyOrInvalidInput";
+  path: string;
+};
+
+export type EmptyOutputError = {
+  type: "EmptyOutput";
+  path: string;
+};
+
+export type BinaryNotExecutableError = {
+  type: "BinaryNotExecutable";
+  program: string;
+};
+
+export type TaskArgsValidationError = {
+  field: string;
+  reason: "Empty" | "EmptyPath";
+};
+
+export type InvalidTaskArgsError = {
+  type: "InvalidTaskArgs";
+  errors: TaskArgsValidationError[];
+};
+
+export type MalformedProgramError = {
+  type: "MalformedProgram";
+  program: string;
+};
+
+export type FileNotFoundError = {
+  type: "FileNotFound";
+  path: string;
+};
+
+export type UnsupportedHashAlgorithmError = {
+  type: "UnsupportedHashAlgorithm";
+  algorithm: string;
+};
+
+export type DiskFullError = {
+  type: "DiskFull";
+  path: string;
+};
+
+export type InvalidOutputFdError = {
+  type: "InvalidOutputFd";
+  fd: number;
+};
+
+export type InvalidSegmentArgsError = {
+  type: "InvalidSegmentArgs";
+  reason: string;
+};
+
+export type UnsupportedStreamInContainerError = {
+  type: "UnsupportedStreamInContainer";
+  codec: string;
+  container: string;
+  stream: string;
+};
+
+export type InvalidCaptureArgsError = {
+  type: "InvalidCaptureArgs";
+  reason: string;
+};
+
+export type CaptureTooLargeError = {
+  type: "CaptureTooLarge";
+  max: number;
+};
+
+export type TaskAlreadyRunningError = {
+  type: "TaskAlreadyRunning";
+  id: string;
+};
+
+export type OutputFormatMismatchError = {
+  type: "OutputFormatMismatch";
+  path: string;
+  format: string;
+  implied: string;
+};
+
+export type OutputFdNotReusableError = {
+  type: "OutputFdNotReusable";
+  id: string;
+};
+
 export interface ErrorToMessage {
   (error: TauriError, printKeywords: true): string;
   (error: Pick<TauriError, "type">, printKeywords: false): string;
@@ -121,6 +313,132 @@ export const toMessage: ErrorToMessage = (error, printKeywords = false) => {
     case "ConfigurationNotLoaded":
       return "configuration not loaded";
     case "ConfigurationUnavailable":
-      return "configuration unavailable";
+      return printKeywords
+        ? `configuration unavailable: ${(error as ConfigurationUnavailableError).reasons
+            .map((reason) => toMessage(reason, true))
+            .join(", ")}`
+        : "configuration unavailable";
+    case "InputNotFound":
+      return printKeywords
+        ? `input file ${(error as InputNotFoundError).path} not found`
+        : "input file not found";
+    case "PreviewDurationOutOfRange":
+      return printKeywords
+        ? `preview duration should be no more than ${(error as PreviewDurationOutOfRangeError).max} seconds`
+        : "preview duration out of range";
+    case "IncompatibleOutput":
+      return printKeywords
+        ? `codec ${(error as IncompatibleOutputError).codec} is incompatible with container ${(error as IncompatibleOutputError).container}`
+        : "incompatible codec and container";
+    case "LoudnessMeasurementFailed":
+      return printKeywords
+        ? (error as LoudnessMeasurementFailedError).reason
+        : "loudness measurement failed";
+    case "LoudnessTargetOutOfRange":
+      return printKeywords
+        ? `loudness target should be in [${(error as LoudnessTargetOutOfRangeError).min}, ${(error as LoudnessTargetOutOfRangeError).max}] LUFS`
+        : "loudness target out of range";
+    case "QueueFull":
+      return printKeywords
+        ? `task queue is full, maximum ${(error as QueueFullError).max} tasks`
+        : "task queue is full";
+    case "UnsupportedProtocol":
+      return printKeywords
+        ? `protocol ${(error as UnsupportedProtocolError).protocol} not supported`
+        : "protocol not supported";
+    case "OutputInUse":
+      return printKeywords
+        ? `output ${(error as OutputInUseError).path} is being written by another task`
+        : "output in use";
+    case "HwaccelUnavailable":
+      return printKeywords
+        ? `hardware acceleration ${(error as HwaccelUnavailableError).name} unavailable`
+        : "hardware acceleration unavailable";
+    case "EmptyProgramPath":
+      return printKeywords
+        ? `${(error as EmptyProgramPathError).name} program path is empty`
+        : "program path is empty";
+    case "BinaryMismatch":
+      return printKeywords
+        ? `expected ${(error as BinaryMismatchError).expected} program but found ${(error as BinaryMismatchError).found} program`
+        : "binary mismatch";
+    case "InvalidFFprobeQuery":
+      return printKeywords
+        ? `invalid ffprobe query: ${(error as InvalidFFprobeQueryError).reason}`
+        : "invalid ffprobe query";
+    case "CopyWithFilter":
+      return printKeywords
+        ? `filter ${(error as CopyWithFilterError).filter} is combined with stream copy`
+        : "filter combined with stream copy";
+    case "TaskStateNotActionable":
+      return printKeywords
+        ? `tasks in state ${(error as TaskStateNotActionableError).state} can not be acted upon`
+        : "task state not actionable";
+    case "EmptyOrInvalidInput":
+      return printKeywords
+        ? `input file ${(error as EmptyOrInvalidInputError).path} is empty or invalid`
+        : "input file is empty or invalid";
+    case "EmptyOutput":
+      return printKeywords
+        ? `output file ${(error as EmptyOutputError).path} is empty, nothing was encoded`
+        : "output file is empty, nothing was encoded";
+    case "BinaryNotExecutable":
+      return printKeywords
+        ? `binary ${(error as BinaryNotExecutableError).program} is not executable`
+        : "binary is not executable";
+    case "InvalidTaskArgs":
+      return printKeywords
+        ? `invalid task args: ${(error as InvalidTaskArgsError).errors
+            .map(({ field, reason }) => `${field} ${reason}`)
+            .join(", ")}`
+        : "invalid task args";
+    case "MalformedProgram":
+      return printKeywords
+        ? `program ${(error as MalformedProgramError).program} has an unterminated quote`
+        : "program has an unterminated quote";
+    case "FileNotFound":
+      return printKeywords
+        ? `file ${(error as FileNotFoundError).path} not found`
+        : "file not found";
+    case "UnsupportedHashAlgorithm":
+      return printKeywords
+        ? `hash algorithm ${(error as UnsupportedHashAlgorithmError).algorithm} not supported`
+        : "hash algorithm not supported";
+    case "DiskFull":
+      return printKeywords
+        ? `no space left on device of output ${(error as DiskFullError).path}`
+        : "no space left on device";
+    case "InvalidOutputFd":
+      return printKeywords
+        ? `invalid output file descriptor ${(error as InvalidOutputFdError).fd}`
+        : "invalid output file descriptor";
+    case "InvalidSegmentArgs":
+      return printKeywords
+        ? `invalid segment args: ${(error as InvalidSegmentArgsError).reason}`
+        : "invalid segment args";
+    case "UnsupportedStreamInContainer":
+      return printKeywords
+        ? `stream ${(error as UnsupportedStreamInContainerError).stream} of codec ${(error as UnsupportedStreamInContainerError).codec} is unsupported by container ${(error as UnsupportedStreamInContainerError).container}`
+        : "stream unsupported by container";
+    case "InvalidCaptureArgs":
+      return printKeywords
+        ? `invalid capture args: ${(error as InvalidCaptureArgsError).reason}`
+        : "invalid capture args";
+    case "CaptureTooLarge":
+      return printKeywords
+        ? `captured output exceeds maximum size of ${(error as CaptureTooLargeError).max} bytes`
+        : "captured output is too large";
+    case "TaskAlreadyRunning":
+      return printKeywords
+        ? `task ${(error as TaskAlreadyRunningError).id} already running`
+        : "task already running";
+    case "OutputFormatMismatch":
+      return printKeywords
+        ? `output ${(error as OutputFormatMismatchError).path} is muxed as ${(error as OutputFormatMismatchError).format} by -f, but its extension implies ${(error as OutputFormatMismatchError).implied}`
+        : "output format mismatches extension";
+    case "OutputFdNotReusable":
+      return printKeywords
+        ? `task ${(error as OutputFdNotReusableError).id} writing to output file descriptors can not run again`
+        : "output file descriptors can not be reused";
   }
 };
@@ -12,10 +12,16 @@ import type { DirectoryNotFoundError, IoError } from "./error";
  *
  * @param dir Directory to search in
  * @param maxDepth Max depth should walk in during searching, default for `5`
+ * @param lazy Only expands top two levels if `true`, deeper directories are left with `has_children` hint
+ * @param followSymlinks Walks into symlinked directories if `true`, default for `false`
  * @returns Search result
  */
-export const searchDirectory = async (dir: string, maxDepth?: number) =>
-  await invoke<SearchDirectory>("search_directory", { dir, maxDepth });
+export const searchDirectory = async (
+  dir: string,
+  maxDepth?: number,
+  lazy?: boolean,
+  followSymlinks?: boolean
+) => await invoke<SearchDirectory>("search_directory", { dir, maxDepth, lazy, followSymlinks });
 
 /**
  * Writes text content to specified file.
@@ -15,6 +15,25 @@ import type {
 } from "./error";
 import { useAppStore } from "../store/app";
 
+export type TaskStateCode =
+  | "Queued"
+  | "Held"
+  | "Idle"
+  | "Running"
+  | "Pausing"
+  | "Stopped"
+  | "Finished"
+  | "Errored";
+
+/**
+ * Result of pausing or resuming a task,
+ * `changed` is `false` if task is already in target state.
+ */
+export type TaskTransition = {
+  changed: boolean;
+  state: TaskStateCode;
+};
+
 type NormalizedTaskArgs = {
   inputs: NormalizedTaskArgsItem[];
   outputs: NormalizedTaskArgsItem[];
@@ -98,8 +117,10 @@ export const stopTask = async (id: string) => await invoke<void>("stop_task", {
  * - {@link TaskNotFoundError} if task id not found
  *
  * @param id Task id
+ * @returns Transition result
  */
-export const pauseTask = async (id: string) => await invoke<void>("pause_task", { id });
+export const pauseTask = async (id: string) =>
+  await invoke<TaskTransition>("pause_task", { id });
 
 /**
  * Resumes a task.
@@ -112,8 +133,10 @@ export const pauseTask = async (id: string) => await invoke<void>("pause_task",
  * - {@link TaskNotFoundError} if task id not found
  *
  * @param id Task id
+ * @returns Transition result
  */
-export const resumeTask = async (id: string) => await invoke<void>("resume_task", { id });
+export const resumeTask = async (id: string) =>
+  await invoke<TaskTransition>("resume_task", { id });
 
 /**
  * Gets metadata of a media